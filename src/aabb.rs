@@ -0,0 +1,109 @@
+use crate::point3d::Point3D;
+use crate::ray::Ray;
+
+// Axis-aligned bounding box, used by the BVH to cull whole groups of
+// bodies with a single slab test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3D,
+    pub max: Point3D,
+}
+
+impl Aabb {
+    pub fn new(a: Point3D, b: Point3D) -> Aabb {
+        Aabb {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn centroid(&self) -> Point3D {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let d = self.max - self.min;
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    // Slab test. `inv_direction` is passed in so callers traversing many
+    // boxes with the same ray only compute it once.
+    pub fn hit(&self, ray: &Ray, inv_direction: Point3D, t_min: f64, t_max: f64) -> bool {
+        self.interval(ray, inv_direction, t_min, t_max).is_some()
+    }
+
+    // Part of (t_min, t_max) where the ray is inside the box.
+    pub fn interval(
+        &self,
+        ray: &Ray,
+        inv_direction: Point3D,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(f64, f64)> {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - ray.origin[axis]) * inv_direction[axis];
+            let t1 = (self.max[axis] - ray.origin[axis]) * inv_direction[axis];
+            let (t0, t1) = if inv_direction[axis] < 0.0 {
+                (t1, t0)
+            } else {
+                (t0, t1)
+            };
+            // written so that a NaN from 0 * inf leaves the interval untouched
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aabb_hit() {
+        let aabb = Aabb::new(Point3D::new(-1.0, -1.0, -1.0), Point3D::new(1.0, 1.0, 1.0));
+        let ray = Ray::new(Point3D::new(0.0, 0.0, -5.0), Point3D::new(0.0, 0.0, 1.0));
+        let inv = Point3D::new(1.0, 1.0, 1.0) / ray.direction;
+        assert!(aabb.hit(&ray, inv, 0.0, f64::INFINITY));
+        assert!(!aabb.hit(&ray, inv, 0.0, 3.0));
+
+        let miss = Ray::new(Point3D::new(2.0, 0.0, -5.0), Point3D::new(0.0, 0.0, 1.0));
+        let inv = Point3D::new(1.0, 1.0, 1.0) / miss.direction;
+        assert!(!aabb.hit(&miss, inv, 0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn test_aabb_surrounding() {
+        let a = Aabb::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0));
+        let b = Aabb::new(Point3D::new(2.0, -1.0, 0.5), Point3D::new(3.0, 0.0, 0.5));
+        let c = a.surrounding(&b);
+        assert_eq!(c.min, Point3D::new(0.0, -1.0, 0.0));
+        assert_eq!(c.max, Point3D::new(3.0, 1.0, 1.0));
+        assert_eq!(c.longest_axis(), 0);
+        assert_eq!(a.surface_area(), 6.0);
+    }
+}
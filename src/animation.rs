@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::body::Body;
+use crate::camera::Camera;
+use crate::config::Config;
+
+#[cfg(test)]
+use assert_approx_eq::assert_approx_eq;
+
+// Keyframed changes to a scene over `frames` frames. Every track changes
+// one parameter, named by a path into the scene:
+//
+//   camera.<field>                  any camera field, e.g. camera.look_from
+//   objects.<i>.<translate|rotate|scale>    of a Transform body
+//   objects.<i>.body.<...>          the body inside a Transform
+//   objects.<i>.material.<param>    e.g. albedo, fuzz or index_of_refraction
+//
+// Everything else in the scene, like textures and meshes, is loaded once
+// and kept for all frames.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Animation {
+    pub frames: usize,
+    pub tracks: Vec<Track>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "TrackParams")]
+pub struct Track {
+    pub target: String,
+    pub keys: Vec<Key>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrackParams {
+    pub target: String,
+    pub keys: Vec<Key>,
+}
+
+impl TryFrom<TrackParams> for Track {
+    type Error = String;
+
+    fn try_from(p: TrackParams) -> Result<Self, Self::Error> {
+        let first = p
+            .keys
+            .first()
+            .ok_or_else(|| format!("track {:?} has no keys", p.target))?;
+        let components = first.value.components().len();
+        for pair in p.keys.windows(2) {
+            if pair[1].frame <= pair[0].frame {
+                return Err(format!("keys of track {:?} are not in order", p.target));
+            }
+        }
+        for key in &p.keys {
+            if key.value.components().len() != components {
+                return Err(format!(
+                    "keys of track {:?} have different numbers of values",
+                    p.target
+                ));
+            }
+            if let Easing::Bezier(curve) = key.easing {
+                if !(0.0..=1.0).contains(&curve[0]) || !(0.0..=1.0).contains(&curve[2]) {
+                    return Err(format!(
+                        "track {:?}: Bezier x values must be between 0 and 1",
+                        p.target
+                    ));
+                }
+            }
+        }
+        Ok(Track {
+            target: p.target,
+            keys: p.keys,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Key {
+    pub frame: f64,
+    pub value: KeyValue,
+    // How the value changes on the way to the next key.
+    #[serde(default, skip_serializing_if = "Easing::is_linear")]
+    pub easing: Easing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyValue {
+    Number(f64),
+    Vector(Vec<f64>),
+}
+
+impl KeyValue {
+    fn components(&self) -> &[f64] {
+        match self {
+            KeyValue::Number(x) => std::slice::from_ref(x),
+            KeyValue::Vector(v) => v,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Easing {
+    #[default]
+    Linear,
+    // Holds the value until the next key.
+    Step,
+    // Starts and ends slowly, the same as Bezier([0.42, 0.0, 0.58, 1.0]).
+    EaseInOut,
+    // Timing curve from (0, 0) to (1, 1) with the control points (x1, y1)
+    // and (x2, y2), as in CSS.
+    Bezier([f64; 4]),
+}
+
+impl Easing {
+    fn is_linear(&self) -> bool {
+        *self == Easing::Linear
+    }
+
+    // Maps the fraction of the time between two keys to the fraction of
+    // the change in value.
+    fn apply(&self, t: f64) -> f64 {
+        match *self {
+            Easing::Linear => t,
+            Easing::Step => 0.0,
+            Easing::EaseInOut => Easing::Bezier([0.42, 0.0, 0.58, 1.0]).apply(t),
+            Easing::Bezier([x1, y1, x2, y2]) => {
+                let bezier = |a: f64, b: f64, s: f64| {
+                    3.0 * (1.0 - s) * (1.0 - s) * s * a + 3.0 * (1.0 - s) * s * s * b + s * s * s
+                };
+                // x grows with s for control points between 0 and 1
+                let (mut lo, mut hi) = (0.0, 1.0);
+                for _ in 0..50 {
+                    let s = (lo + hi) / 2.0;
+                    if bezier(x1, x2, s) < t {
+                        lo = s;
+                    } else {
+                        hi = s;
+                    }
+                }
+                bezier(y1, y2, (lo + hi) / 2.0)
+            }
+        }
+    }
+}
+
+impl Track {
+    pub fn sample(&self, frame: f64) -> Vec<f64> {
+        let next = self.keys.partition_point(|k| k.frame <= frame);
+        if next == 0 {
+            return self.keys[0].value.components().to_vec();
+        }
+        let from = &self.keys[next - 1];
+        let to = match self.keys.get(next) {
+            Some(to) => to,
+            None => return from.value.components().to_vec(),
+        };
+        let t = from
+            .easing
+            .apply((frame - from.frame) / (to.frame - from.frame));
+        from.value
+            .components()
+            .iter()
+            .zip(to.value.components())
+            .map(|(a, b)| a + (b - a) * t)
+            .collect()
+    }
+}
+
+impl Animation {
+    // Changes the scene to how it looks at `frame`.
+    pub fn apply(&self, scene: &mut Config, frame: f64) -> Result<(), String> {
+        for track in &self.tracks {
+            let value = track.sample(frame);
+            let path: Vec<&str> = track.target.split('.').collect();
+            let result = match path[..] {
+                ["camera", ref field @ ..] if !field.is_empty() => {
+                    set_camera(&mut scene.camera, field, &value)
+                }
+                ["objects", index, ref rest @ ..] => {
+                    let body = index
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| scene.objects.get_mut(i))
+                        .ok_or_else(|| format!("no object {}", index))?;
+                    set_body(body, rest, &value)
+                }
+                _ => Err("unknown target".to_string()),
+            };
+            result.map_err(|e| format!("track {:?}: {}", track.target, e))?;
+        }
+        Ok(())
+    }
+}
+
+// The camera is cheap to rebuild from its fields, so any of them can be
+// changed through its JSON form.
+fn set_camera(camera: &mut Camera, path: &[&str], value: &[f64]) -> Result<(), String> {
+    let mut json = serde_json::to_value(*camera).map_err(|e| e.to_string())?;
+    let (last, parents) = path.split_last().unwrap();
+    let mut object = &mut json;
+    for name in parents {
+        object = object
+            .get_mut(*name)
+            .ok_or_else(|| format!("no camera field {}", name))?;
+    }
+    let object = object
+        .as_object_mut()
+        .ok_or_else(|| format!("no camera field {}", path.join(".")))?;
+    let field = object.entry(*last).or_insert(Value::Null);
+    *field = match (&*field, value) {
+        (Value::Array(_), _) => json!(value),
+        (_, [x, y, z]) => json!({ "x": x, "y": y, "z": z }),
+        (_, [x]) => json!(x),
+        _ => return Err(format!("{} values don't fit {}", value.len(), last)),
+    };
+    *camera = serde_json::from_value(json).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn set_body(body: &mut Body, path: &[&str], value: &[f64]) -> Result<(), String> {
+    match (body, path) {
+        (Body::Transform(t), ["body", rest @ ..]) => set_body(&mut t.body, rest, value),
+        (Body::Transform(t), [field]) => {
+            let vector = match value {
+                [x, y, z] => Some(crate::point3d::Point3D::new(*x, *y, *z)),
+                _ => return Err(format!("{} needs 3 values", field)),
+            };
+            let mut placement = t.placement.clone();
+            match *field {
+                "translate" => placement.translate = vector,
+                "rotate" => placement.rotate = vector,
+                "scale" => placement.scale = vector,
+                _ => return Err(format!("a Transform can't change its {}", field)),
+            }
+            t.set_placement(placement)
+        }
+        (body, ["material", param]) => body
+            .material_mut()
+            .ok_or_else(|| "the body has no material of its own".to_string())?
+            .set_param(param, value),
+        _ => Err("unknown target".to_string()),
+    }
+}
+
+#[test]
+fn test_easing() {
+    assert_eq!(Easing::Linear.apply(0.25), 0.25);
+    assert_eq!(Easing::Step.apply(0.75), 0.0);
+    assert_approx_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    assert!(Easing::EaseInOut.apply(0.1) < 0.05);
+    assert!(Easing::EaseInOut.apply(0.9) > 0.95);
+    assert_approx_eq!(Easing::Bezier([0.0, 0.0, 1.0, 1.0]).apply(0.3), 0.3);
+
+    let json = "{\"target\":\"camera.vfov\",\"keys\":[{\"frame\":0.0,\"value\":20.0,\"easing\":\"EaseInOut\"},{\"frame\":10.0,\"value\":40.0,\"easing\":{\"Bezier\":[0.25,0.1,0.25,1.0]}},{\"frame\":20.0,\"value\":30.0}]}";
+    let track = serde_json::from_str::<Track>(json).unwrap();
+    assert_eq!(serde_json::to_string(&track).unwrap(), json);
+    assert_eq!(track.sample(-1.0), vec![20.0]);
+    assert_approx_eq!(track.sample(5.0)[0], 30.0);
+    assert_eq!(track.sample(10.0), vec![40.0]);
+    assert_eq!(track.sample(25.0), vec![30.0]);
+
+    let unordered = json.replace("\"frame\":20.0", "\"frame\":5.0");
+    assert!(serde_json::from_str::<Track>(&unordered).is_err());
+}
+
+#[test]
+fn test_animate_scene() {
+    let json = std::fs::read("data/animation.json").expect("Unable to read file");
+    let mut scene = serde_json::from_slice::<Config>(&json).expect("Unable to parse json");
+    let animation = scene.animation.clone().unwrap();
+
+    animation.apply(&mut scene, 12.0).unwrap();
+    let camera = serde_json::to_value(scene.camera).unwrap();
+    assert_approx_eq!(camera["look_from"]["x"].as_f64().unwrap(), 0.0);
+    assert_approx_eq!(camera["look_from"]["z"].as_f64().unwrap(), 12.0);
+    match &scene.objects[1] {
+        Body::Transform(t) => {
+            let rotate = t.placement.rotate.unwrap();
+            assert_approx_eq!(rotate.y(), 90.0);
+        }
+        _ => panic!("expected a Transform"),
+    }
+
+    // out of range targets are errors, not ignored
+    let mut broken = animation.clone();
+    broken.tracks[0].target = "objects.99.material.albedo".to_string();
+    assert!(broken.apply(&mut scene, 0.0).is_err());
+    broken.tracks[0].target = "camera.no_such_field".to_string();
+    assert!(broken.apply(&mut scene, 0.0).is_err());
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::solid::{azimuth, closest, solve_quadratic, Frame, SurfaceHit};
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// All points within `radius` of the segment from `start` to `end`: a
+// cylinder closed by two hemispheres. u goes around the axis and v along
+// it over the whole length including the rounded ends.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Capsule {
+    pub start: Point3D,
+    pub end: Point3D,
+    pub radius: f64,
+    pub material: Material,
+}
+
+impl Capsule {
+    pub fn new(start: Point3D, end: Point3D, radius: f64, material: Material) -> Capsule {
+        Capsule {
+            start,
+            end,
+            radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let length = (self.end - self.start).length();
+        let frame = Frame::new(self.start, self.end - self.start);
+        let local = frame.to_local(ray);
+        let (o, d) = (local.origin, local.direction);
+        let r2 = self.radius * self.radius;
+        let v = |z: f64| (z + self.radius) / (length + 2.0 * self.radius);
+
+        let mut hits = Vec::with_capacity(6);
+        let a = d.x() * d.x() + d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y());
+        let c = o.x() * o.x() + o.y() * o.y() - r2;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                let p = local.at(t);
+                if (0.0..=length).contains(&p.z()) {
+                    hits.push(SurfaceHit {
+                        t,
+                        normal: Point3D::new(p.x(), p.y(), 0.0),
+                        uv: (azimuth(p), v(p.z())),
+                    });
+                }
+            }
+        }
+        // only the outer half of each end sphere belongs to the capsule
+        for z in [0.0, length] {
+            let center = Point3D::new(0.0, 0.0, z);
+            let oc = o - center;
+            if let Some((t0, t1)) = solve_quadratic(
+                d.length_squared(),
+                2.0 * oc.dot(d),
+                oc.length_squared() - r2,
+            ) {
+                for t in [t0, t1] {
+                    let p = local.at(t);
+                    let outside = if z == 0.0 {
+                        p.z() <= 0.0
+                    } else {
+                        p.z() >= length
+                    };
+                    if outside {
+                        hits.push(SurfaceHit {
+                            t,
+                            normal: p - center,
+                            uv: (azimuth(p), v(p.z())),
+                        });
+                    }
+                }
+            }
+        }
+        let hit = closest(hits, t_min, t_max)?;
+        Some(frame.hit_record(ray, hit, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Point3D::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(
+            self.start.min(self.end) - extent,
+            self.start.max(self.end) + extent,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    #[test]
+    fn test_capsule_hit() {
+        let capsule = Capsule::new(
+            Point3D::new(-1.0, 0.0, 0.0),
+            Point3D::new(1.0, 0.0, 0.0),
+            0.5,
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        );
+        // along the axis into the rounded end
+        let ray = Ray::new(Point3D::new(5.0, 0.0, 0.0), Point3D::new(-1.0, 0.0, 0.0));
+        let hit = capsule.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 3.5);
+        assert!(hit.front_face);
+        assert_approx_eq!(hit.normal.x(), 1.0);
+        assert_approx_eq!(hit.v, 1.0);
+
+        // side
+        let ray = Ray::new(Point3D::new(0.0, 5.0, 0.0), Point3D::new(0.0, -1.0, 0.0));
+        let hit = capsule.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 4.5);
+        assert_approx_eq!(hit.normal.y(), 1.0);
+
+        // past the end of the rounded cap
+        let miss = Ray::new(Point3D::new(1.45, 5.0, 0.45), Point3D::new(0.0, -1.0, 0.0));
+        assert!(capsule.hit(&miss, 0.0, f64::INFINITY).is_none());
+    }
+}
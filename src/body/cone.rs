@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::disk::disk_bounding_box;
+use crate::body::solid::{azimuth, cap_hit, closest, solve_quadratic, Frame, SurfaceHit};
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Cone with a round cap of `radius` at `base`, narrowing to a point at
+// `apex`. Texture coordinates follow the Cylinder layout.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Cone {
+    pub base: Point3D,
+    pub apex: Point3D,
+    pub radius: f64,
+    pub material: Material,
+}
+
+impl Cone {
+    pub fn new(base: Point3D, apex: Point3D, radius: f64, material: Material) -> Cone {
+        Cone {
+            base,
+            apex,
+            radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let height = (self.apex - self.base).length();
+        let frame = Frame::new(self.base, self.apex - self.base);
+        let local = frame.to_local(ray);
+        let (o, d) = (local.origin, local.direction);
+
+        // x^2 + y^2 = (k (height - z))^2
+        let k = self.radius / height;
+        let k2 = k * k;
+        let h = height - o.z();
+        let a = d.x() * d.x() + d.y() * d.y() - k2 * d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y() + k2 * h * d.z());
+        let c = o.x() * o.x() + o.y() * o.y() - k2 * h * h;
+
+        let mut hits = Vec::with_capacity(3);
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                let p = local.at(t);
+                // the quadric also contains the mirrored cone above the apex
+                if (0.0..=height).contains(&p.z()) {
+                    let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+                    hits.push(SurfaceHit {
+                        t,
+                        normal: Point3D::new(p.x(), p.y(), k * rho),
+                        uv: (azimuth(p), p.z() / height),
+                    });
+                }
+            }
+        }
+        hits.extend(cap_hit(&local, 0.0, self.radius, false));
+        let hit = closest(hits, t_min, t_max)?;
+        Some(frame.hit_record(ray, hit, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let base = disk_bounding_box(self.base, self.apex - self.base, self.radius);
+        Some(base.surrounding(&Aabb::new(self.apex, self.apex)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    #[test]
+    fn test_cone_hit() {
+        let cone = Cone::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 2.0, 0.0),
+            1.0,
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        );
+        // halfway up the radius is 0.5
+        let ray = Ray::new(Point3D::new(-5.0, 1.0, 0.0), Point3D::new(1.0, 0.0, 0.0));
+        let hit = cone.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 4.5);
+        assert!(hit.front_face);
+        assert!(hit.normal.x() < 0.0 && hit.normal.y() > 0.0);
+        assert_approx_eq!(hit.normal.length(), 1.0);
+
+        let below = Ray::new(Point3D::new(0.2, -1.0, 0.0), Point3D::new(0.0, 1.0, 0.0));
+        let hit = cone.hit(&below, 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 1.0);
+        assert_approx_eq!(hit.normal.y(), -1.0);
+
+        // would hit the mirrored half of the quadric above the apex
+        let above = Ray::new(Point3D::new(-5.0, 3.0, 0.0), Point3D::new(1.0, 0.0, 0.0));
+        assert!(cone.hit(&above, 0.0, f64::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,209 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::Body;
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Smoke, fog or any other participating medium of the same density
+// everywhere inside the closed `boundary` body. The material of the
+// boundary is ignored, light scatters by the phase function `material`
+// at random points inside. The denser the medium, the shorter the
+// distance light travels before it scatters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "ConstantMediumParams")]
+pub struct ConstantMedium {
+    pub boundary: Box<Body>,
+    pub density: f64,
+    pub material: Material,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConstantMediumParams {
+    pub boundary: Box<Body>,
+    pub density: f64,
+    pub material: Material,
+}
+
+impl TryFrom<ConstantMediumParams> for ConstantMedium {
+    type Error = String;
+
+    fn try_from(p: ConstantMediumParams) -> Result<Self, Self::Error> {
+        ConstantMedium::new(*p.boundary, p.density, p.material)
+    }
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Body, density: f64, material: Material) -> Result<ConstantMedium, String> {
+        if !matches!(
+            material,
+            Material::Isotropic(_) | Material::HenyeyGreenstein(_)
+        ) {
+            return Err(
+                "ConstantMedium needs an Isotropic or HenyeyGreenstein material".to_string(),
+            );
+        }
+        if density.is_nan() || density <= 0.0 {
+            return Err(format!(
+                "ConstantMedium density must be positive, not {}",
+                density
+            ));
+        }
+        Ok(ConstantMedium {
+            boundary: Box::new(boundary),
+            density,
+            material,
+        })
+    }
+
+    // Stretches of the ray inside the boundary, which may be concave.
+    fn segments(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<(f64, f64)> {
+        let hits = self.boundary.hit_all(ray, t_min, t_max);
+        // a ray that leaves the boundary first started inside it
+        let mut entered = match hits.first() {
+            Some(h) if !h.front_face => Some(t_min),
+            _ => None,
+        };
+        let mut segments = Vec::new();
+        let ends = hits.iter().map(|h| (h.t, h.front_face));
+        for (t, front_face) in ends.chain(std::iter::once((t_max, false))) {
+            match (entered, front_face) {
+                (None, true) => entered = Some(t),
+                (Some(start), false) => {
+                    segments.push((start, t));
+                    entered = None;
+                }
+                _ => {}
+            }
+        }
+        segments
+    }
+
+    // Samples where the ray scatters for a free-flight distance drawn from
+    // the exponential distribution, spent over the segments in turn.
+    fn scatter_t(&self, ray: &Ray, t_min: f64, t_max: f64, xi: f64) -> Option<f64> {
+        let scale = ray.direction.length();
+        let mut distance = -(1.0 - xi).ln() / self.density;
+        for (start, end) in self.segments(ray, t_min, t_max) {
+            let inside = (end - start) * scale;
+            if distance < inside {
+                return Some(start + distance / scale);
+            }
+            distance -= inside;
+        }
+        None
+    }
+
+    pub fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let scale = ray.direction.length();
+        let inside: f64 = self
+            .segments(ray, t_min, t_max)
+            .iter()
+            .map(|(start, end)| (end - start) * scale)
+            .sum();
+        (-self.density * inside).exp()
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = self.scatter_t(ray, t_min, t_max, rand::thread_rng().gen::<f64>())?;
+        // there is no surface, the normal is arbitrary
+        Some(HitRecord {
+            t,
+            point: ray.at(t),
+            normal: Point3D::new(1.0, 0.0, 0.0),
+            front_face: true,
+            material: &self.material,
+            u: 0.0,
+            v: 0.0,
+            color: None,
+            emitted: None,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Sphere, Torus};
+    use crate::materials::{Isotropic, Lambertian};
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    fn isotropic() -> Material {
+        Material::Isotropic(Isotropic::new(Srgb::new(0.8, 0.8, 0.8)))
+    }
+
+    fn fog(density: f64) -> ConstantMedium {
+        let boundary = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, isotropic());
+        ConstantMedium::new(Body::Sphere(boundary), density, isotropic()).unwrap()
+    }
+
+    #[test]
+    fn test_free_flight() {
+        let thin = fog(0.5);
+        let ray = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Point3D::new(2.0, 0.0, 0.0));
+        // half of all rays scatter within ln(2) / density
+        let median = 2.0f64.ln() / 0.5;
+        assert_approx_eq!(
+            thin.scatter_t(&ray, 0.0, f64::INFINITY, 0.5).unwrap(),
+            (4.0 + median) / 2.0
+        );
+        // this one would need 4.6 units of fog, the sphere is 2 across
+        assert!(thin.scatter_t(&ray, 0.0, f64::INFINITY, 0.9).is_none());
+        assert!(thin.scatter_t(&ray, 0.0, 2.5, 0.5).is_none());
+
+        // starting inside, e.g. after scattering
+        let inside = Ray::new(Point3D::new(0.5, 0.0, 0.0), Point3D::new(-1.0, 0.0, 0.0));
+        assert_approx_eq!(
+            thin.scatter_t(&inside, 0.0, f64::INFINITY, 0.5).unwrap(),
+            median
+        );
+
+        let dense = fog(1000.0);
+        let hit = dense.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(hit.t > 2.0 && hit.t < 2.01);
+        assert!(matches!(hit.material, Material::Isotropic(_)));
+    }
+
+    #[test]
+    fn test_concave_boundary() {
+        // the ray crosses both sides of a ring, 1 unit of medium each
+        let torus = Torus::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            isotropic(),
+        );
+        let ring = ConstantMedium::new(Body::Torus(torus), 1.0, isotropic()).unwrap();
+        let ray = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Point3D::new(1.0, 0.0, 0.0));
+        let xi = 1.0 - (-1.5f64).exp();
+        assert_approx_eq!(ring.scatter_t(&ray, 0.0, f64::INFINITY, xi).unwrap(), 7.0);
+        assert_approx_eq!(
+            ring.transmittance(&ray, 0.0, f64::INFINITY),
+            (-2.0f64).exp()
+        );
+    }
+
+    #[test]
+    fn test_medium_json() {
+        let json = "{\"type\":\"ConstantMedium\",\"boundary\":{\"type\":\"Sphere\",\"center\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"radius\":1.0,\"material\":{\"Isotropic\":{\"albedo\":[1.0,1.0,1.0]}}},\"density\":0.2,\"material\":{\"HenyeyGreenstein\":{\"albedo\":[0.9,0.9,0.9],\"g\":0.7}}}";
+        let body = serde_json::from_str::<Body>(json).unwrap();
+        assert_eq!(serde_json::to_string(&body).unwrap(), json);
+
+        let boundary = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, isotropic());
+        let lambertian = Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5)));
+        assert!(ConstantMedium::new(Body::Sphere(boundary.clone()), 1.0, lambertian).is_err());
+        assert!(ConstantMedium::new(Body::Sphere(boundary), 0.0, isotropic()).is_err());
+    }
+}
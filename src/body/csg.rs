@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::Body;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum Operation {
+    Union,
+    Intersection,
+    // Everything in `left` that is not in `right`.
+    Difference,
+}
+
+impl Operation {
+    fn inside(&self, left: bool, right: bool) -> bool {
+        match self {
+            Operation::Union => left || right,
+            Operation::Intersection => left && right,
+            Operation::Difference => left && !right,
+        }
+    }
+}
+
+// Combination of two closed bodies. Surfaces keep the material of the body
+// they come from, e.g. the walls of a hole cut by a difference have the
+// material of the `right` body.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Csg {
+    pub operation: Operation,
+    pub left: Box<Body>,
+    pub right: Box<Body>,
+}
+
+impl Csg {
+    pub fn new(operation: Operation, left: Body, right: Body) -> Csg {
+        Csg {
+            operation,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    // Walks the entry and exit points of both bodies along the ray and
+    // keeps those where the ray goes in or out of the combined body. The
+    // whole ray past t_min is needed to know whether it starts inside a
+    // body, so t_max only limits the hits that are returned.
+    fn surface_hits(&self, ray: &Ray, t_min: f64, t_max: f64, first_only: bool) -> Vec<HitRecord> {
+        let left = self.left.hit_all(ray, t_min, f64::INFINITY);
+        let right = self.right.hit_all(ray, t_min, f64::INFINITY);
+        // a ray that leaves a body first started inside it
+        let mut in_left = left.first().is_some_and(|h| !h.front_face);
+        let mut in_right = right.first().is_some_and(|h| !h.front_face);
+        let mut inside = self.operation.inside(in_left, in_right);
+
+        let mut hits = Vec::new();
+        let mut left = left.into_iter().peekable();
+        let mut right = right.into_iter().peekable();
+        loop {
+            let from_left = match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) => l.t <= r.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let mut hit = if from_left {
+                let hit = left.next().unwrap();
+                in_left = hit.front_face;
+                hit
+            } else {
+                let hit = right.next().unwrap();
+                in_right = hit.front_face;
+                hit
+            };
+            if hit.t >= t_max {
+                break;
+            }
+            let now_inside = self.operation.inside(in_left, in_right);
+            if now_inside != inside {
+                inside = now_inside;
+                // the carved out surfaces of a difference face the other way
+                if !from_left && self.operation == Operation::Difference {
+                    hit.front_face = !hit.front_face;
+                }
+                hits.push(hit);
+                if first_only {
+                    break;
+                }
+            }
+        }
+        hits
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.surface_hits(ray, t_min, t_max, true)
+            .into_iter()
+            .next()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self.operation {
+            Operation::Union => Some(
+                self.left
+                    .bounding_box()?
+                    .surrounding(&self.right.bounding_box()?),
+            ),
+            Operation::Intersection => {
+                match (self.left.bounding_box(), self.right.bounding_box()) {
+                    (Some(l), Some(r)) => Some(Aabb::new(l.min.max(r.min), l.max.min(r.max))),
+                    (l, r) => l.or(r),
+                }
+            }
+            Operation::Difference => self.left.bounding_box(),
+        }
+    }
+
+    fn hit_all(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        self.surface_hits(ray, t_min, t_max, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Cuboid, Sphere};
+    use crate::materials::{Glass, Lambertian, Material};
+    use crate::point3d::Point3D;
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    fn sphere(x: f64, radius: f64) -> Body {
+        Body::Sphere(Sphere::new(
+            Point3D::new(x, 0.0, 0.0),
+            radius,
+            Material::Glass(Glass::new(1.5)),
+        ))
+    }
+
+    fn ts(hits: Vec<HitRecord>) -> Vec<(f64, bool)> {
+        hits.iter().map(|h| (h.t, h.front_face)).collect()
+    }
+
+    #[test]
+    fn test_csg_operations() {
+        // two unit spheres overlapping between x = 0 and x = 1, the ray
+        // runs along the x axis from x = -5
+        let ray = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Point3D::new(1.0, 0.0, 0.0));
+        let csg = |operation| Csg::new(operation, sphere(0.0, 1.0), sphere(1.0, 1.0));
+
+        let (union, intersection, difference) = (
+            csg(Operation::Union),
+            csg(Operation::Intersection),
+            csg(Operation::Difference),
+        );
+        let hits = union.hit_all(&ray, 0.0, f64::INFINITY);
+        assert_eq!(ts(hits), vec![(4.0, true), (7.0, false)]);
+
+        let hits = intersection.hit_all(&ray, 0.0, f64::INFINITY);
+        assert_eq!(ts(hits), vec![(5.0, true), (6.0, false)]);
+
+        let hits = difference.hit_all(&ray, 0.0, f64::INFINITY);
+        assert_eq!(ts(hits), vec![(4.0, true), (5.0, false)]);
+
+        // starting inside the union
+        let hit = union.hit(&ray, 5.5, f64::INFINITY).unwrap();
+        assert_eq!((hit.t, hit.front_face), (7.0, false));
+        assert!(union.hit(&ray, 0.0, 3.0).is_none());
+    }
+
+    #[test]
+    fn test_hollow_sphere() {
+        let shell = Csg::new(Operation::Difference, sphere(0.0, 1.0), sphere(0.0, 0.9));
+        let ray = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Point3D::new(1.0, 0.0, 0.0));
+        let hits = shell.hit_all(&ray, 0.0, f64::INFINITY);
+        let expected = [(4.0, true), (4.1, false), (5.9, true), (6.0, false)];
+        assert_eq!(hits.len(), expected.len());
+        for (hit, (t, front_face)) in hits.iter().zip(expected) {
+            assert_approx_eq!(hit.t, t);
+            assert_eq!(hit.front_face, front_face);
+        }
+        // leaving the glass into the hole, the normal faces the ray
+        assert_approx_eq!(hits[1].normal.x(), -1.0);
+
+        // nested: cutting the shell out of a box that starts inside the
+        // cavity
+        let cube = Body::Box(Cuboid::new(
+            Point3D::new(-0.5, -2.0, -2.0),
+            Point3D::new(2.0, 2.0, 2.0),
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        ));
+        let carved = Csg::new(Operation::Difference, cube, Body::Csg(shell));
+        let t: Vec<f64> = carved
+            .hit_all(&ray, 0.0, f64::INFINITY)
+            .iter()
+            .map(|h| h.t)
+            .collect();
+        assert_eq!(t.len(), 4);
+        assert_approx_eq!(t[0], 4.5);
+        assert_approx_eq!(t[1], 5.9);
+        assert_approx_eq!(t[2], 6.0);
+        assert_approx_eq!(t[3], 7.0);
+    }
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::rect::Axis;
+use crate::body::triangle::triangle_hit_record;
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Axis-aligned box, exposed as the `Box` body. Each face is mapped to the
+// whole [0, 1] texture square.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Cuboid {
+    pub min: Point3D,
+    pub max: Point3D,
+    pub material: Material,
+}
+
+impl Cuboid {
+    pub fn new(min: Point3D, max: Point3D, material: Material) -> Cuboid {
+        Cuboid {
+            min: min.min(max),
+            max: min.max(max),
+            material,
+        }
+    }
+}
+
+const AXES: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // slab test remembering which slabs the ray enters and leaves last
+        let mut enter = (f64::NEG_INFINITY, Axis::X);
+        let mut exit = (f64::INFINITY, Axis::X);
+        for axis in AXES {
+            let k = axis.index();
+            let inv = 1.0 / ray.direction[k];
+            let mut t0 = (self.min[k] - ray.origin[k]) * inv;
+            let mut t1 = (self.max[k] - ray.origin[k]) * inv;
+            if inv < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN for rays in the plane of a face, keep the other bounds
+            if t0 > enter.0 {
+                enter = (t0, axis);
+            }
+            if t1 < exit.0 {
+                exit = (t1, axis);
+            }
+        }
+        if enter.0 > exit.0 {
+            return None;
+        }
+        // outward normals: the entry face faces the ray, the exit face away
+        let (t, axis, outward) = if enter.0 > t_min && enter.0 < t_max {
+            (enter.0, enter.1, -ray.direction[enter.1.index()].signum())
+        } else if exit.0 > t_min && exit.0 < t_max {
+            (exit.0, exit.1, ray.direction[exit.1.index()].signum())
+        } else {
+            return None;
+        };
+        let normal = axis.unit_vector() * outward;
+        let p = ray.at(t);
+        let (i, j) = axis.others();
+        let uv = (
+            (p[i] - self.min[i]) / (self.max[i] - self.min[i]),
+            (p[j] - self.min[j]) / (self.max[j] - self.min[j]),
+        );
+        Some(triangle_hit_record(
+            ray,
+            t,
+            normal,
+            normal,
+            uv,
+            None,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    fn make_box() -> Cuboid {
+        Cuboid::new(
+            Point3D::new(-1.0, 0.0, -1.0),
+            Point3D::new(1.0, 1.0, 1.0),
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn test_box_hit() {
+        let cuboid = make_box();
+        let ray = Ray::new(Point3D::new(0.5, 5.0, 0.0), Point3D::new(0.0, -1.0, 0.0));
+        let hit = cuboid.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 4.0);
+        assert!(hit.front_face);
+        assert_eq!(hit.normal, Point3D::new(0.0, 1.0, 0.0));
+        assert_approx_eq!(hit.u, 0.75);
+        assert_approx_eq!(hit.v, 0.5);
+
+        // from inside, the exit face is hit from the back
+        let ray = Ray::new(Point3D::new(0.0, 0.5, 0.0), Point3D::new(-1.0, 0.0, 0.0));
+        let hit = cuboid.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert!(!hit.front_face);
+        assert_eq!(hit.normal, Point3D::new(1.0, 0.0, 0.0));
+
+        let miss = Ray::new(Point3D::new(2.0, 5.0, 0.0), Point3D::new(0.0, -1.0, 0.0));
+        assert!(cuboid.hit(&miss, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_to_json() {
+        let body = crate::body::Body::Box(make_box());
+        let serialized = serde_json::to_string(&body).unwrap();
+        assert_eq!(
+            "{\"type\":\"Box\",\"min\":{\"x\":-1.0,\"y\":0.0,\"z\":-1.0},\"max\":{\"x\":1.0,\"y\":1.0,\"z\":1.0},\"material\":{\"Lambertian\":{\"albedo\":[0.5,0.5,0.5]}}}",
+            serialized
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::disk::disk_bounding_box;
+use crate::body::solid::{azimuth, cap_hit, closest, solve_quadratic, Frame, SurfaceHit};
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Cylinder from the center of the `base` cap to the center of the `top`
+// cap. On the side u goes around the axis and v from base to top, the caps
+// are mapped like a square texture laid over them.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Cylinder {
+    pub base: Point3D,
+    pub top: Point3D,
+    pub radius: f64,
+    pub material: Material,
+}
+
+impl Cylinder {
+    pub fn new(base: Point3D, top: Point3D, radius: f64, material: Material) -> Cylinder {
+        Cylinder {
+            base,
+            top,
+            radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let height = (self.top - self.base).length();
+        let frame = Frame::new(self.base, self.top - self.base);
+        let local = frame.to_local(ray);
+        let (o, d) = (local.origin, local.direction);
+
+        let mut hits = Vec::with_capacity(4);
+        let a = d.x() * d.x() + d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y());
+        let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                let p = local.at(t);
+                if (0.0..=height).contains(&p.z()) {
+                    hits.push(SurfaceHit {
+                        t,
+                        normal: Point3D::new(p.x(), p.y(), 0.0),
+                        uv: (azimuth(p), p.z() / height),
+                    });
+                }
+            }
+        }
+        hits.extend(cap_hit(&local, 0.0, self.radius, false));
+        hits.extend(cap_hit(&local, height, self.radius, true));
+        let hit = closest(hits, t_min, t_max)?;
+        Some(frame.hit_record(ray, hit, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let axis = self.top - self.base;
+        Some(
+            disk_bounding_box(self.base, axis, self.radius).surrounding(&disk_bounding_box(
+                self.top,
+                axis,
+                self.radius,
+            )),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    #[test]
+    fn test_cylinder_hit() {
+        let cylinder = Cylinder::new(
+            Point3D::new(0.0, -1.0, 0.0),
+            Point3D::new(0.0, 1.0, 0.0),
+            0.5,
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        );
+        // side
+        let ray = Ray::new(Point3D::new(0.0, 0.5, -5.0), Point3D::new(0.0, 0.0, 1.0));
+        let hit = cylinder.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 4.5);
+        assert!(hit.front_face);
+        assert_approx_eq!(hit.normal.z(), -1.0);
+        assert_approx_eq!(hit.v, 0.75);
+
+        // from inside the cylinder towards the top cap
+        let ray = Ray::new(Point3D::new(0.1, 0.0, 0.0), Point3D::new(0.0, 1.0, 0.0));
+        let hit = cylinder.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 1.0);
+        assert!(!hit.front_face);
+        assert_approx_eq!(hit.normal.y(), -1.0);
+
+        let miss = Ray::new(Point3D::new(0.0, 1.5, -5.0), Point3D::new(0.0, 0.0, 1.0));
+        assert!(cylinder.hit(&miss, 0.0, f64::INFINITY).is_none());
+
+        let bounds = cylinder.bounding_box().unwrap();
+        assert_approx_eq!(bounds.max.x(), 0.5, 1e-5);
+        assert_approx_eq!(bounds.max.y(), 1.0, 1e-5);
+    }
+}
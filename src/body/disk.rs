@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::plane::{check_normal, intersect_plane};
+use crate::body::triangle::triangle_hit_record;
+use crate::materials::Material;
+use crate::onb::Onb;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Flat disk facing `normal`. Texture coordinates are polar: u goes around
+// the center and v from the center (0) to the rim (1).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "DiskParams")]
+pub struct Disk {
+    pub center: Point3D,
+    pub normal: Point3D,
+    pub radius: f64,
+    pub material: Material,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiskParams {
+    pub center: Point3D,
+    pub normal: Point3D,
+    pub radius: f64,
+    pub material: Material,
+}
+
+impl TryFrom<DiskParams> for Disk {
+    type Error = String;
+
+    fn try_from(p: DiskParams) -> Result<Self, Self::Error> {
+        check_normal(p.normal)?;
+        Ok(Disk::new(p.center, p.normal, p.radius, p.material))
+    }
+}
+
+impl Disk {
+    pub fn new(center: Point3D, normal: Point3D, radius: f64, material: Material) -> Disk {
+        Disk {
+            center,
+            normal,
+            radius,
+            material,
+        }
+    }
+}
+
+// Tight box around a disk, also used for the caps of round solids.
+pub(crate) fn disk_bounding_box(center: Point3D, normal: Point3D, radius: f64) -> Aabb {
+    let n = normal.unit_vector();
+    let extent = |c: f64| radius * (1.0 - c * c).max(0.0).sqrt() + 1e-6;
+    let extent = Point3D::new(extent(n.x()), extent(n.y()), extent(n.z()));
+    Aabb::new(center - extent, center + extent)
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let normal = self.normal.unit_vector();
+        let t = intersect_plane(ray, self.center, normal)?;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+        let d = ray.at(t) - self.center;
+        let distance = d.length();
+        if distance > self.radius {
+            return None;
+        }
+        let onb = Onb::from_w(normal);
+        let u = d.dot(onb.v).atan2(d.dot(onb.u)) / (2.0 * std::f64::consts::PI) + 0.5;
+        Some(triangle_hit_record(
+            ray,
+            t,
+            normal,
+            normal,
+            (u, distance / self.radius),
+            None,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(disk_bounding_box(self.center, self.normal, self.radius))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    #[test]
+    fn test_disk_hit() {
+        let disk = Disk::new(
+            Point3D::new(0.0, 0.0, -1.0),
+            Point3D::new(0.0, 0.0, 1.0),
+            2.0,
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        );
+        let ray = Ray::new(Point3D::new(1.0, 0.0, 1.0), Point3D::new(0.0, 0.0, -1.0));
+        let hit = disk.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert!(hit.front_face);
+        assert_approx_eq!(hit.v, 0.5);
+
+        let outside = Ray::new(Point3D::new(1.5, 1.5, 1.0), Point3D::new(0.0, 0.0, -1.0));
+        assert!(disk.hit(&outside, 0.0, f64::INFINITY).is_none());
+
+        let bounds = disk.bounding_box().unwrap();
+        assert_approx_eq!(bounds.max.x(), 2.0, 1e-5);
+        assert_approx_eq!(bounds.max.z(), -1.0, 1e-5);
+
+        let json = serde_json::to_string(&disk).unwrap();
+        assert!(serde_json::from_str::<Disk>(&json).is_ok());
+        let flat = json.replace("\"z\":1.0", "\"z\":0.0");
+        assert!(serde_json::from_str::<Disk>(&flat).is_err());
+    }
+}
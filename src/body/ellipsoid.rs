@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::aabb::Aabb;
+use crate::body::motion::{position_at, Motion};
 use crate::materials::Material;
 use crate::point3d::Point3D;
 use crate::ray::HitRecord;
@@ -11,6 +13,8 @@ pub struct Ellipsoid {
     pub center: Point3D,
     pub radii: Point3D, // Semi-axes lengths along x, y, and z axes
     pub material: Material,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion: Option<Motion>,
 }
 
 impl Ellipsoid {
@@ -19,8 +23,13 @@ impl Ellipsoid {
             center,
             radii,
             material,
+            motion: None,
         }
     }
+
+    pub fn center_at(&self, time: f64) -> Point3D {
+        position_at(self.center, &self.motion, time)
+    }
 }
 
 fn u_v_from_ellipsoid_hit_point(hit_point_on_ellipsoid: Point3D, radii: Point3D) -> (f64, f64) {
@@ -35,10 +44,13 @@ fn u_v_from_ellipsoid_hit_point(hit_point_on_ellipsoid: Point3D, radii: Point3D)
 
 impl Hittable for Ellipsoid {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = ray.origin - self.center;
-        let a = (ray.direction / self.radii).length_squared();
-        let half_b = oc.dot(ray.direction / self.radii);
-        let c = oc.length_squared() / self.radii.length_squared() - 1.0; // Element-wise division
+        let center = self.center_at(ray.time);
+        // Scale space so the ellipsoid becomes a unit sphere
+        let oc = (ray.origin - center) / self.radii;
+        let direction = ray.direction / self.radii;
+        let a = direction.length_squared();
+        let half_b = oc.dot(direction);
+        let c = oc.length_squared() - 1.0;
         let discriminant = (half_b * half_b) - (a * c);
 
         if discriminant >= 0.0 {
@@ -48,10 +60,10 @@ impl Hittable for Ellipsoid {
             for root in [root_a, root_b].iter() {
                 if *root < t_max && *root > t_min {
                     let p = ray.at(*root);
-                    let normal = (p - self.center) / (self.radii * self.radii); // Normalize by the squared radii
+                    let normal = ((p - center) / (self.radii * self.radii)).unit_vector(); // Gradient of the implicit surface
                     let front_face = ray.direction.dot(normal) < 0.0;
 
-                    let (u, v) = u_v_from_ellipsoid_hit_point(p - self.center, self.radii);
+                    let (u, v) = u_v_from_ellipsoid_hit_point(p - center, self.radii);
 
                     return Some(HitRecord {
                         t: *root,
@@ -61,12 +73,27 @@ impl Hittable for Ellipsoid {
                         material: &self.material,
                         u,
                         v,
+                        color: None,
+                        emitted: None,
                     });
                 }
             }
         }
         None
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Point3D::new(
+            self.radii.x().abs(),
+            self.radii.y().abs(),
+            self.radii.z().abs(),
+        );
+        let b = Aabb::new(self.center - extent, self.center + extent);
+        Some(match &self.motion {
+            Some(m) => m.bounding_box(b),
+            None => b,
+        })
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -85,7 +112,7 @@ mod tests {
         let ellipsoid = Ellipsoid::new(center, radii, Material::Glass(Glass::new(1.5)));
         let ray = Ray::new(Point3D::new(0.0, 0.0, -5.0), Point3D::new(0.0, 0.0, 1.0));
         let hit = ellipsoid.hit(&ray, 0.0, f64::INFINITY);
-        assert_eq!(hit.unwrap().t, 0.6547694874158765);
+        assert_eq!(hit.unwrap().t, 3.0);
     }
 
     #[test]
@@ -0,0 +1,420 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::triangle::{interpolate, intersect_triangle, triangle_hit_record};
+use crate::materials::{load_grayscale_image, Material};
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Terrain from a grayscale image such as a 16-bit PNG, one height sample
+// per pixel. The terrain covers `width` along x and `depth` along z from
+// `origin`, with the top row of the image at origin.z. Black is at origin.y and white
+// `height_scale` above it. Each square of four samples is split into two
+// triangles, shaded with normals interpolated from the neighbouring
+// samples.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "HeightfieldParams")]
+pub struct Heightfield {
+    pub path: String,
+    pub origin: Point3D,
+    pub width: f64,
+    pub depth: f64,
+    pub height_scale: f64,
+    pub material: Material,
+    #[serde(skip_serializing)]
+    terrain: Terrain, // Note, loaded from `path`.
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HeightfieldParams {
+    pub path: String,
+    pub origin: Point3D,
+    pub width: f64,
+    pub depth: f64,
+    pub height_scale: f64,
+    pub material: Material,
+}
+
+impl TryFrom<HeightfieldParams> for Heightfield {
+    type Error = String;
+
+    fn try_from(p: HeightfieldParams) -> Result<Self, Self::Error> {
+        let (heights, columns, rows) = load_grayscale_image(&p.path)?;
+        let mut heightfield = Heightfield::new(
+            heights,
+            columns,
+            rows,
+            p.origin,
+            p.width,
+            p.depth,
+            p.height_scale,
+            p.material,
+        )
+        .map_err(|e| format!("{}: {}", p.path, e))?;
+        heightfield.path = p.path;
+        Ok(heightfield)
+    }
+}
+
+// World space samples with their normals, and a quadtree of the height
+// ranges of the cells between them.
+#[derive(Debug, Clone, Default)]
+struct Terrain {
+    columns: usize,
+    rows: usize,
+    points: Vec<Point3D>,
+    normals: Vec<Point3D>,
+    // levels[0] has one (min, max) per cell, every further level halves
+    // the number of cells along both axes down to a single root.
+    levels: Vec<Level>,
+}
+
+#[derive(Debug, Clone)]
+struct Level {
+    columns: usize,
+    rows: usize,
+    ranges: Vec<(f64, f64)>,
+}
+
+impl Level {
+    fn range(&self, i: usize, j: usize) -> (f64, f64) {
+        self.ranges[j * self.columns + i]
+    }
+
+    // Height ranges of the cells of the next level up, each covering up
+    // to 2x2 cells of this one.
+    fn parent(&self) -> Level {
+        let columns = self.columns.div_ceil(2);
+        let rows = self.rows.div_ceil(2);
+        let mut ranges = Vec::with_capacity(columns * rows);
+        for j in 0..rows {
+            for i in 0..columns {
+                let mut range = (f64::INFINITY, f64::NEG_INFINITY);
+                for (ci, cj) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let (ci, cj) = (2 * i + ci, 2 * j + cj);
+                    if ci < self.columns && cj < self.rows {
+                        let (lo, hi) = self.range(ci, cj);
+                        range = (range.0.min(lo), range.1.max(hi));
+                    }
+                }
+                ranges.push(range);
+            }
+        }
+        Level {
+            columns,
+            rows,
+            ranges,
+        }
+    }
+}
+
+impl Heightfield {
+    // `heights` are in [0, 1], row by row starting at origin.z.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        heights: Vec<f64>,
+        columns: usize,
+        rows: usize,
+        origin: Point3D,
+        width: f64,
+        depth: f64,
+        height_scale: f64,
+        material: Material,
+    ) -> Result<Heightfield, String> {
+        if columns < 2 || rows < 2 || heights.len() != columns * rows {
+            return Err(format!(
+                "{} heights do not make a grid of at least 2x2 ({}x{})",
+                heights.len(),
+                columns,
+                rows
+            ));
+        }
+        // zero sizes give no normals, negative ones turn the terrain over
+        if !(width > 0.0 && width.is_finite() && depth > 0.0 && depth.is_finite()) {
+            return Err(format!(
+                "width {} and depth {} must be positive",
+                width, depth
+            ));
+        }
+        if !height_scale.is_finite() {
+            return Err(format!("height_scale {} is not a number", height_scale));
+        }
+        let dx = width / (columns - 1) as f64;
+        let dz = depth / (rows - 1) as f64;
+        let height = |i: usize, j: usize| heights[j * columns + i] * height_scale;
+        let points = (0..rows)
+            .flat_map(|j| (0..columns).map(move |i| (i, j)))
+            .map(|(i, j)| origin + Point3D::new(i as f64 * dx, height(i, j), j as f64 * dz))
+            .collect();
+        // central differences, one sided at the border
+        let normals = (0..rows)
+            .flat_map(|j| (0..columns).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(columns - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(rows - 1));
+                let slope_x = (height(i1, j) - height(i0, j)) / ((i1 - i0) as f64 * dx);
+                let slope_z = (height(i, j1) - height(i, j0)) / ((j1 - j0) as f64 * dz);
+                Point3D::new(-slope_x, 1.0, -slope_z).unit_vector()
+            })
+            .collect();
+
+        let mut ranges = Vec::with_capacity((columns - 1) * (rows - 1));
+        for j in 0..rows - 1 {
+            for i in 0..columns - 1 {
+                let corners = [
+                    height(i, j),
+                    height(i + 1, j),
+                    height(i, j + 1),
+                    height(i + 1, j + 1),
+                ];
+                let lo = corners.iter().fold(f64::INFINITY, |a, &b| a.min(b));
+                let hi = corners.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+                ranges.push((origin.y() + lo, origin.y() + hi));
+            }
+        }
+        let mut levels = vec![Level {
+            columns: columns - 1,
+            rows: rows - 1,
+            ranges,
+        }];
+        while levels.last().is_some_and(|l| l.columns > 1 || l.rows > 1) {
+            let parent = levels.last().unwrap().parent();
+            levels.push(parent);
+        }
+
+        Ok(Heightfield {
+            path: String::new(),
+            origin,
+            width,
+            depth,
+            height_scale,
+            material,
+            terrain: Terrain {
+                columns,
+                rows,
+                points,
+                normals,
+                levels,
+            },
+        })
+    }
+
+    // Bounds of the cells covered by cell (i, j) of `level`.
+    fn node_bounds(&self, level: usize, i: usize, j: usize) -> Aabb {
+        let terrain = &self.terrain;
+        let size = 1 << level;
+        let (i0, j0) = (i * size, j * size);
+        let i1 = ((i + 1) * size).min(terrain.columns - 1);
+        let j1 = ((j + 1) * size).min(terrain.rows - 1);
+        let (lo, hi) = terrain.levels[level].range(i, j);
+        let a = terrain.points[j0 * terrain.columns + i0];
+        let b = terrain.points[j1 * terrain.columns + i1];
+        // flat cells still need some thickness for the slab test
+        Aabb::new(
+            Point3D::new(a.x(), lo - 1e-9, a.z()),
+            Point3D::new(b.x(), hi + 1e-9, b.z()),
+        )
+    }
+
+    fn hit_cell(&self, i: usize, j: usize, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let terrain = &self.terrain;
+        let index = |i: usize, j: usize| j * terrain.columns + i;
+        let triangles = [
+            [index(i, j), index(i, j + 1), index(i + 1, j)],
+            [index(i + 1, j + 1), index(i + 1, j), index(i, j + 1)],
+        ];
+        let mut closest = t_max;
+        let mut hit = None;
+        for corners in triangles {
+            let [v0, v1, v2] = corners.map(|c| terrain.points[c]);
+            if let Some((t, b1, b2)) = intersect_triangle(ray, v0, v1, v2, t_min, closest) {
+                closest = t;
+                hit = Some((t, b1, b2, corners));
+            }
+        }
+        let (t, b1, b2, corners) = hit?;
+        let [v0, v1, v2] = corners.map(|c| terrain.points[c]);
+        let geometric_normal = (v1 - v0).cross(v2 - v0);
+        let normal = interpolate(corners.map(|c| terrain.normals[c]), b1, b2);
+        let p = ray.at(t) - self.origin;
+        let uv = (p.x() / self.width, 1.0 - p.z() / self.depth);
+        Some(triangle_hit_record(
+            ray,
+            t,
+            geometric_normal,
+            normal.unit_vector(),
+            uv,
+            None,
+            &self.material,
+        ))
+    }
+
+    // Descends the quadtree, skipping nodes whose height range the ray
+    // misses. Children are visited nearest first so the closest hit found
+    // so far prunes the rest.
+    fn traverse(
+        &self,
+        node: (usize, usize, usize),
+        ray: &Ray,
+        inv_direction: Point3D,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord> {
+        let (level, i, j) = node;
+        let levels = &self.terrain.levels;
+        if i >= levels[level].columns || j >= levels[level].rows {
+            return None;
+        }
+        if !self
+            .node_bounds(level, i, j)
+            .hit(ray, inv_direction, t_min, t_max)
+        {
+            return None;
+        }
+        if level == 0 {
+            return self.hit_cell(i, j, ray, t_min, t_max);
+        }
+        let flip_i = usize::from(ray.direction.x() < 0.0);
+        let flip_j = usize::from(ray.direction.z() < 0.0);
+        let mut closest = t_max;
+        let mut hit = None;
+        for (ci, cj) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let child = (level - 1, 2 * i + (ci ^ flip_i), 2 * j + (cj ^ flip_j));
+            if let Some(h) = self.traverse(child, ray, inv_direction, t_min, closest) {
+                closest = h.t;
+                hit = Some(h);
+            }
+        }
+        hit
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let inv_direction = Point3D::new(1.0, 1.0, 1.0) / ray.direction;
+        let root = (self.terrain.levels.len() - 1, 0, 0);
+        self.traverse(root, ray, inv_direction, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.node_bounds(self.terrain.levels.len() - 1, 0, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    fn material() -> Material {
+        Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5)))
+    }
+
+    // A ramp rising along x over 5x3 samples, 4 by 2 units, 2 units high.
+    fn ramp() -> Heightfield {
+        let heights = (0..3)
+            .flat_map(|_| (0..5).map(|i| i as f64 / 4.0))
+            .collect();
+        Heightfield::new(
+            heights,
+            5,
+            3,
+            Point3D::new(0.0, 0.0, 0.0),
+            4.0,
+            2.0,
+            2.0,
+            material(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_heightfield_hit() {
+        let ramp = ramp();
+        assert_eq!(ramp.terrain.levels.len(), 3);
+        let bounds = ramp.bounding_box().unwrap();
+        assert_approx_eq!(bounds.max.y(), 2.0, 1e-6);
+        assert_approx_eq!(bounds.max.x(), 4.0);
+
+        // straight down onto the ramp, the height at x is x / 2
+        for x in [0.3, 1.7, 2.5, 3.9] {
+            let ray = Ray::new(Point3D::new(x, 10.0, 0.6), Point3D::new(0.0, -1.0, 0.0));
+            let hit = ramp.hit(&ray, 0.0, f64::INFINITY).unwrap();
+            assert_approx_eq!(hit.point.y(), x / 2.0);
+            assert!(hit.front_face);
+            // the slope is 1 in 2, so the normal leans back along x
+            assert_approx_eq!(hit.normal.x(), -1.0 / 5.0f64.sqrt());
+            assert_approx_eq!(hit.u, x / 4.0);
+        }
+
+        // along the ramp from the low end, just above it
+        let ray = Ray::new(Point3D::new(-1.0, 1.0, 1.0), Point3D::new(1.0, 0.0, 0.0));
+        assert_approx_eq!(ramp.hit(&ray, 0.0, f64::INFINITY).unwrap().t, 3.0);
+        // from below
+        let ray = Ray::new(Point3D::new(2.0, -1.0, 1.0), Point3D::new(0.0, 1.0, 0.0));
+        assert!(!ramp.hit(&ray, 0.0, f64::INFINITY).unwrap().front_face);
+        // outside the terrain
+        let ray = Ray::new(Point3D::new(5.0, 10.0, 1.0), Point3D::new(0.0, -1.0, 0.0));
+        assert!(ramp.hit(&ray, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_heightfield_checks() {
+        let build = |width: f64, depth: f64, height_scale: f64| {
+            Heightfield::new(
+                vec![0.0; 4],
+                2,
+                2,
+                Point3D::new(0.0, 0.0, 0.0),
+                width,
+                depth,
+                height_scale,
+                material(),
+            )
+        };
+        assert!(build(1.0, 1.0, 1.0).is_ok());
+        assert!(build(0.0, 1.0, 1.0).is_err());
+        assert!(build(1.0, -1.0, 1.0).is_err());
+        assert!(build(f64::NAN, 1.0, 1.0).is_err());
+        assert!(build(1.0, 1.0, f64::INFINITY).is_err());
+        assert!(Heightfield::new(
+            vec![0.0; 3],
+            3,
+            1,
+            Point3D::new(0.0, 0.0, 0.0),
+            1.0,
+            1.0,
+            1.0,
+            material()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_heightfield_json() {
+        let json = "{\"type\":\"Heightfield\",\"path\":\"data/terrain.jpg\",\"origin\":{\"x\":-5.0,\"y\":0.0,\"z\":-5.0},\"width\":10.0,\"depth\":10.0,\"height_scale\":2.0,\"material\":{\"Lambertian\":{\"albedo\":[0.5,0.5,0.5]}}}";
+        let body = serde_json::from_str::<crate::body::Body>(json).unwrap();
+        assert_eq!(serde_json::to_string(&body).unwrap(), json);
+        let ray = Ray::new(Point3D::new(0.3, 10.0, 0.2), Point3D::new(0.0, -1.0, 0.0));
+        let hit = body.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(hit.point.y() >= 0.0 && hit.point.y() <= 2.0);
+
+        let missing = json.replace("terrain.jpg", "missing.jpg");
+        assert!(serde_json::from_str::<crate::body::Body>(&missing).is_err());
+
+        // 16-bit heightmaps keep their precision
+        image::ImageBuffer::<image::Luma<u16>, _>::from_raw(2, 2, vec![0, 1, 32768, 65535])
+            .unwrap()
+            .save("/tmp/test_heightmap.png")
+            .unwrap();
+        let (heights, columns, rows) = load_grayscale_image("/tmp/test_heightmap.png").unwrap();
+        assert_eq!((columns, rows), (2, 2));
+        assert_eq!(heights[0], 0.0);
+        assert_eq!(heights[1], 1.0 / 65535.0);
+        assert_eq!(heights[3], 1.0);
+    }
+}
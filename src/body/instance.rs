@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::body::transform::{Affine, Placement};
+use crate::body::Body;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Places a copy of one of the scene's named `geometries`. All instances of
+// a geometry share the same data, e.g. a mesh is loaded only once no
+// matter how often it is placed. The geometry is looked up when the scene
+// is loaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "InstanceParams")]
+pub struct Instance {
+    pub geometry: String,
+    #[serde(flatten)]
+    pub placement: Placement,
+    #[serde(skip_serializing)]
+    body: Option<Arc<Body>>, // Note, set by `link`.
+    #[serde(skip_serializing)]
+    affine: Affine,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstanceParams {
+    pub geometry: String,
+    #[serde(flatten)]
+    pub placement: Placement,
+}
+
+impl TryFrom<InstanceParams> for Instance {
+    type Error = String;
+
+    fn try_from(p: InstanceParams) -> Result<Self, Self::Error> {
+        let affine = Affine::new(p.placement.matrix()?)?;
+        Ok(Instance {
+            geometry: p.geometry,
+            placement: p.placement,
+            body: None,
+            affine,
+        })
+    }
+}
+
+impl Instance {
+    pub fn new(geometry: Arc<Body>, name: &str, placement: Placement) -> Result<Instance, String> {
+        let affine = Affine::new(placement.matrix()?)?;
+        Ok(Instance {
+            geometry: name.to_string(),
+            placement,
+            body: Some(geometry),
+            affine,
+        })
+    }
+
+    pub fn link(&mut self, geometries: &BTreeMap<String, Arc<Body>>) -> Result<(), String> {
+        let body = geometries
+            .get(&self.geometry)
+            .ok_or_else(|| format!("unknown geometry {:?}", self.geometry))?;
+        self.body = Some(body.clone());
+        Ok(())
+    }
+
+    // The geometry, once linked.
+    pub fn body(&self) -> Option<&Body> {
+        self.body.as_deref()
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.affine.hit(self.body.as_ref()?, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.affine.bounding_box(self.body.as_ref()?)
+    }
+}
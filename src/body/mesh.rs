@@ -0,0 +1,237 @@
+use palette::Srgb;
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::triangle::{
+    interpolate, intersect_triangle, triangle_bounding_box, triangle_hit_record,
+};
+use crate::bvh::Bvh;
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// An indexed triangle mesh. Vertex attributes are shared between the
+// triangles that reference them, and the triangles are kept in their own
+// BVH so a mesh is a single entry in the world BVH.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "MeshParams")]
+pub struct Mesh {
+    pub vertices: Vec<Point3D>,
+    pub indices: Vec<[usize; 3]>,
+    // Either empty or one per vertex.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub normals: Vec<Point3D>,
+    // Either empty or one per vertex.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub uvs: Vec<[f64; 2]>,
+    // Either empty or one linear RGB color per vertex, multiplied into the
+    // albedo of the material.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub colors: Vec<[f32; 3]>,
+    pub material: Material,
+    #[serde(skip_serializing)]
+    bvh: Bvh, // Note, computed from the other fields.
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MeshParams {
+    pub vertices: Vec<Point3D>,
+    pub indices: Vec<[usize; 3]>,
+    #[serde(default)]
+    pub normals: Vec<Point3D>,
+    #[serde(default)]
+    pub uvs: Vec<[f64; 2]>,
+    #[serde(default)]
+    pub colors: Vec<[f32; 3]>,
+    pub material: Material,
+}
+
+impl TryFrom<MeshParams> for Mesh {
+    type Error = String;
+
+    fn try_from(p: MeshParams) -> Result<Self, Self::Error> {
+        Mesh::new(p.vertices, p.indices, p.normals, p.uvs, p.material)?.with_colors(p.colors)
+    }
+}
+
+impl Mesh {
+    pub fn new(
+        vertices: Vec<Point3D>,
+        indices: Vec<[usize; 3]>,
+        normals: Vec<Point3D>,
+        uvs: Vec<[f64; 2]>,
+        material: Material,
+    ) -> Result<Mesh, String> {
+        if !normals.is_empty() && normals.len() != vertices.len() {
+            return Err(format!(
+                "mesh has {} vertices but {} normals",
+                vertices.len(),
+                normals.len()
+            ));
+        }
+        if !uvs.is_empty() && uvs.len() != vertices.len() {
+            return Err(format!(
+                "mesh has {} vertices but {} uvs",
+                vertices.len(),
+                uvs.len()
+            ));
+        }
+        if let Some(index) = indices.iter().flatten().find(|i| **i >= vertices.len()) {
+            return Err(format!(
+                "mesh index {} out of range for {} vertices",
+                index,
+                vertices.len()
+            ));
+        }
+        let boxes: Vec<Option<Aabb>> = indices
+            .iter()
+            .map(|[a, b, c]| {
+                Some(triangle_bounding_box(
+                    vertices[*a],
+                    vertices[*b],
+                    vertices[*c],
+                ))
+            })
+            .collect();
+        let bvh = Bvh::new(&boxes);
+        Ok(Mesh {
+            vertices,
+            indices,
+            normals,
+            uvs,
+            colors: Vec::new(),
+            material,
+            bvh,
+        })
+    }
+
+    pub fn with_colors(mut self, colors: Vec<[f32; 3]>) -> Result<Mesh, String> {
+        if !colors.is_empty() && colors.len() != self.vertices.len() {
+            return Err(format!(
+                "mesh has {} vertices but {} colors",
+                self.vertices.len(),
+                colors.len()
+            ));
+        }
+        self.colors = colors;
+        Ok(self)
+    }
+
+    fn hit_triangle(&self, index: usize, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let [a, b, c] = self.indices[index];
+        let (v0, v1, v2) = (self.vertices[a], self.vertices[b], self.vertices[c]);
+        let (t, b1, b2) = intersect_triangle(ray, v0, v1, v2, t_min, t_max)?;
+        let geometric_normal = (v1 - v0).cross(v2 - v0).unit_vector();
+        let shading_normal = if self.normals.is_empty() {
+            geometric_normal
+        } else {
+            interpolate([self.normals[a], self.normals[b], self.normals[c]], b1, b2).unit_vector()
+        };
+        let uv = if self.uvs.is_empty() {
+            (b1, b2)
+        } else {
+            let uv = interpolate(
+                [self.uvs[a], self.uvs[b], self.uvs[c]].map(|[u, v]| Point3D::new(u, v, 0.0)),
+                b1,
+                b2,
+            );
+            (uv.x(), uv.y())
+        };
+        let color = if self.colors.is_empty() {
+            None
+        } else {
+            let c = interpolate(
+                [self.colors[a], self.colors[b], self.colors[c]]
+                    .map(|[r, g, b]| Point3D::new(r as f64, g as f64, b as f64)),
+                b1,
+                b2,
+            );
+            Some(Srgb::new(c.x() as f32, c.y() as f32, c.z() as f32))
+        };
+        Some(triangle_hit_record(
+            ray,
+            t,
+            geometric_normal,
+            shading_normal,
+            uv,
+            color,
+            &self.material,
+        ))
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.bvh.hit_with(ray, t_min, t_max, |index, t_min, t_max| {
+            self.hit_triangle(index, ray, t_min, t_max)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+
+    // A unit square in the z=0 plane facing +z, made of two triangles with
+    // normals tilted outwards so shading varies across the surface.
+    fn make_quad() -> Mesh {
+        let vertices = vec![
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(1.0, 0.0, 0.0),
+            Point3D::new(1.0, 1.0, 0.0),
+            Point3D::new(0.0, 1.0, 0.0),
+        ];
+        let normals = vec![
+            Point3D::new(-1.0, 0.0, 1.0).unit_vector(),
+            Point3D::new(1.0, 0.0, 1.0).unit_vector(),
+            Point3D::new(1.0, 0.0, 1.0).unit_vector(),
+            Point3D::new(-1.0, 0.0, 1.0).unit_vector(),
+        ];
+        let uvs = vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        Mesh::new(
+            vertices,
+            vec![[0, 1, 2], [0, 2, 3]],
+            normals,
+            uvs,
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mesh_hit_interpolates() {
+        let mesh = make_quad();
+        let ray = Ray::new(Point3D::new(0.5, 0.25, 1.0), Point3D::new(0.0, 0.0, -1.0));
+        let hit = mesh.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert!(hit.front_face);
+        assert!((hit.u - 0.5).abs() < 1e-9);
+        assert!((hit.v - 0.25).abs() < 1e-9);
+        assert!(hit.normal.x().abs() < 1e-9);
+        assert!(hit.normal.z() > 0.0);
+
+        let ray = Ray::new(Point3D::new(0.9, 0.5, 1.0), Point3D::new(0.0, 0.0, -1.0));
+        let hit = mesh.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(hit.normal.x() > 0.0);
+    }
+
+    #[test]
+    fn test_mesh_json_round_trip() {
+        let mesh = make_quad();
+        let serialized = serde_json::to_string(&mesh).unwrap();
+        let m = serde_json::from_str::<Mesh>(&serialized).unwrap();
+        assert_eq!(m.vertices, mesh.vertices);
+        assert_eq!(m.indices, mesh.indices);
+        assert_eq!(m.bounding_box(), mesh.bounding_box());
+
+        let bad = "{\"vertices\":[{\"x\":0.0,\"y\":0.0,\"z\":0.0}],\"indices\":[[0,1,2]],\"material\":{\"Glass\":{\"index_of_refraction\":1.5}}}";
+        assert!(serde_json::from_str::<Mesh>(bad).is_err());
+    }
+}
@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use crate::aabb::Aabb;
+use crate::body::Mesh;
+use crate::bvh::Bvh;
+use crate::loaders::obj::{load_obj, load_obj_materials};
+use crate::loaders::ply::load_ply;
+use crate::loaders::stl::load_stl;
+use crate::materials::Material;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// A model file format that can be turned into meshes.
+pub trait MeshFormat {
+    fn load(path: &Path, material: &Material, use_mtl: bool) -> eyre::Result<Vec<Mesh>>;
+}
+
+// A Wavefront OBJ model. Every group becomes its own mesh.
+#[derive(Debug, Clone)]
+pub struct Obj;
+
+// A PLY mesh. Per-vertex colors in the file tint the albedo of `material`.
+#[derive(Debug, Clone)]
+pub struct Ply;
+
+// An ASCII or binary STL mesh.
+#[derive(Debug, Clone)]
+pub struct Stl;
+
+pub type ObjFile = MeshFile<Obj>;
+pub type PlyFile = MeshFile<Ply>;
+pub type StlFile = MeshFile<Stl>;
+
+// A model referenced by path. The file is parsed when the scene is loaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "MeshFileParams", bound = "")]
+pub struct MeshFile<F: MeshFormat> {
+    pub path: String,
+    pub material: Material,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub use_mtl: bool,
+    #[serde(skip_serializing)]
+    pub meshes: Vec<Mesh>, // Note, loaded from `path`.
+    #[serde(skip_serializing)]
+    bvh: Bvh,
+    #[serde(skip)]
+    format: PhantomData<F>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MeshFileParams {
+    pub path: String,
+    // Used for OBJ groups without a material, or for everything else.
+    pub material: Material,
+    // Map the materials from an OBJ model's MTL libraries onto our materials.
+    #[serde(default)]
+    pub use_mtl: bool,
+}
+
+impl<F: MeshFormat> TryFrom<MeshFileParams> for MeshFile<F> {
+    type Error = String;
+
+    fn try_from(p: MeshFileParams) -> Result<Self, Self::Error> {
+        MeshFile::load(&p.path, p.material, p.use_mtl).map_err(|e| format!("{:#}", e))
+    }
+}
+
+impl<F: MeshFormat> MeshFile<F> {
+    pub fn load(path: &str, material: Material, use_mtl: bool) -> eyre::Result<MeshFile<F>> {
+        let meshes = F::load(Path::new(path), &material, use_mtl)?;
+        let bvh = Bvh::build(&meshes);
+        Ok(MeshFile {
+            path: path.to_string(),
+            material,
+            use_mtl,
+            meshes,
+            bvh,
+            format: PhantomData,
+        })
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.meshes.iter().any(|m| m.material.is_emissive())
+    }
+}
+
+impl MeshFormat for Obj {
+    fn load(path: &Path, material: &Material, use_mtl: bool) -> eyre::Result<Vec<Mesh>> {
+        let model = load_obj(path)?;
+        let materials = if use_mtl {
+            load_obj_materials(path, &model)?
+        } else {
+            Default::default()
+        };
+        let mut meshes = Vec::with_capacity(model.groups.len());
+        for group in model.groups {
+            let group_material = group
+                .material
+                .as_ref()
+                .and_then(|name| materials.get(name))
+                .unwrap_or(material)
+                .clone();
+            let mesh = Mesh::new(
+                group.vertices,
+                group.indices,
+                group.normals,
+                group.uvs,
+                group_material,
+            )
+            .map_err(|e| eyre::eyre!("group {}: {}", group.name, e))?;
+            meshes.push(mesh);
+        }
+        Ok(meshes)
+    }
+}
+
+impl MeshFormat for Ply {
+    fn load(path: &Path, material: &Material, use_mtl: bool) -> eyre::Result<Vec<Mesh>> {
+        if use_mtl {
+            eyre::bail!("{}: use_mtl only applies to OBJ models", path.display());
+        }
+        let model = load_ply(path)?;
+        let mesh = Mesh::new(
+            model.vertices,
+            model.indices,
+            model.normals,
+            model.uvs,
+            material.clone(),
+        )
+        .and_then(|m| m.with_colors(model.colors))
+        .map_err(|e| eyre::eyre!("{}: {}", path.display(), e))?;
+        Ok(vec![mesh])
+    }
+}
+
+impl MeshFormat for Stl {
+    fn load(path: &Path, material: &Material, use_mtl: bool) -> eyre::Result<Vec<Mesh>> {
+        if use_mtl {
+            eyre::bail!("{}: use_mtl only applies to OBJ models", path.display());
+        }
+        let model = load_stl(path)?;
+        let mesh = Mesh::new(
+            model.vertices,
+            model.indices,
+            Vec::new(),
+            Vec::new(),
+            material.clone(),
+        )
+        .map_err(|e| eyre::eyre!("{}: {}", path.display(), e))?;
+        Ok(vec![mesh])
+    }
+}
+
+impl<F: MeshFormat> Hittable for MeshFile<F> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.bvh.hit(&self.meshes, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use crate::point3d::Point3D;
+
+    const GLASS: &str = "{\"Glass\":{\"index_of_refraction\":1.5}}";
+
+    fn body(kind: &str, path: &str, material: &str, extra: &str) -> (String, Body) {
+        let json = format!(
+            "{{\"type\":\"{}\",\"path\":\"{}\",\"material\":{}{}}}",
+            kind, path, material, extra
+        );
+        let body = serde_json::from_str::<Body>(&json).unwrap();
+        (json, body)
+    }
+
+    fn first_hit(body: &Body, origin: Point3D, direction: Point3D) -> HitRecord<'_> {
+        body.hit(&Ray::new(origin, direction), 0.001, f64::INFINITY)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_load_obj_file() {
+        let (json, body) = body("ObjFile", "data/cube.obj", GLASS, ",\"use_mtl\":true");
+        let obj = match &body {
+            Body::ObjFile(obj) => obj,
+            _ => panic!("Wrong body type"),
+        };
+        assert_eq!(obj.meshes.len(), 2);
+        assert_eq!(
+            obj.meshes.iter().map(|m| m.indices.len()).sum::<usize>(),
+            12
+        );
+        assert!(matches!(obj.meshes[0].material, Material::Lambertian(_)));
+        assert!(matches!(obj.meshes[1].material, Material::Metal(_)));
+
+        let hit = first_hit(
+            &body,
+            Point3D::new(0.0, 0.0, -5.0),
+            Point3D::new(0.0, 0.0, 1.0),
+        );
+        assert!((hit.t - 4.0).abs() < 1e-9);
+        assert_eq!(serde_json::to_string(&body).unwrap(), json);
+
+        let missing = "{\"type\":\"ObjFile\",\"path\":\"data/missing.obj\",\"material\":{\"Glass\":{\"index_of_refraction\":1.5}}}";
+        assert!(serde_json::from_str::<Body>(missing).is_err());
+    }
+
+    #[test]
+    fn test_load_ply_file() {
+        let white = "{\"Lambertian\":{\"albedo\":[1.0,1.0,1.0]}}";
+        let (json, body) = body("PlyFile", "data/quad.ply", white, "");
+        // the bottom left corner of the quad is red
+        let hit = first_hit(
+            &body,
+            Point3D::new(0.01, 0.01, 1.0),
+            Point3D::new(0.0, 0.0, -1.0),
+        );
+        let color = hit.color.unwrap();
+        assert!(color.red > 0.9 && color.green < 0.1 && color.blue < 0.1);
+        assert_eq!(serde_json::to_string(&body).unwrap(), json);
+    }
+
+    #[test]
+    fn test_load_stl_file() {
+        let (_, body) = body("StlFile", "data/tetrahedron.stl", GLASS, "");
+        let stl = match &body {
+            Body::StlFile(stl) => stl,
+            _ => panic!("Wrong body type"),
+        };
+        assert_eq!(stl.meshes[0].vertices.len(), 4);
+        assert_eq!(stl.meshes[0].indices.len(), 4);
+        let hit = first_hit(
+            &body,
+            Point3D::new(0.2, 0.2, -1.0),
+            Point3D::new(0.0, 0.0, 1.0),
+        );
+        assert!((hit.t - 1.0).abs() < 1e-9);
+
+        let mtl = "{\"type\":\"StlFile\",\"path\":\"data/tetrahedron.stl\",\"material\":{\"Glass\":{\"index_of_refraction\":1.5}},\"use_mtl\":true}";
+        assert!(serde_json::from_str::<Body>(mtl).is_err());
+    }
+}
@@ -1,16 +1,239 @@
+use crate::aabb::Aabb;
+use crate::materials::Material;
+use crate::onb::Onb;
+use crate::point3d::Point3D;
 use crate::ray::{HitRecord, Hittable, Ray};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
+mod capsule;
+mod cone;
+mod constant_medium;
+mod csg;
+mod cuboid;
+mod cylinder;
+mod disk;
 mod ellipsoid;
+mod heightfield;
+mod instance;
+mod mesh;
+mod mesh_file;
+mod motion;
+mod plane;
+mod quad;
+mod rect;
+mod sdf;
+mod solid;
 mod sphere;
+mod torus;
+mod transform;
+mod triangle;
+mod volume;
 
+pub use capsule::Capsule;
+pub use cone::Cone;
+pub use constant_medium::ConstantMedium;
+pub use csg::{Csg, Operation};
+pub use cuboid::Cuboid;
+pub use cylinder::Cylinder;
+pub use disk::Disk;
 pub use ellipsoid::Ellipsoid;
+pub use heightfield::Heightfield;
+pub use instance::Instance;
+pub use mesh::Mesh;
+pub use mesh_file::{MeshFile, MeshFormat, ObjFile, PlyFile, StlFile};
+pub use motion::Motion;
+pub use plane::Plane;
+pub use quad::Quad;
+pub use rect::{Axis, Rect};
+pub use sdf::{Sdf, SdfShape};
 pub use sphere::Sphere;
+pub use torus::Torus;
+pub use transform::{Placement, Transform};
+pub use triangle::Triangle;
+pub use volume::{blackbody, GridFile, Volume};
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type")]
 pub enum Body {
     Sphere(Sphere),
     Ellipsoid(Ellipsoid),
+    Triangle(Triangle),
+    Mesh(Mesh),
+    ObjFile(ObjFile),
+    PlyFile(PlyFile),
+    StlFile(StlFile),
+    Plane(Plane),
+    Disk(Disk),
+    Quad(Quad),
+    Rect(Rect),
+    Box(Cuboid),
+    Cylinder(Cylinder),
+    Cone(Cone),
+    Capsule(Capsule),
+    Torus(Torus),
+    Sdf(Sdf),
+    Heightfield(Heightfield),
+    Transform(Transform),
+    Instance(Instance),
+    Csg(Csg),
+    ConstantMedium(ConstantMedium),
+    Volume(Volume),
+}
+
+impl Body {
+    // Resolves the geometry each Instance refers to, also inside other
+    // bodies.
+    pub fn link(&mut self, geometries: &BTreeMap<String, Arc<Body>>) -> Result<(), String> {
+        match self {
+            Self::Transform(t) => t.body.link(geometries),
+            Self::Instance(i) => i.link(geometries),
+            Self::Csg(c) => {
+                c.left.link(geometries)?;
+                c.right.link(geometries)
+            }
+            Self::ConstantMedium(m) => m.boundary.link(geometries),
+            _ => Ok(()),
+        }
+    }
+
+    // Participating media scatter light inside them rather than at a
+    // surface.
+    pub fn is_medium(&self) -> bool {
+        matches!(self, Self::ConstantMedium(_) | Self::Volume(_))
+    }
+
+    // Fraction of the light along the ray between t_min and t_max that
+    // gets through the medium, 1 for bodies that are not media.
+    pub fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match self {
+            Self::ConstantMedium(m) => m.transmittance(ray, t_min, t_max),
+            Self::Volume(v) => v.transmittance(ray, t_min, t_max),
+            _ => 1.0,
+        }
+    }
+
+    // The material of bodies that have exactly one. Models loaded from
+    // files and bodies made of other bodies have none.
+    pub fn material(&self) -> Option<&Material> {
+        match self {
+            Self::Sphere(s) => Some(&s.material),
+            Self::Ellipsoid(e) => Some(&e.material),
+            Self::Triangle(t) => Some(&t.material),
+            Self::Mesh(m) => Some(&m.material),
+            Self::Plane(p) => Some(&p.material),
+            Self::Disk(d) => Some(&d.material),
+            Self::Quad(q) => Some(&q.material),
+            Self::Rect(r) => Some(&r.material),
+            Self::Box(b) => Some(&b.material),
+            Self::Cylinder(c) => Some(&c.material),
+            Self::Cone(c) => Some(&c.material),
+            Self::Capsule(c) => Some(&c.material),
+            Self::Torus(t) => Some(&t.material),
+            Self::Sdf(s) => Some(&s.material),
+            Self::Heightfield(h) => Some(&h.material),
+            Self::ConstantMedium(m) => Some(&m.material),
+            Self::Volume(v) => Some(&v.material),
+            _ => None,
+        }
+    }
+
+    // Whether the body gives off light, also when moved by a Transform,
+    // made of other bodies or loaded from a file.
+    pub fn is_emissive(&self) -> bool {
+        match self {
+            Self::Transform(t) => t.body.is_emissive(),
+            Self::Instance(i) => i.body().is_some_and(Body::is_emissive),
+            Self::Csg(c) => c.left.is_emissive() || c.right.is_emissive(),
+            Self::ObjFile(o) => o.is_emissive(),
+            Self::PlyFile(p) => p.is_emissive(),
+            Self::StlFile(s) => s.is_emissive(),
+            _ => self.material().is_some_and(Material::is_emissive),
+        }
+    }
+
+    // A sphere around the body at `time` to aim at it with: the sphere
+    // itself, or one around the bounds of other bodies.
+    fn light_sphere(&self, time: f64) -> Option<(Point3D, f64)> {
+        match self {
+            Self::Sphere(s) => Some((s.center_at(time), s.radius.abs())),
+            Self::Ellipsoid(e) => {
+                let r = e.radii.x().max(e.radii.y()).max(e.radii.z());
+                Some((e.center_at(time), r))
+            }
+            _ => {
+                let b = self.bounding_box()?;
+                Some(((b.min + b.max) * 0.5, (b.max - b.min).length() / 2.0))
+            }
+        }
+    }
+
+    // The cone of directions from `origin` that the light sphere fills,
+    // as its axis and the cosine of its half angle. From inside the sphere
+    // that is every direction.
+    fn light_cone(&self, origin: Point3D, time: f64) -> Option<(Point3D, f64)> {
+        let (center, radius) = self.light_sphere(time)?;
+        let axis = center - origin;
+        let distance_squared = axis.length_squared();
+        if distance_squared <= radius * radius {
+            return Some((Point3D::new(0.0, 0.0, 1.0), -1.0));
+        }
+        Some((axis, (1.0 - radius * radius / distance_squared).sqrt()))
+    }
+
+    // Direction from `origin` towards the body for lighting, uniformly
+    // distributed over the cone of its light sphere. Bodies without bounds
+    // can't be aimed at, the density of any direction towards them is 0.
+    pub fn random_direction_towards(&self, origin: Point3D, time: f64) -> Point3D {
+        let mut rng = rand::thread_rng();
+        let (axis, cos_max) = self
+            .light_cone(origin, time)
+            .unwrap_or((Point3D::new(0.0, 0.0, 1.0), -1.0));
+        let z = 1.0 + rng.gen::<f64>() * (cos_max - 1.0);
+        let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        Onb::from_w(axis).local(Point3D::new(r * phi.cos(), r * phi.sin(), z))
+    }
+
+    // Probability density per unit solid angle of
+    // `random_direction_towards` picking `direction`.
+    pub fn pdf_towards(&self, origin: Point3D, direction: Point3D, time: f64) -> f64 {
+        let (axis, cos_max) = match self.light_cone(origin, time) {
+            Some(cone) => cone,
+            None => return 0.0,
+        };
+        if direction.unit_vector().dot(axis.unit_vector()) < cos_max {
+            return 0.0;
+        }
+        1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_max))
+    }
+
+    // The material of bodies that have exactly one, for changing it in
+    // place. Models loaded from files and bodies made of other bodies
+    // have none.
+    pub fn material_mut(&mut self) -> Option<&mut Material> {
+        match self {
+            Self::Sphere(s) => Some(&mut s.material),
+            Self::Ellipsoid(e) => Some(&mut e.material),
+            Self::Triangle(t) => Some(&mut t.material),
+            Self::Mesh(m) => Some(&mut m.material),
+            Self::Plane(p) => Some(&mut p.material),
+            Self::Disk(d) => Some(&mut d.material),
+            Self::Quad(q) => Some(&mut q.material),
+            Self::Rect(r) => Some(&mut r.material),
+            Self::Box(b) => Some(&mut b.material),
+            Self::Cylinder(c) => Some(&mut c.material),
+            Self::Cone(c) => Some(&mut c.material),
+            Self::Capsule(c) => Some(&mut c.material),
+            Self::Torus(t) => Some(&mut t.material),
+            Self::Sdf(s) => Some(&mut s.material),
+            Self::Heightfield(h) => Some(&mut h.material),
+            Self::ConstantMedium(m) => Some(&mut m.material),
+            Self::Volume(v) => Some(&mut v.material),
+            _ => None,
+        }
+    }
 }
 
 impl Hittable for Body {
@@ -18,6 +241,83 @@ impl Hittable for Body {
         match self {
             Self::Sphere(s) => s.hit(ray, t_min, t_max),
             Self::Ellipsoid(e) => e.hit(ray, t_min, t_max),
+            Self::Triangle(t) => t.hit(ray, t_min, t_max),
+            Self::Mesh(m) => m.hit(ray, t_min, t_max),
+            Self::ObjFile(o) => o.hit(ray, t_min, t_max),
+            Self::PlyFile(p) => p.hit(ray, t_min, t_max),
+            Self::StlFile(s) => s.hit(ray, t_min, t_max),
+            Self::Plane(p) => p.hit(ray, t_min, t_max),
+            Self::Disk(d) => d.hit(ray, t_min, t_max),
+            Self::Quad(q) => q.hit(ray, t_min, t_max),
+            Self::Rect(r) => r.hit(ray, t_min, t_max),
+            Self::Box(b) => b.hit(ray, t_min, t_max),
+            Self::Cylinder(c) => c.hit(ray, t_min, t_max),
+            Self::Cone(c) => c.hit(ray, t_min, t_max),
+            Self::Capsule(c) => c.hit(ray, t_min, t_max),
+            Self::Torus(t) => t.hit(ray, t_min, t_max),
+            Self::Sdf(s) => s.hit(ray, t_min, t_max),
+            Self::Heightfield(h) => h.hit(ray, t_min, t_max),
+            Self::Transform(t) => t.hit(ray, t_min, t_max),
+            Self::Instance(i) => i.hit(ray, t_min, t_max),
+            Self::Csg(c) => c.hit(ray, t_min, t_max),
+            Self::ConstantMedium(m) => m.hit(ray, t_min, t_max),
+            Self::Volume(v) => v.hit(ray, t_min, t_max),
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self {
+            Self::Sphere(s) => s.bounding_box(),
+            Self::Ellipsoid(e) => e.bounding_box(),
+            Self::Triangle(t) => t.bounding_box(),
+            Self::Mesh(m) => m.bounding_box(),
+            Self::ObjFile(o) => o.bounding_box(),
+            Self::PlyFile(p) => p.bounding_box(),
+            Self::StlFile(s) => s.bounding_box(),
+            Self::Plane(p) => p.bounding_box(),
+            Self::Disk(d) => d.bounding_box(),
+            Self::Quad(q) => q.bounding_box(),
+            Self::Rect(r) => r.bounding_box(),
+            Self::Box(b) => b.bounding_box(),
+            Self::Cylinder(c) => c.bounding_box(),
+            Self::Cone(c) => c.bounding_box(),
+            Self::Capsule(c) => c.bounding_box(),
+            Self::Torus(t) => t.bounding_box(),
+            Self::Sdf(s) => s.bounding_box(),
+            Self::Heightfield(h) => h.bounding_box(),
+            Self::Transform(t) => t.bounding_box(),
+            Self::Instance(i) => i.bounding_box(),
+            Self::Csg(c) => c.bounding_box(),
+            Self::ConstantMedium(m) => m.bounding_box(),
+            Self::Volume(v) => v.bounding_box(),
+        }
+    }
+
+    fn hit_all(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        match self {
+            Self::Sphere(s) => s.hit_all(ray, t_min, t_max),
+            Self::Ellipsoid(e) => e.hit_all(ray, t_min, t_max),
+            Self::Triangle(t) => t.hit_all(ray, t_min, t_max),
+            Self::Mesh(m) => m.hit_all(ray, t_min, t_max),
+            Self::ObjFile(o) => o.hit_all(ray, t_min, t_max),
+            Self::PlyFile(p) => p.hit_all(ray, t_min, t_max),
+            Self::StlFile(s) => s.hit_all(ray, t_min, t_max),
+            Self::Plane(p) => p.hit_all(ray, t_min, t_max),
+            Self::Disk(d) => d.hit_all(ray, t_min, t_max),
+            Self::Quad(q) => q.hit_all(ray, t_min, t_max),
+            Self::Rect(r) => r.hit_all(ray, t_min, t_max),
+            Self::Box(b) => b.hit_all(ray, t_min, t_max),
+            Self::Cylinder(c) => c.hit_all(ray, t_min, t_max),
+            Self::Cone(c) => c.hit_all(ray, t_min, t_max),
+            Self::Capsule(c) => c.hit_all(ray, t_min, t_max),
+            Self::Torus(t) => t.hit_all(ray, t_min, t_max),
+            Self::Sdf(s) => s.hit_all(ray, t_min, t_max),
+            Self::Heightfield(h) => h.hit_all(ray, t_min, t_max),
+            Self::Transform(t) => t.hit_all(ray, t_min, t_max),
+            Self::Instance(i) => i.hit_all(ray, t_min, t_max),
+            Self::Csg(c) => c.hit_all(ray, t_min, t_max),
+            Self::ConstantMedium(m) => m.hit_all(ray, t_min, t_max),
+            Self::Volume(v) => v.hit_all(ray, t_min, t_max),
         }
     }
 }
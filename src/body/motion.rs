@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::point3d::Point3D;
+
+fn default_time1() -> f64 {
+    1.0
+}
+
+// Straight line movement of a body, blurred over the time the camera
+// shutter is open. The body is where the scene puts it until `time0`,
+// then moves with `velocity` (units per unit of time) and stops at
+// `time1`. Rather than a velocity, `displacement` gives how far the body
+// has moved by `time1`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "MotionParams")]
+pub struct Motion {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<Point3D>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub displacement: Option<Point3D>,
+    #[serde(default)]
+    pub time0: f64,
+    #[serde(default = "default_time1")]
+    pub time1: f64,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct MotionParams {
+    #[serde(default)]
+    pub velocity: Option<Point3D>,
+    #[serde(default)]
+    pub displacement: Option<Point3D>,
+    #[serde(default)]
+    pub time0: f64,
+    #[serde(default = "default_time1")]
+    pub time1: f64,
+}
+
+impl TryFrom<MotionParams> for Motion {
+    type Error = String;
+
+    fn try_from(p: MotionParams) -> Result<Self, Self::Error> {
+        if !p.time0.is_finite() || !p.time1.is_finite() {
+            return Err("motion times must be finite".to_string());
+        }
+        if p.time0 > p.time1 {
+            return Err(format!(
+                "motion time0 {} comes after time1 {}",
+                p.time0, p.time1
+            ));
+        }
+        if p.velocity.is_some() == p.displacement.is_some() {
+            return Err("a motion needs either a velocity or a displacement".to_string());
+        }
+        Ok(Motion {
+            velocity: p.velocity,
+            displacement: p.displacement,
+            time0: p.time0,
+            time1: p.time1,
+        })
+    }
+}
+
+impl Motion {
+    pub fn new(velocity: Point3D) -> Motion {
+        Motion {
+            velocity: Some(velocity),
+            displacement: None,
+            time0: 0.0,
+            time1: 1.0,
+        }
+    }
+
+    // How far the body has moved at `time`.
+    pub fn offset(&self, time: f64) -> Point3D {
+        let elapsed = time.clamp(self.time0, self.time1) - self.time0;
+        match (self.velocity, self.displacement) {
+            (Some(velocity), _) => velocity * elapsed,
+            (None, Some(displacement)) if self.time1 > self.time0 => {
+                displacement * (elapsed / (self.time1 - self.time0))
+            }
+            _ => Point3D::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    // Bounds of a body with bounds `b` at rest, over its whole movement.
+    pub fn bounding_box(&self, b: Aabb) -> Aabb {
+        let offset = self.offset(self.time1);
+        b.surrounding(&Aabb::new(b.min + offset, b.max + offset))
+    }
+}
+
+// Where a body that may move is at `time`.
+pub(crate) fn position_at(position: Point3D, motion: &Option<Motion>, time: f64) -> Point3D {
+    match motion {
+        Some(m) => position + m.offset(time),
+        None => position,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, Sphere};
+    use crate::materials::{Lambertian, Material};
+    use crate::ray::{Hittable, Ray};
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    #[test]
+    fn test_moving_sphere() {
+        let material = Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere {
+            motion: Some(Motion::new(Point3D::new(2.0, 0.0, 0.0))),
+            ..Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, material)
+        };
+        let bounds = sphere.bounding_box().unwrap();
+        assert_eq!(bounds.min.x(), -1.0);
+        assert_eq!(bounds.max.x(), 3.0);
+
+        // looking down at x = 2, hit once the sphere has moved there
+        let ray = |time| {
+            Ray::with_time(
+                Point3D::new(2.0, 5.0, 0.0),
+                Point3D::new(0.0, -1.0, 0.0),
+                time,
+            )
+        };
+        assert!(sphere.hit(&ray(0.0), 0.0, f64::INFINITY).is_none());
+        let hit = sphere.hit(&ray(1.0), 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 4.0);
+        assert_approx_eq!(hit.normal.y(), 1.0);
+        // it stops at time1
+        assert!(sphere.hit(&ray(5.0), 0.0, f64::INFINITY).is_some());
+    }
+
+    #[test]
+    fn test_motion_json() {
+        let json = "{\"type\":\"Transform\",\"translate\":{\"x\":0.0,\"y\":1.0,\"z\":0.0},\"body\":{\"type\":\"Ellipsoid\",\"center\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"radii\":{\"x\":1.0,\"y\":1.0,\"z\":1.0},\"material\":{\"Lambertian\":{\"albedo\":[0.5,0.5,0.5]}}},\"motion\":{\"displacement\":{\"x\":0.0,\"y\":0.0,\"z\":-4.0},\"time0\":0.5,\"time1\":1.0}}";
+        let body = serde_json::from_str::<Body>(json).unwrap();
+        assert_eq!(serde_json::to_string(&body).unwrap(), json);
+        let bounds = body.bounding_box().unwrap();
+        assert_approx_eq!(bounds.min.z(), -5.0);
+        assert_approx_eq!(bounds.max.y(), 2.0);
+
+        let ray = |time| {
+            Ray::with_time(
+                Point3D::new(0.0, 1.0, 5.0),
+                Point3D::new(0.0, 0.0, -1.0),
+                time,
+            )
+        };
+        assert_approx_eq!(body.hit(&ray(0.25), 0.0, f64::INFINITY).unwrap().t, 4.0);
+        let hit = body.hit(&ray(0.75), 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 6.0);
+        assert_approx_eq!(hit.point.z(), -1.0);
+    }
+
+    #[test]
+    fn test_motion_checks() {
+        let parse = |json: &str| serde_json::from_str::<Motion>(json);
+        assert!(parse("{\"velocity\":{\"x\":1.0,\"y\":0.0,\"z\":0.0},\"time0\":0.5}").is_ok());
+        // time1 defaults to 1
+        assert!(parse("{\"velocity\":{\"x\":1.0,\"y\":0.0,\"z\":0.0},\"time0\":2.0}").is_err());
+        assert!(parse(
+            "{\"displacement\":{\"x\":1.0,\"y\":0.0,\"z\":0.0},\"time0\":1.0,\"time1\":0.0}"
+        )
+        .is_err());
+        assert!(Motion::try_from(MotionParams {
+            velocity: Some(Point3D::new(1.0, 0.0, 0.0)),
+            displacement: None,
+            time0: 0.0,
+            time1: f64::NAN,
+        })
+        .is_err());
+        assert!(parse("{\"velocity\":{\"x\":1.0,\"y\":0.0,\"z\":0.0},\"displacement\":{\"x\":1.0,\"y\":0.0,\"z\":0.0}}").is_err());
+        // a motion that does not move is a mistake
+        assert!(parse("{}").is_err());
+        assert!(parse("{\"end\":{\"x\":1.0,\"y\":0.0,\"z\":0.0}}").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::triangle::triangle_hit_record;
+use crate::materials::Material;
+use crate::onb::Onb;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Infinite plane through `point`. Texture coordinates are the distances
+// along two axes in the plane, so textures repeat every unit.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "PlaneParams")]
+pub struct Plane {
+    pub point: Point3D,
+    pub normal: Point3D,
+    pub material: Material,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaneParams {
+    pub point: Point3D,
+    pub normal: Point3D,
+    pub material: Material,
+}
+
+impl TryFrom<PlaneParams> for Plane {
+    type Error = String;
+
+    fn try_from(p: PlaneParams) -> Result<Self, Self::Error> {
+        check_normal(p.normal)?;
+        Ok(Plane::new(p.point, p.normal, p.material))
+    }
+}
+
+impl Plane {
+    pub fn new(point: Point3D, normal: Point3D, material: Material) -> Plane {
+        Plane {
+            point,
+            normal,
+            material,
+        }
+    }
+}
+
+// A zero normal has no direction, every ray would hit at NaN.
+pub(crate) fn check_normal(normal: Point3D) -> Result<(), String> {
+    let length = normal.length();
+    if !(length > 0.0 && length.is_finite()) {
+        return Err(format!("normal {:?} has no direction", normal));
+    }
+    Ok(())
+}
+
+// Ray parameter of the intersection with the plane through `point`, None
+// for rays parallel to it.
+pub(crate) fn intersect_plane(ray: &Ray, point: Point3D, normal: Point3D) -> Option<f64> {
+    let denominator = normal.dot(ray.direction);
+    if denominator.abs() < 1e-12 {
+        return None;
+    }
+    Some((point - ray.origin).dot(normal) / denominator)
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let normal = self.normal.unit_vector();
+        let t = intersect_plane(ray, self.point, normal)?;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+        let onb = Onb::from_w(normal);
+        let d = ray.at(t) - self.point;
+        Some(triangle_hit_record(
+            ray,
+            t,
+            normal,
+            normal,
+            (d.dot(onb.u), d.dot(onb.v)),
+            None,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    fn make_floor() -> Plane {
+        Plane::new(
+            Point3D::new(0.0, -0.5, 0.0),
+            Point3D::new(0.0, 2.0, 0.0),
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn test_plane_hit() {
+        let floor = make_floor();
+        let ray = Ray::new(Point3D::new(3.0, 1.5, -2.0), Point3D::new(0.0, -1.0, 0.0));
+        let hit = floor.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert!(hit.front_face);
+        assert_eq!(hit.normal, Point3D::new(0.0, 1.0, 0.0));
+        // the hit point is 3 units from `point` along one plane axis and 2
+        // along the other
+        assert_approx_eq!(hit.u * hit.u + hit.v * hit.v, 13.0);
+
+        let below = Ray::new(Point3D::new(0.0, -2.0, 0.0), Point3D::new(0.0, 1.0, 0.0));
+        let hit = floor.hit(&below, 0.0, f64::INFINITY).unwrap();
+        assert!(!hit.front_face);
+        assert_eq!(hit.normal, Point3D::new(0.0, -1.0, 0.0));
+
+        let parallel = Ray::new(Point3D::new(0.0, 1.0, 0.0), Point3D::new(1.0, 0.0, 0.0));
+        assert!(floor.hit(&parallel, 0.0, f64::INFINITY).is_none());
+        assert!(floor.bounding_box().is_none());
+    }
+
+    #[test]
+    fn test_to_json() {
+        let floor = make_floor();
+        let serialized = serde_json::to_string(&floor).unwrap();
+        assert_eq!(
+            "{\"point\":{\"x\":0.0,\"y\":-0.5,\"z\":0.0},\"normal\":{\"x\":0.0,\"y\":2.0,\"z\":0.0},\"material\":{\"Lambertian\":{\"albedo\":[0.5,0.5,0.5]}}}",
+            serialized
+        );
+        let p = serde_json::from_str::<Plane>(&serialized).unwrap();
+        assert_eq!(p.normal, floor.normal);
+
+        let flat = serialized.replace("\"y\":2.0", "\"y\":0.0");
+        assert!(serde_json::from_str::<Plane>(&flat).is_err());
+        // built without the check, a zero normal misses instead of hitting at NaN
+        let ray = Ray::new(Point3D::new(0.0, 1.0, 0.0), Point3D::new(0.0, -1.0, 0.0));
+        let flat = Plane::new(floor.point, Point3D::new(0.0, 0.0, 0.0), floor.material);
+        assert!(flat.hit(&ray, 0.0, f64::INFINITY).is_none());
+    }
+}
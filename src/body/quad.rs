@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::plane::{check_normal, intersect_plane};
+use crate::body::triangle::{triangle_bounding_box, triangle_hit_record};
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Parallelogram spanned by the edges `u` and `v` from `corner`. The normal
+// is u x v and the texture coordinates run from 0 to 1 along each edge.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "QuadParams")]
+pub struct Quad {
+    pub corner: Point3D,
+    pub u: Point3D,
+    pub v: Point3D,
+    pub material: Material,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuadParams {
+    pub corner: Point3D,
+    pub u: Point3D,
+    pub v: Point3D,
+    pub material: Material,
+}
+
+impl TryFrom<QuadParams> for Quad {
+    type Error = String;
+
+    fn try_from(p: QuadParams) -> Result<Self, Self::Error> {
+        // parallel edges span no area
+        check_normal(p.u.cross(p.v))?;
+        Ok(Quad::new(p.corner, p.u, p.v, p.material))
+    }
+}
+
+impl Quad {
+    pub fn new(corner: Point3D, u: Point3D, v: Point3D, material: Material) -> Quad {
+        Quad {
+            corner,
+            u,
+            v,
+            material,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let n = self.u.cross(self.v);
+        let t = intersect_plane(ray, self.corner, n)?;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+        // planar coordinates of the hit point in terms of the two edges
+        let d = ray.at(t) - self.corner;
+        let w = n / n.dot(n);
+        let alpha = w.dot(d.cross(self.v));
+        let beta = w.dot(self.u.cross(d));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        let normal = n.unit_vector();
+        Some(triangle_hit_record(
+            ray,
+            t,
+            normal,
+            normal,
+            (alpha, beta),
+            None,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let opposite = self.corner + self.u + self.v;
+        Some(
+            triangle_bounding_box(self.corner, self.corner + self.u, opposite).surrounding(
+                &triangle_bounding_box(self.corner, self.corner + self.v, opposite),
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    #[test]
+    fn test_quad_hit() {
+        let quad = Quad::new(
+            Point3D::new(-1.0, -1.0, 0.0),
+            Point3D::new(2.0, 0.0, 0.0),
+            Point3D::new(1.0, 2.0, 0.0),
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        );
+        let ray = Ray::new(Point3D::new(0.5, 0.0, 5.0), Point3D::new(0.0, 0.0, -1.0));
+        let hit = quad.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 5.0);
+        assert!(hit.front_face);
+        assert_eq!(hit.normal, Point3D::new(0.0, 0.0, 1.0));
+        assert_approx_eq!(hit.u, 0.5);
+        assert_approx_eq!(hit.v, 0.5);
+
+        // inside the bounding box but outside the parallelogram
+        let miss = Ray::new(Point3D::new(-0.9, 0.9, 5.0), Point3D::new(0.0, 0.0, -1.0));
+        assert!(quad.hit(&miss, 0.0, f64::INFINITY).is_none());
+        let bounds = quad.bounding_box().unwrap();
+        assert_approx_eq!(bounds.max.x(), 2.0, 1e-5);
+        assert_approx_eq!(bounds.max.y(), 1.0, 1e-5);
+
+        // parallel edges make no quad
+        let json = serde_json::to_string(&quad).unwrap();
+        assert!(serde_json::from_str::<Quad>(&json).is_ok());
+        let flat = json.replace("\"x\":1.0,\"y\":2.0", "\"x\":4.0,\"y\":0.0");
+        assert!(serde_json::from_str::<Quad>(&flat).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::triangle::triangle_hit_record;
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub fn index(&self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+
+    pub fn unit_vector(&self) -> Point3D {
+        match self {
+            Axis::X => Point3D::new(1.0, 0.0, 0.0),
+            Axis::Y => Point3D::new(0.0, 1.0, 0.0),
+            Axis::Z => Point3D::new(0.0, 0.0, 1.0),
+        }
+    }
+
+    // The two other axes in increasing order, they span the plane
+    // perpendicular to this one.
+    pub fn others(&self) -> (usize, usize) {
+        match self {
+            Axis::X => (1, 2),
+            Axis::Y => (0, 2),
+            Axis::Z => (0, 1),
+        }
+    }
+}
+
+// Rectangle perpendicular to `axis` at `offset` along it, e.g. for the X
+// axis `min` and `max` are the (y, z) corners. The normal points along the
+// positive axis and the texture coordinates run from 0 at `min` to 1 at
+// `max`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Rect {
+    pub axis: Axis,
+    pub offset: f64,
+    pub min: [f64; 2],
+    pub max: [f64; 2],
+    pub material: Material,
+}
+
+impl Rect {
+    pub fn new(axis: Axis, offset: f64, min: [f64; 2], max: [f64; 2], material: Material) -> Rect {
+        Rect {
+            axis,
+            offset,
+            min,
+            max,
+            material,
+        }
+    }
+
+    fn corner(&self, a: f64, b: f64) -> Point3D {
+        let mut c = [0.0; 3];
+        let (i, j) = self.axis.others();
+        c[self.axis.index()] = self.offset;
+        c[i] = a;
+        c[j] = b;
+        Point3D::new(c[0], c[1], c[2])
+    }
+}
+
+impl Hittable for Rect {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let k = self.axis.index();
+        let (i, j) = self.axis.others();
+        let t = (self.offset - ray.origin[k]) / ray.direction[k];
+        // also rejects NaN for rays parallel to the rectangle
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+        let a = ray.origin[i] + t * ray.direction[i];
+        let b = ray.origin[j] + t * ray.direction[j];
+        if a < self.min[0] || a > self.max[0] || b < self.min[1] || b > self.max[1] {
+            return None;
+        }
+        let normal = self.axis.unit_vector();
+        let u = (a - self.min[0]) / (self.max[0] - self.min[0]);
+        let v = (b - self.min[1]) / (self.max[1] - self.min[1]);
+        Some(triangle_hit_record(
+            ray,
+            t,
+            normal,
+            normal,
+            (u, v),
+            None,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // pad the flat axis so the slab test still hits it
+        let padding = self.axis.unit_vector() * 1e-6;
+        Some(Aabb::new(
+            self.corner(self.min[0], self.min[1]) - padding,
+            self.corner(self.max[0], self.max[1]) + padding,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    #[test]
+    fn test_rect_hit() {
+        let wall = Rect::new(
+            Axis::X,
+            2.0,
+            [0.0, -1.0],
+            [4.0, 1.0],
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        );
+        let ray = Ray::new(Point3D::new(0.0, 1.0, 0.5), Point3D::new(1.0, 0.0, 0.0));
+        let hit = wall.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert!(!hit.front_face);
+        assert_eq!(hit.normal, Point3D::new(-1.0, 0.0, 0.0));
+        assert_approx_eq!(hit.u, 0.25);
+        assert_approx_eq!(hit.v, 0.75);
+
+        let above = Ray::new(Point3D::new(0.0, 5.0, 0.0), Point3D::new(1.0, 0.0, 0.0));
+        assert!(wall.hit(&above, 0.0, f64::INFINITY).is_none());
+        let parallel = Ray::new(Point3D::new(0.0, 1.0, 0.0), Point3D::new(0.0, 1.0, 0.0));
+        assert!(wall.hit(&parallel, 0.0, f64::INFINITY).is_none());
+
+        let bounds = wall.bounding_box().unwrap();
+        assert_eq!(bounds.min.y(), 0.0);
+        assert_eq!(bounds.max.z(), 1.0);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json = "{\"axis\":\"Y\",\"offset\":0.0,\"min\":[-1.0,-1.0],\"max\":[1.0,1.0],\"material\":{\"Lambertian\":{\"albedo\":[0.5,0.5,0.5]}}}";
+        let rect = serde_json::from_str::<Rect>(json).unwrap();
+        assert_eq!(rect.axis, Axis::Y);
+        assert_eq!(json, serde_json::to_string(&rect).unwrap());
+    }
+}
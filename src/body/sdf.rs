@@ -0,0 +1,521 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::sphere::u_v_from_sphere_hit_point;
+use crate::body::triangle::triangle_hit_record;
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Tree of signed distance functions, negative inside the shape and
+// positive outside. Primitives are positioned directly, the operators
+// combine or deform the shapes below them. Use a `Transform` body to move
+// the whole Sdf, e.g. as twist always turns around the y axis.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum SdfShape {
+    Sphere {
+        center: Point3D,
+        radius: f64,
+    },
+    Box {
+        center: Point3D,
+        half_size: Point3D,
+    },
+    // Lies in the xz plane.
+    Torus {
+        center: Point3D,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Capsule {
+        a: Point3D,
+        b: Point3D,
+        radius: f64,
+    },
+    Union {
+        shapes: Vec<SdfShape>,
+    },
+    // Blends the shapes where they are closer than `smoothness`.
+    SmoothUnion {
+        shapes: Vec<SdfShape>,
+        smoothness: f64,
+    },
+    Intersection {
+        shapes: Vec<SdfShape>,
+    },
+    Difference {
+        left: Box<SdfShape>,
+        right: Box<SdfShape>,
+    },
+    // `count` copies on either side of the original along each axis,
+    // `spacing` apart. The shape should fit in one cell.
+    Repeat {
+        spacing: Point3D,
+        count: Point3D,
+        shape: Box<SdfShape>,
+    },
+    // Turns the shape around the y axis by `degrees_per_unit` times y.
+    Twist {
+        degrees_per_unit: f64,
+        shape: Box<SdfShape>,
+    },
+    // Grows the shape by `radius`, which rounds off its edges.
+    Round {
+        radius: f64,
+        shape: Box<SdfShape>,
+    },
+}
+
+// Polynomial smooth minimum, at most k / 4 below min(a, b).
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (k - (a - b).abs()).max(0.0) / k;
+    a.min(b) - h * h * k * 0.25
+}
+
+fn abs(p: Point3D) -> Point3D {
+    Point3D::new(p.x().abs(), p.y().abs(), p.z().abs())
+}
+
+fn grow(b: Aabb, by: Point3D) -> Aabb {
+    Aabb::new(b.min - by, b.max + by)
+}
+
+// Largest distance of the box from the y axis.
+fn radial_extent(b: &Aabb) -> f64 {
+    let x = b.min.x().abs().max(b.max.x().abs());
+    let z = b.min.z().abs().max(b.max.z().abs());
+    (x * x + z * z).sqrt()
+}
+
+impl SdfShape {
+    pub fn distance(&self, p: Point3D) -> f64 {
+        match self {
+            SdfShape::Sphere { center, radius } => (p - *center).length() - radius,
+            SdfShape::Box { center, half_size } => {
+                let q = abs(p - *center) - *half_size;
+                let outside = q.max(Point3D::new(0.0, 0.0, 0.0)).length();
+                let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+                outside + inside
+            }
+            SdfShape::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let q = p - *center;
+                let ring = (q.x() * q.x() + q.z() * q.z()).sqrt() - major_radius;
+                (ring * ring + q.y() * q.y()).sqrt() - minor_radius
+            }
+            SdfShape::Capsule { a, b, radius } => {
+                let pa = p - *a;
+                let ba = *b - *a;
+                let h = (pa.dot(ba) / ba.length_squared()).clamp(0.0, 1.0);
+                (pa - ba * h).length() - radius
+            }
+            SdfShape::Union { shapes } => shapes
+                .iter()
+                .map(|s| s.distance(p))
+                .fold(f64::INFINITY, f64::min),
+            SdfShape::SmoothUnion { shapes, smoothness } => shapes
+                .iter()
+                .map(|s| s.distance(p))
+                .reduce(|a, b| smooth_min(a, b, *smoothness))
+                .unwrap_or(f64::INFINITY),
+            SdfShape::Intersection { shapes } => shapes
+                .iter()
+                .map(|s| s.distance(p))
+                .fold(f64::NEG_INFINITY, f64::max),
+            SdfShape::Difference { left, right } => left.distance(p).max(-right.distance(p)),
+            SdfShape::Repeat {
+                spacing,
+                count,
+                shape,
+            } => {
+                // move p into the nearest cell
+                let cell = |axis: usize| {
+                    if spacing[axis] == 0.0 {
+                        return p[axis];
+                    }
+                    let i = (p[axis] / spacing[axis])
+                        .round()
+                        .clamp(-count[axis], count[axis]);
+                    p[axis] - spacing[axis] * i
+                };
+                shape.distance(Point3D::new(cell(0), cell(1), cell(2)))
+            }
+            SdfShape::Twist {
+                degrees_per_unit,
+                shape,
+            } => {
+                let (sin, cos) = (degrees_per_unit.to_radians() * p.y()).sin_cos();
+                shape.distance(Point3D::new(
+                    cos * p.x() - sin * p.z(),
+                    p.y(),
+                    sin * p.x() + cos * p.z(),
+                ))
+            }
+            SdfShape::Round { radius, shape } => shape.distance(p) - radius,
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        match self {
+            SdfShape::Sphere { center, radius } => {
+                let r = Point3D::new(*radius, *radius, *radius);
+                Aabb::new(*center - r, *center + r)
+            }
+            SdfShape::Box { center, half_size } => {
+                Aabb::new(*center - *half_size, *center + *half_size)
+            }
+            SdfShape::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let extent = Point3D::new(
+                    major_radius + minor_radius,
+                    *minor_radius,
+                    major_radius + minor_radius,
+                );
+                Aabb::new(*center - extent, *center + extent)
+            }
+            SdfShape::Capsule { a, b, radius } => {
+                grow(Aabb::new(*a, *b), Point3D::new(*radius, *radius, *radius))
+            }
+            SdfShape::Union { shapes } => surrounding(shapes),
+            SdfShape::SmoothUnion { shapes, smoothness } => {
+                let k = smoothness.max(0.0) * 0.25;
+                grow(surrounding(shapes), Point3D::new(k, k, k))
+            }
+            SdfShape::Intersection { shapes } => shapes
+                .iter()
+                .map(|s| s.bounds())
+                .reduce(|a, b| Aabb::new(a.min.max(b.min), a.max.min(b.max)))
+                .expect("Intersection has shapes"),
+            SdfShape::Difference { left, .. } => left.bounds(),
+            SdfShape::Repeat {
+                spacing,
+                count,
+                shape,
+            } => grow(shape.bounds(), abs(*spacing * *count)),
+            SdfShape::Twist { shape, .. } => {
+                let b = shape.bounds();
+                let r = radial_extent(&b);
+                Aabb::new(
+                    Point3D::new(-r, b.min.y(), -r),
+                    Point3D::new(r, b.max.y(), r),
+                )
+            }
+            SdfShape::Round { radius, shape } => {
+                grow(shape.bounds(), Point3D::new(*radius, *radius, *radius))
+            }
+        }
+    }
+
+    // How much faster than the true distance the function can change.
+    // Sphere tracing divides by this so it never steps through the surface.
+    pub fn lipschitz(&self) -> f64 {
+        match self {
+            SdfShape::Sphere { .. }
+            | SdfShape::Box { .. }
+            | SdfShape::Torus { .. }
+            | SdfShape::Capsule { .. } => 1.0,
+            SdfShape::Union { shapes }
+            | SdfShape::SmoothUnion { shapes, .. }
+            | SdfShape::Intersection { shapes } => {
+                shapes.iter().map(|s| s.lipschitz()).fold(1.0, f64::max)
+            }
+            SdfShape::Difference { left, right } => left.lipschitz().max(right.lipschitz()),
+            SdfShape::Repeat { shape, .. } | SdfShape::Round { shape, .. } => shape.lipschitz(),
+            SdfShape::Twist {
+                degrees_per_unit,
+                shape,
+            } => {
+                // points far from the axis are moved the most
+                let k = degrees_per_unit.to_radians() * radial_extent(&shape.bounds());
+                shape.lipschitz() * (1.0 + k * k).sqrt()
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            SdfShape::Union { shapes }
+            | SdfShape::SmoothUnion { shapes, .. }
+            | SdfShape::Intersection { shapes } => {
+                if shapes.is_empty() {
+                    return Err("Sdf operators need at least one shape".to_string());
+                }
+                shapes.iter().try_for_each(|s| s.validate())
+            }
+            SdfShape::Difference { left, right } => {
+                left.validate()?;
+                right.validate()
+            }
+            SdfShape::Repeat { shape, .. }
+            | SdfShape::Twist { shape, .. }
+            | SdfShape::Round { shape, .. } => shape.validate(),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn surrounding(shapes: &[SdfShape]) -> Aabb {
+    shapes
+        .iter()
+        .map(|s| s.bounds())
+        .reduce(|a, b| a.surrounding(&b))
+        .expect("Union has shapes")
+}
+
+const EPSILON: f64 = 1e-4;
+
+fn default_max_steps() -> u32 {
+    256
+}
+
+// Body described by a signed distance function and intersected by sphere
+// tracing: the ray advances by the distance to the nearest surface until
+// it is close enough to count as a hit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "SdfParams")]
+pub struct Sdf {
+    pub shape: SdfShape,
+    pub material: Material,
+    #[serde(default = "default_max_steps")]
+    pub max_steps: u32,
+    #[serde(skip_serializing)]
+    bounds: Aabb,
+    #[serde(skip_serializing)]
+    lipschitz: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SdfParams {
+    pub shape: SdfShape,
+    pub material: Material,
+    #[serde(default = "default_max_steps")]
+    pub max_steps: u32,
+}
+
+impl TryFrom<SdfParams> for Sdf {
+    type Error = String;
+
+    fn try_from(p: SdfParams) -> Result<Self, Self::Error> {
+        let mut sdf = Sdf::new(p.shape, p.material)?;
+        sdf.max_steps = p.max_steps;
+        Ok(sdf)
+    }
+}
+
+impl Sdf {
+    pub fn new(shape: SdfShape, material: Material) -> Result<Sdf, String> {
+        shape.validate()?;
+        // a little slack so the bounds never clip the surface
+        let bounds = grow(shape.bounds(), Point3D::new(EPSILON, EPSILON, EPSILON));
+        let lipschitz = shape.lipschitz();
+        Ok(Sdf {
+            shape,
+            material,
+            max_steps: default_max_steps(),
+            bounds,
+            lipschitz,
+        })
+    }
+
+    fn gradient(&self, p: Point3D) -> Point3D {
+        let d = |offset: Point3D| self.shape.distance(p + offset) - self.shape.distance(p - offset);
+        Point3D::new(
+            d(Point3D::new(EPSILON, 0.0, 0.0)),
+            d(Point3D::new(0.0, EPSILON, 0.0)),
+            d(Point3D::new(0.0, 0.0, EPSILON)),
+        )
+    }
+}
+
+impl Hittable for Sdf {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let inv_direction = Point3D::new(1.0, 1.0, 1.0) / ray.direction;
+        let (t0, t1) = self.bounds.interval(ray, inv_direction, t_min, t_max)?;
+        // march in units of length rather than ray parameter
+        let scale = ray.direction.length();
+        let d = ray.direction / scale;
+        let point = |s: f64| ray.origin + d * s;
+        let distance = |s: f64| self.shape.distance(point(s)) / self.lipschitz;
+
+        let mut s = t0 * scale;
+        let end = t1 * scale;
+        // Rays coming from outside the bounds start outside the body. A ray
+        // starting on the surface, e.g. one scattered off it, is traced on
+        // the side it heads into.
+        let start = distance(s);
+        let mut leaving = t0 == t_min && start.abs() < EPSILON;
+        let side = if t0 > t_min {
+            1.0
+        } else if leaving {
+            self.gradient(point(s)).dot(d).signum()
+        } else {
+            start.signum()
+        };
+        for _ in 0..self.max_steps {
+            let step = side * distance(s);
+            if step < EPSILON {
+                if !leaving {
+                    let t = s / scale;
+                    let normal = self.gradient(ray.at(t)).unit_vector();
+                    let uv = u_v_from_sphere_hit_point(normal);
+                    return Some(triangle_hit_record(
+                        ray,
+                        t,
+                        normal,
+                        normal,
+                        uv,
+                        None,
+                        &self.material,
+                    ));
+                }
+                s += EPSILON;
+            } else {
+                leaving = false;
+                s += step;
+            }
+            if s > end {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::{Glass, Lambertian};
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    fn material() -> Material {
+        Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5)))
+    }
+
+    fn sphere(x: f64, radius: f64) -> SdfShape {
+        SdfShape::Sphere {
+            center: Point3D::new(x, 0.0, 0.0),
+            radius,
+        }
+    }
+
+    #[test]
+    fn test_distances() {
+        let origin = Point3D::new(0.0, 0.0, 0.0);
+        let cube = SdfShape::Box {
+            center: origin,
+            half_size: Point3D::new(1.0, 1.0, 1.0),
+        };
+        assert_approx_eq!(cube.distance(Point3D::new(3.0, 0.0, 0.0)), 2.0);
+        assert_approx_eq!(cube.distance(Point3D::new(2.0, 2.0, 0.0)), 2.0f64.sqrt());
+        assert_approx_eq!(cube.distance(origin), -1.0);
+
+        let rounded = SdfShape::Round {
+            radius: 0.5,
+            shape: Box::new(cube),
+        };
+        assert_approx_eq!(rounded.distance(Point3D::new(3.0, 0.0, 0.0)), 1.5);
+
+        let torus = SdfShape::Torus {
+            center: origin,
+            major_radius: 2.0,
+            minor_radius: 0.5,
+        };
+        assert_approx_eq!(torus.distance(origin), 1.5);
+        assert_approx_eq!(torus.distance(Point3D::new(0.0, 0.0, 2.0)), -0.5);
+
+        // the blend bulges out between the two spheres
+        let shapes = vec![sphere(-1.0, 0.8), sphere(1.0, 0.8)];
+        let union = SdfShape::Union {
+            shapes: shapes.clone(),
+        };
+        let smooth = SdfShape::SmoothUnion {
+            shapes,
+            smoothness: 0.5,
+        };
+        assert_approx_eq!(union.distance(origin), 0.2);
+        assert!(smooth.distance(origin) < 0.2);
+        assert_approx_eq!(smooth.distance(Point3D::new(-3.0, 0.0, 0.0)), 1.2);
+
+        let repeat = SdfShape::Repeat {
+            spacing: Point3D::new(3.0, 0.0, 0.0),
+            count: Point3D::new(2.0, 0.0, 0.0),
+            shape: Box::new(sphere(0.0, 1.0)),
+        };
+        assert_approx_eq!(repeat.distance(Point3D::new(6.0, 0.0, 0.0)), -1.0);
+        assert_approx_eq!(repeat.distance(Point3D::new(10.0, 0.0, 0.0)), 3.0);
+        assert_approx_eq!(repeat.bounds().max.x(), 7.0);
+    }
+
+    #[test]
+    fn test_sphere_tracing() {
+        let sdf = Sdf::new(sphere(0.0, 1.0), material()).unwrap();
+        let ray = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Point3D::new(2.0, 0.0, 0.0));
+        let hit = sdf.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-3);
+        assert!(hit.front_face);
+        assert_approx_eq!(hit.normal.x(), -1.0, 1e-3);
+        assert!(sdf.hit(&ray, 0.0, 1.5).is_none());
+
+        let miss = Ray::new(Point3D::new(-5.0, 1.1, 0.0), Point3D::new(1.0, 0.0, 0.0));
+        assert!(sdf.hit(&miss, 0.0, f64::INFINITY).is_none());
+
+        // refracted into the body: the ray starts on the surface and
+        // finds the far side
+        let glass = Sdf::new(sphere(0.0, 1.0), Material::Glass(Glass::new(1.5))).unwrap();
+        let inside = Ray::new(Point3D::new(-1.0, 0.0, 0.0), Point3D::new(1.0, 0.0, 0.0));
+        let hit = glass.hit(&inside, 0.0, f64::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-3);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn test_twist() {
+        // a flat bar, its top is turned by 90 degrees
+        let bar = SdfShape::Box {
+            center: Point3D::new(0.0, 0.0, 0.0),
+            half_size: Point3D::new(1.0, 1.0, 0.1),
+        };
+        let twist = SdfShape::Twist {
+            degrees_per_unit: 90.0,
+            shape: Box::new(bar),
+        };
+        assert!(twist.lipschitz() > 1.0);
+        assert!(twist.distance(Point3D::new(0.9, 0.0, 0.0)) < 0.0);
+        assert!(twist.distance(Point3D::new(0.9, 0.99, 0.0)) > 0.0);
+        assert!(twist.distance(Point3D::new(0.0, 0.99, 0.9)) < 0.0);
+
+        let sdf = Sdf::new(twist, material()).unwrap();
+        let ray = Ray::new(Point3D::new(0.0, 0.99, 5.0), Point3D::new(0.0, 0.0, -1.0));
+        let hit = sdf.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(hit.point.z() > 0.9 && hit.point.z() < 1.01);
+    }
+
+    #[test]
+    fn test_sdf_json() {
+        let json = "{\"type\":\"Sdf\",\"shape\":{\"type\":\"SmoothUnion\",\"shapes\":[{\"type\":\"Sphere\",\"center\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"radius\":1.0},{\"type\":\"Capsule\",\"a\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"b\":{\"x\":0.0,\"y\":2.0,\"z\":0.0},\"radius\":0.3}],\"smoothness\":0.5},\"material\":{\"Lambertian\":{\"albedo\":[0.5,0.5,0.5]}},\"max_steps\":256}";
+        let body = serde_json::from_str::<crate::body::Body>(json).unwrap();
+        assert_eq!(serde_json::to_string(&body).unwrap(), json);
+        assert_approx_eq!(body.bounding_box().unwrap().max.y(), 2.425, 1e-3);
+
+        let empty = "{\"type\":\"Sdf\",\"shape\":{\"type\":\"Union\",\"shapes\":[]},\"material\":{\"Lambertian\":{\"albedo\":[0.5,0.5,0.5]}}}";
+        let err = serde_json::from_str::<crate::body::Body>(empty).unwrap_err();
+        assert!(err.to_string().contains("at least one shape"));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::body::triangle::triangle_hit_record;
+use crate::materials::Material;
+use crate::onb::Onb;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Ray;
+
+// Local coordinate frame of a round solid: its axis runs along +z starting
+// at the origin. Rays are moved into the frame so the intersection code
+// only deals with the canonical solid. The basis is orthonormal, so ray
+// parameters are the same in both frames.
+pub(crate) struct Frame {
+    origin: Point3D,
+    onb: Onb,
+}
+
+impl Frame {
+    pub(crate) fn new(origin: Point3D, axis: Point3D) -> Frame {
+        Frame {
+            origin,
+            onb: Onb::from_w(axis),
+        }
+    }
+
+    pub(crate) fn to_local(&self, ray: &Ray) -> Ray {
+        Ray::with_time(
+            self.onb.to_local(ray.origin - self.origin),
+            self.onb.to_local(ray.direction),
+            ray.time,
+        )
+    }
+
+    // Turns the nearest local surface hit into a hit record for the world
+    // space ray. The local normal must point outwards.
+    pub(crate) fn hit_record<'material>(
+        &self,
+        ray: &Ray,
+        hit: SurfaceHit,
+        material: &'material Material,
+    ) -> HitRecord<'material> {
+        let normal = self.onb.local(hit.normal).unit_vector();
+        triangle_hit_record(ray, hit.t, normal, normal, hit.uv, None, material)
+    }
+}
+
+// Intersection with one of the surfaces of a solid, in local coordinates.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SurfaceHit {
+    pub t: f64,
+    pub normal: Point3D,
+    pub uv: (f64, f64),
+}
+
+// Keeps the hit closest to the ray origin within (t_min, t_max).
+pub(crate) fn closest(
+    hits: impl IntoIterator<Item = SurfaceHit>,
+    t_min: f64,
+    t_max: f64,
+) -> Option<SurfaceHit> {
+    hits.into_iter()
+        .filter(|h| h.t > t_min && h.t < t_max)
+        .min_by(|a, b| a.t.total_cmp(&b.t))
+}
+
+// Real roots of a x^2 + b x + c, smallest first.
+pub(crate) fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 || a == 0.0 {
+        return None;
+    }
+    // avoids the cancellation of the textbook formula
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (r0, r1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some((r0.min(r1), r0.max(r1)))
+}
+
+// Angle around the local z axis mapped to [0, 1].
+pub(crate) fn azimuth(p: Point3D) -> f64 {
+    p.y().atan2(p.x()) / (2.0 * std::f64::consts::PI) + 0.5
+}
+
+// Hit with the flat cap of radius `radius` at height `z`, facing +z or -z.
+pub(crate) fn cap_hit(ray: &Ray, z: f64, radius: f64, facing_up: bool) -> Option<SurfaceHit> {
+    let t = (z - ray.origin.z()) / ray.direction.z();
+    if !t.is_finite() {
+        return None;
+    }
+    let p = ray.at(t);
+    if p.x() * p.x() + p.y() * p.y() > radius * radius {
+        return None;
+    }
+    let normal = Point3D::new(0.0, 0.0, if facing_up { 1.0 } else { -1.0 });
+    Some(SurfaceHit {
+        t,
+        normal,
+        uv: (0.5 + 0.5 * p.x() / radius, 0.5 + 0.5 * p.y() / radius),
+    })
+}
+
+#[test]
+fn test_solve_quadratic() {
+    assert_eq!(solve_quadratic(1.0, -3.0, 2.0), Some((1.0, 2.0)));
+    assert_eq!(solve_quadratic(1.0, 0.0, 1.0), None);
+    assert_eq!(solve_quadratic(2.0, 0.0, 0.0), Some((0.0, 0.0)));
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::aabb::Aabb;
+use crate::body::motion::{position_at, Motion};
 use crate::materials::Material;
 use crate::point3d::Point3D;
 use crate::ray::HitRecord;
@@ -20,6 +22,8 @@ pub struct Sphere {
     pub center: Point3D,
     pub radius: f64,
     pub material: Material,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion: Option<Motion>,
 }
 
 impl Sphere {
@@ -28,11 +32,16 @@ impl Sphere {
             center,
             radius,
             material,
+            motion: None,
         }
     }
+
+    pub fn center_at(&self, time: f64) -> Point3D {
+        position_at(self.center, &self.motion, time)
+    }
 }
 
-fn u_v_from_sphere_hit_point(hit_point_on_sphere: Point3D) -> (f64, f64) {
+pub(crate) fn u_v_from_sphere_hit_point(hit_point_on_sphere: Point3D) -> (f64, f64) {
     let n = hit_point_on_sphere.unit_vector();
     let x = n.x();
     let y = n.y();
@@ -44,7 +53,8 @@ fn u_v_from_sphere_hit_point(hit_point_on_sphere: Point3D) -> (f64, f64) {
 
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = ray.origin - self.center;
+        let center = self.center_at(ray.time);
+        let oc = ray.origin - center;
         let a = ray.direction.length_squared();
         let half_b = oc.dot(ray.direction);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -57,10 +67,10 @@ impl Hittable for Sphere {
             for root in [root_a, root_b].iter() {
                 if *root < t_max && *root > t_min {
                     let p = ray.at(*root);
-                    let normal = (p - self.center) / self.radius;
+                    let normal = (p - center) / self.radius;
                     let front_face = ray.direction.dot(normal) < 0.0;
 
-                    let (u, v) = u_v_from_sphere_hit_point(p - self.center);
+                    let (u, v) = u_v_from_sphere_hit_point(p - center);
 
                     return Some(HitRecord {
                         t: *root,
@@ -70,12 +80,25 @@ impl Hittable for Sphere {
                         material: &self.material,
                         u,
                         v,
+                        color: None,
+                        emitted: None,
                     });
                 }
             }
         }
         None
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // radius may be negative for hollow spheres
+        let r = self.radius.abs();
+        let extent = Point3D::new(r, r, r);
+        let b = Aabb::new(self.center - extent, self.center + extent);
+        Some(match &self.motion {
+            Some(m) => m.bounding_box(b),
+            None => b,
+        })
+    }
 }
 
 #[test]
@@ -106,11 +129,7 @@ fn test_to_json() {
     let textured_sphere = Sphere::new(
         Point3D::new(0.0, 0.0, 0.0),
         1.0,
-        Material::Texture(Texture::new(
-            Srgb::new(0.5, 0.5, 0.5),
-            "data/earth.jpg",
-            0.0,
-        )),
+        Material::Texture(Texture::new(Srgb::new(0.5, 0.5, 0.5), "data/earth.jpg", 0.0).unwrap()),
     );
 
     let tserialized = serde_json::to_string(&textured_sphere).unwrap();
@@ -119,7 +138,7 @@ fn test_to_json() {
         tserialized,
     );
 
-    let tex = Texture::new(Srgb::new(0.5, 0.5, 0.5), "data/earth.jpg", 0.0);
+    let tex = Texture::new(Srgb::new(0.5, 0.5, 0.5), "data/earth.jpg", 0.0).unwrap();
     let tloadable = "{\"center\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"radius\":1.0,\"material\":{\"Texture\":{\"albedo\":[0.5,0.5,0.5],\"pixels\":\"data/earth.jpg\",\"width\":2048,\"height\":1024,\"h_offset\":0.0}}}";
     let loaded = serde_json::from_str::<Sphere>(&tloadable).unwrap();
     match loaded.material {
@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::solid::{azimuth, closest, Frame, SurfaceHit};
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Ring around `axis` through `center`. `major_radius` is the distance from
+// the center to the middle of the tube and `minor_radius` the radius of
+// the tube. u goes around the axis and v around the tube.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Torus {
+    pub center: Point3D,
+    pub axis: Point3D,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub material: Material,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3D,
+        axis: Point3D,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Material,
+    ) -> Torus {
+        Torus {
+            center,
+            axis,
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+const EPSILON: f64 = 1e-9;
+
+fn is_zero(x: f64) -> bool {
+    x.abs() < EPSILON
+}
+
+// Real roots of x^2 + p x + q.
+fn solve_normed_quadratic(p: f64, q: f64, roots: &mut Vec<f64>) {
+    let p = p / 2.0;
+    let d = p * p - q;
+    if is_zero(d) {
+        roots.push(-p);
+    } else if d > 0.0 {
+        let sqrt_d = d.sqrt();
+        roots.push(sqrt_d - p);
+        roots.push(-sqrt_d - p);
+    }
+}
+
+// Real roots of x^3 + a x^2 + b x + c (Cardano, after Schwarze in Graphics
+// Gems I).
+fn solve_normed_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // substitute x = y - a/3 to eliminate the quadratic term
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+    let mut roots = if is_zero(d) {
+        if is_zero(q) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        // three real roots
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        let third = std::f64::consts::PI / 3.0;
+        vec![
+            t * phi.cos(),
+            -t * (phi + third).cos(),
+            -t * (phi - third).cos(),
+        ]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+    for r in roots.iter_mut() {
+        *r -= a / 3.0;
+    }
+    roots
+}
+
+// Real roots of x^4 + a x^3 + b x^2 + c x + d (Ferrari).
+fn solve_normed_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // substitute x = y - a/4 to eliminate the cubic term
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + c;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * c / 4.0 + d;
+    let mut roots = Vec::with_capacity(4);
+    if is_zero(r) {
+        // no absolute term: y (y^3 + p y + q) = 0
+        roots.push(0.0);
+        roots.extend(solve_normed_cubic(0.0, p, q));
+    } else {
+        // one root of the resolvent cubic splits the quartic in two
+        // quadratics
+        let z = solve_normed_cubic(-p / 2.0, -r, r * p / 2.0 - q * q / 8.0)[0];
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if is_zero(u) {
+            0.0
+        } else if u > 0.0 {
+            u.sqrt()
+        } else {
+            return roots;
+        };
+        let v = if is_zero(v) {
+            0.0
+        } else if v > 0.0 {
+            v.sqrt()
+        } else {
+            return roots;
+        };
+        let v = if q < 0.0 { -v } else { v };
+        solve_normed_quadratic(v, z - u, &mut roots);
+        solve_normed_quadratic(-v, z + u, &mut roots);
+    }
+    for root in roots.iter_mut() {
+        *root -= a / 4.0;
+        // polish with a couple of Newton steps, the closed form loses
+        // accuracy for grazing rays
+        for _ in 0..2 {
+            let x = *root;
+            let f = (((x + a) * x + b) * x + c) * x + d;
+            let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+            if df != 0.0 {
+                *root -= f / df;
+            }
+        }
+    }
+    roots
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let frame = Frame::new(self.center, self.axis);
+        let local = frame.to_local(ray);
+        let scale = local.direction.length();
+        let d = local.direction / scale;
+        let big_r2 = self.major_radius * self.major_radius;
+        let small_r2 = self.minor_radius * self.minor_radius;
+
+        // The quartic loses precision quickly with the distance to the
+        // torus, so solve it from the closest point to the center instead.
+        let bound = self.major_radius + self.minor_radius;
+        let shift = local.origin.dot(-d);
+        let closest_point = local.origin + d * shift;
+        if closest_point.length_squared() > bound * bound {
+            return None;
+        }
+        let o = closest_point;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) for p = o + s d
+        let f = o.dot(d);
+        let e = o.length_squared() - big_r2 - small_r2;
+        let roots = solve_normed_quartic(
+            4.0 * f,
+            4.0 * f * f + 2.0 * e + 4.0 * big_r2 * d.z() * d.z(),
+            4.0 * f * e + 8.0 * big_r2 * o.z() * d.z(),
+            e * e - 4.0 * big_r2 * (small_r2 - o.z() * o.z()),
+        );
+
+        let hits = roots.into_iter().map(|s| {
+            let p = o + d * s;
+            // the normal points away from the closest point on the ring
+            // through the middle of the tube
+            let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+            let ring = Point3D::new(p.x(), p.y(), 0.0) * (self.major_radius / rho);
+            let normal = p - ring;
+            let tube = p.z().atan2(rho - self.major_radius);
+            SurfaceHit {
+                t: (shift + s) / scale,
+                normal,
+                uv: (azimuth(p), tube / (2.0 * std::f64::consts::PI) + 0.5),
+            }
+        });
+        let hit = closest(hits, t_min, t_max)?;
+        Some(frame.hit_record(ray, hit, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let n = self.axis.unit_vector();
+        let extent = |c: f64| self.major_radius * (1.0 - c * c).max(0.0).sqrt() + self.minor_radius;
+        let extent = Point3D::new(extent(n.x()), extent(n.y()), extent(n.z()));
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    #[test]
+    fn test_solve_normed_quartic() {
+        // (x - 1)(x + 2)(x - 3)(x + 0.5)
+        let mut roots = solve_normed_quartic(-1.5, -6.0, 3.5, 3.0);
+        roots.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([-2.0, -0.5, 1.0, 3.0]) {
+            assert_approx_eq!(*root, expected);
+        }
+        // x^4 + 1 has no real roots
+        assert!(solve_normed_quartic(0.0, 0.0, 0.0, 1.0).is_empty());
+    }
+
+    fn make_torus() -> Torus {
+        Torus::new(
+            Point3D::new(0.0, 0.0, -10.0),
+            Point3D::new(0.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn test_torus_hit() {
+        let torus = make_torus();
+        // across the ring: hits the outside of the near tube first
+        let ray = Ray::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(0.0, 0.0, -2.0));
+        let hit = torus.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 3.75);
+        assert!(hit.front_face);
+        assert_approx_eq!(hit.normal.z(), 1.0);
+
+        // straight down through the hole
+        let hole = Ray::new(Point3D::new(0.0, 5.0, -10.0), Point3D::new(0.0, -1.0, 0.0));
+        assert!(torus.hit(&hole, 0.0, f64::INFINITY).is_none());
+
+        // straight down onto the top of the tube
+        let top = Ray::new(Point3D::new(2.0, 5.0, -10.0), Point3D::new(0.0, -1.0, 0.0));
+        let hit = torus.hit(&top, 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 4.5);
+        assert_approx_eq!(hit.normal.y(), 1.0);
+
+        let bounds = torus.bounding_box().unwrap();
+        assert_approx_eq!(bounds.max.x(), 2.5);
+        assert_approx_eq!(bounds.max.y(), 0.5);
+    }
+}
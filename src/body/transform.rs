@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::body::motion::Motion;
+use crate::body::Body;
+use crate::matrix::Matrix4;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+// Where a body is placed in the scene, either as translation, rotation and
+// scale (applied in that order: scale first, translation last) or as a
+// full matrix.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Placement {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate: Option<Point3D>,
+    // Euler angles in degrees, applied around x, then y, then z.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<Point3D>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<Point3D>,
+    // Row-major affine transform, the last row should be 0 0 0 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<[[f64; 4]; 4]>,
+}
+
+impl Placement {
+    pub fn matrix(&self) -> Result<Matrix4, String> {
+        if let Some(m) = self.matrix {
+            if self.translate.is_some() || self.rotate.is_some() || self.scale.is_some() {
+                return Err("use either matrix or translate/rotate/scale, not both".to_string());
+            }
+            return Ok(Matrix4::new(m));
+        }
+        let mut m = Matrix4::identity();
+        if let Some(t) = self.translate {
+            m = m * Matrix4::translation(t);
+        }
+        if let Some(r) = self.rotate {
+            m = m
+                * Matrix4::rotation_axis(Point3D::new(0.0, 0.0, 1.0), r.z())
+                * Matrix4::rotation_axis(Point3D::new(0.0, 1.0, 0.0), r.y())
+                * Matrix4::rotation_axis(Point3D::new(1.0, 0.0, 0.0), r.x());
+        }
+        if let Some(s) = self.scale {
+            m = m * Matrix4::scale(s);
+        }
+        Ok(m)
+    }
+}
+
+// Object to world transform together with the inverses needed to move rays
+// into object space and normals back out.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Affine {
+    to_world: Matrix4,
+    to_object: Matrix4,
+    normal_to_world: Matrix4,
+}
+
+impl Affine {
+    pub(crate) fn new(to_world: Matrix4) -> Result<Affine, String> {
+        let to_object = to_world
+            .inverse()
+            .ok_or_else(|| "transform is not invertible".to_string())?;
+        Ok(Affine {
+            to_world,
+            to_object,
+            normal_to_world: to_object.transpose(),
+        })
+    }
+
+    // The object space ray is not normalized, so hits keep the ray
+    // parameter of the world space ray.
+    pub(crate) fn hit<'a>(
+        &self,
+        body: &'a Body,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord<'a>> {
+        let local = Ray::with_time(
+            self.to_object.transform_point(ray.origin),
+            self.to_object.transform_vector(ray.direction),
+            ray.time,
+        );
+        let mut hit = body.hit(&local, t_min, t_max)?;
+        hit.point = ray.at(hit.t);
+        hit.normal = self
+            .normal_to_world
+            .transform_vector(hit.normal)
+            .unit_vector();
+        Some(hit)
+    }
+
+    pub(crate) fn bounding_box(&self, body: &Body) -> Option<Aabb> {
+        let b = body.bounding_box()?;
+        let corners = (0..8).map(|i| {
+            self.to_world.transform_point(Point3D::new(
+                if i & 1 == 0 { b.min.x() } else { b.max.x() },
+                if i & 2 == 0 { b.min.y() } else { b.max.y() },
+                if i & 4 == 0 { b.min.z() } else { b.max.z() },
+            ))
+        });
+        corners
+            .map(|c| Aabb::new(c, c))
+            .reduce(|a, b| a.surrounding(&b))
+    }
+}
+
+// Moves, rotates and scales the wrapped body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "TransformParams")]
+pub struct Transform {
+    #[serde(flatten)]
+    pub placement: Placement,
+    pub body: Box<Body>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion: Option<Motion>,
+    #[serde(skip_serializing)]
+    affine: Affine,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransformParams {
+    #[serde(flatten)]
+    pub placement: Placement,
+    pub body: Box<Body>,
+    #[serde(default)]
+    pub motion: Option<Motion>,
+}
+
+impl TryFrom<TransformParams> for Transform {
+    type Error = String;
+
+    fn try_from(p: TransformParams) -> Result<Self, Self::Error> {
+        let mut transform = Transform::new(p.placement, *p.body)?;
+        transform.motion = p.motion;
+        Ok(transform)
+    }
+}
+
+impl Transform {
+    pub fn new(placement: Placement, body: Body) -> Result<Transform, String> {
+        let affine = Affine::new(placement.matrix()?)?;
+        Ok(Transform {
+            placement,
+            body: Box::new(body),
+            motion: None,
+            affine,
+        })
+    }
+
+    // Moves the body somewhere else, e.g. for the next frame of an
+    // animation.
+    pub fn set_placement(&mut self, placement: Placement) -> Result<(), String> {
+        self.affine = Affine::new(placement.matrix()?)?;
+        self.placement = placement;
+        Ok(())
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let motion = match &self.motion {
+            Some(m) => m,
+            None => return self.affine.hit(&self.body, ray, t_min, t_max),
+        };
+        // moving the body is moving the ray the other way
+        let offset = motion.offset(ray.time);
+        let moved = Ray::with_time(ray.origin - offset, ray.direction, ray.time);
+        let mut hit = self.affine.hit(&self.body, &moved, t_min, t_max)?;
+        hit.point = ray.at(hit.t);
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let b = self.affine.bounding_box(&self.body)?;
+        Some(match &self.motion {
+            Some(m) => m.bounding_box(b),
+            None => b,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Cuboid, Ellipsoid};
+    use crate::materials::{Lambertian, Material};
+    use assert_approx_eq::assert_approx_eq;
+    use palette::Srgb;
+
+    fn material() -> Material {
+        Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn test_transform_hit() {
+        // unit box rotated 45 degrees around y and moved to z = -5
+        let placement = Placement {
+            translate: Some(Point3D::new(0.0, 0.0, -5.0)),
+            rotate: Some(Point3D::new(0.0, 45.0, 0.0)),
+            ..Default::default()
+        };
+        let cube = Cuboid::new(
+            Point3D::new(-1.0, -1.0, -1.0),
+            Point3D::new(1.0, 1.0, 1.0),
+            material(),
+        );
+        let transform = Transform::new(placement, Body::Box(cube)).unwrap();
+        let ray = Ray::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(0.0, 0.0, -1.0));
+        let hit = transform.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 5.0 - 2.0f64.sqrt());
+        assert!(hit.front_face);
+        assert_approx_eq!(hit.point.z(), -5.0 + 2.0f64.sqrt());
+        assert_approx_eq!(hit.normal.length(), 1.0);
+        assert_approx_eq!(hit.normal.y(), 0.0);
+
+        let bounds = transform.bounding_box().unwrap();
+        assert_approx_eq!(bounds.max.x(), 2.0f64.sqrt());
+        assert_approx_eq!(bounds.min.z(), -5.0 - 2.0f64.sqrt());
+    }
+
+    #[test]
+    fn test_rotated_ellipsoid() {
+        // a long ellipsoid along x, turned to lie along z
+        let json = "{\"type\":\"Transform\",\"rotate\":{\"x\":0.0,\"y\":90.0,\"z\":0.0},\"body\":{\"type\":\"Ellipsoid\",\"center\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"radii\":{\"x\":3.0,\"y\":1.0,\"z\":1.0},\"material\":{\"Lambertian\":{\"albedo\":[0.5,0.5,0.5]}}}}";
+        let body = serde_json::from_str::<Body>(json).unwrap();
+        let ray = Ray::new(Point3D::new(0.0, 0.0, 10.0), Point3D::new(0.0, 0.0, -1.0));
+        let hit = body.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_approx_eq!(hit.t, 7.0);
+        assert_approx_eq!(hit.normal.z(), 1.0);
+        assert_eq!(serde_json::to_string(&body).unwrap(), json);
+
+        let scaled = Transform::new(
+            Placement {
+                scale: Some(Point3D::new(2.0, 1.0, 1.0)),
+                ..Default::default()
+            },
+            Body::Ellipsoid(Ellipsoid::new(
+                Point3D::new(0.0, 0.0, 0.0),
+                Point3D::new(1.0, 1.0, 1.0),
+                material(),
+            )),
+        )
+        .unwrap();
+        let ray = Ray::new(Point3D::new(10.0, 0.0, 0.0), Point3D::new(-1.0, 0.0, 0.0));
+        assert_approx_eq!(scaled.hit(&ray, 0.0, f64::INFINITY).unwrap().t, 8.0);
+
+        let singular = "{\"type\":\"Transform\",\"scale\":{\"x\":0.0,\"y\":1.0,\"z\":1.0},\"body\":{\"type\":\"Ellipsoid\",\"center\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"radii\":{\"x\":3.0,\"y\":1.0,\"z\":1.0},\"material\":{\"Lambertian\":{\"albedo\":[0.5,0.5,0.5]}}}}";
+        assert!(serde_json::from_str::<Body>(singular).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use palette::Srgb;
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Triangle {
+    pub vertices: [Point3D; 3],
+    // Per-vertex normals for smooth shading. The geometric normal is used
+    // when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normals: Option<[Point3D; 3]>,
+    // Per-vertex texture coordinates. Defaults to the barycentric
+    // coordinates of the hit point.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uvs: Option<[[f64; 2]; 3]>,
+    pub material: Material,
+}
+
+impl Triangle {
+    pub fn new(v0: Point3D, v1: Point3D, v2: Point3D, material: Material) -> Triangle {
+        Triangle {
+            vertices: [v0, v1, v2],
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+}
+
+// Möller-Trumbore intersection. Returns the ray parameter and the
+// barycentric coordinates of the hit with respect to v1 and v2.
+pub(crate) fn intersect_triangle(
+    ray: &Ray,
+    v0: Point3D,
+    v1: Point3D,
+    v2: Point3D,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let pvec = ray.direction.cross(edge2);
+    let det = edge1.dot(pvec);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = ray.origin - v0;
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = tvec.cross(edge1);
+    let b2 = ray.direction.dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(qvec) * inv_det;
+    if t < t_max && t > t_min {
+        Some((t, b1, b2))
+    } else {
+        None
+    }
+}
+
+pub(crate) fn interpolate<T>(values: [T; 3], b1: f64, b2: f64) -> T
+where
+    T: std::ops::Mul<f64, Output = T> + std::ops::Add<Output = T> + Copy,
+{
+    values[0] * (1.0 - b1 - b2) + values[1] * b1 + values[2] * b2
+}
+
+// Builds the hit record for a triangle hit. `front_face` is decided by the
+// geometric normal; the (possibly interpolated) shading normal is flipped
+// to the same side.
+pub(crate) fn triangle_hit_record<'material>(
+    ray: &Ray,
+    t: f64,
+    geometric_normal: Point3D,
+    shading_normal: Point3D,
+    (u, v): (f64, f64),
+    color: Option<Srgb>,
+    material: &'material Material,
+) -> HitRecord<'material> {
+    let front_face = ray.direction.dot(geometric_normal) < 0.0;
+    let normal = if shading_normal.dot(geometric_normal) < 0.0 {
+        -shading_normal
+    } else {
+        shading_normal
+    };
+    HitRecord {
+        t,
+        point: ray.at(t),
+        normal: if front_face { normal } else { -normal },
+        front_face,
+        material,
+        u,
+        v,
+        color,
+        emitted: None,
+    }
+}
+
+pub(crate) fn triangle_bounding_box(v0: Point3D, v1: Point3D, v2: Point3D) -> Aabb {
+    // pad flat boxes so axis-aligned triangles still get hit by the slab test
+    let padding = Point3D::new(1e-6, 1e-6, 1e-6);
+    Aabb::new(v0.min(v1).min(v2) - padding, v0.max(v1).max(v2) + padding)
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let [v0, v1, v2] = self.vertices;
+        let (t, b1, b2) = intersect_triangle(ray, v0, v1, v2, t_min, t_max)?;
+        let geometric_normal = (v1 - v0).cross(v2 - v0).unit_vector();
+        let shading_normal = match self.normals {
+            Some(normals) => interpolate(normals, b1, b2).unit_vector(),
+            None => geometric_normal,
+        };
+        let uv = match self.uvs {
+            Some(uvs) => {
+                let uv = interpolate(uvs.map(|[u, v]| Point3D::new(u, v, 0.0)), b1, b2);
+                (uv.x(), uv.y())
+            }
+            None => (b1, b2),
+        };
+        Some(triangle_hit_record(
+            ray,
+            t,
+            geometric_normal,
+            shading_normal,
+            uv,
+            None,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [v0, v1, v2] = self.vertices;
+        Some(triangle_bounding_box(v0, v1, v2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+
+    fn make_triangle() -> Triangle {
+        Triangle::new(
+            Point3D::new(-1.0, -1.0, 0.0),
+            Point3D::new(1.0, -1.0, 0.0),
+            Point3D::new(0.0, 1.0, 0.0),
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn test_triangle_hit() {
+        let triangle = make_triangle();
+        let ray = Ray::new(Point3D::new(0.0, 0.0, -5.0), Point3D::new(0.0, 0.0, 1.0));
+        let hit = triangle.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 5.0);
+        assert!(!hit.front_face);
+        assert_eq!(hit.normal, Point3D::new(0.0, 0.0, -1.0));
+
+        let miss = Ray::new(Point3D::new(2.0, 0.0, -5.0), Point3D::new(0.0, 0.0, 1.0));
+        assert!(triangle.hit(&miss, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_to_json() {
+        let triangle = make_triangle();
+        let serialized = serde_json::to_string(&triangle).unwrap();
+        assert_eq!(
+            "{\"vertices\":[{\"x\":-1.0,\"y\":-1.0,\"z\":0.0},{\"x\":1.0,\"y\":-1.0,\"z\":0.0},{\"x\":0.0,\"y\":1.0,\"z\":0.0}],\"material\":{\"Lambertian\":{\"albedo\":[0.5,0.5,0.5]}}}",
+            serialized
+        );
+        let t = serde_json::from_str::<Triangle>(&serialized).unwrap();
+        assert_eq!(t.vertices, triangle.vertices);
+    }
+}
@@ -0,0 +1,382 @@
+use palette::Srgb;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::aabb::Aabb;
+use crate::loaders::grid::{load_grid, Grid};
+use crate::materials::Material;
+use crate::point3d::Point3D;
+use crate::ray::HitRecord;
+use crate::ray::Hittable;
+use crate::ray::Ray;
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+// A voxel grid stored in a .npy file, or in a raw file of little-endian
+// 32 bit floats if `resolution` is given. Values are multiplied by `scale`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "GridFileParams")]
+pub struct GridFile {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<[usize; 3]>,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(skip_serializing)]
+    pub grid: Grid, // Note, loaded from `path`.
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GridFileParams {
+    pub path: String,
+    #[serde(default)]
+    pub resolution: Option<[usize; 3]>,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+}
+
+impl TryFrom<GridFileParams> for GridFile {
+    type Error = String;
+
+    fn try_from(p: GridFileParams) -> Result<Self, Self::Error> {
+        let grid = load_grid(Path::new(&p.path), p.resolution).map_err(|e| format!("{:#}", e))?;
+        Ok(GridFile {
+            path: p.path,
+            resolution: p.resolution,
+            scale: p.scale,
+            grid,
+        })
+    }
+}
+
+impl GridFile {
+    fn sample(&self, p: Point3D) -> f64 {
+        self.grid.sample(p) * self.scale
+    }
+}
+
+// Color of a black body at `kelvin`, scaled so that the red channel is 1
+// at 1500 K.
+pub fn blackbody(kelvin: f64) -> Srgb {
+    if kelvin <= 0.0 {
+        return Srgb::new(0.0, 0.0, 0.0);
+    }
+    // Planck's law, up to a constant factor, at a wavelength typical for
+    // each channel
+    let planck = |nm: f64, kelvin: f64| {
+        let meters = nm * 1e-9;
+        1.0 / (meters.powi(5) * ((1.4388e-2 / (meters * kelvin)).exp() - 1.0))
+    };
+    let reference = planck(610.0, 1500.0);
+    let channel = |nm: f64| (planck(nm, kelvin) / reference) as f32;
+    Srgb::new(channel(610.0), channel(550.0), channel(465.0))
+}
+
+// Smoke, clouds or fire with a density that varies over a voxel grid
+// spanning the box from `min` to `max`. Rays scatter by the phase function
+// `material` at points found by delta tracking. Voxels glow with the
+// `emission` grid times `emission_color` plus black body light for the
+// `temperature` grid (in Kelvin) times `blackbody_intensity`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "VolumeParams")]
+pub struct Volume {
+    pub min: Point3D,
+    pub max: Point3D,
+    pub density: GridFile,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emission: Option<GridFile>,
+    #[serde(default = "white")]
+    pub emission_color: [f32; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<GridFile>,
+    #[serde(default = "default_scale")]
+    pub blackbody_intensity: f64,
+    pub material: Material,
+    #[serde(skip_serializing)]
+    majorant: f64,
+}
+
+fn white() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VolumeParams {
+    pub min: Point3D,
+    pub max: Point3D,
+    pub density: GridFile,
+    #[serde(default)]
+    pub emission: Option<GridFile>,
+    #[serde(default = "white")]
+    pub emission_color: [f32; 3],
+    #[serde(default)]
+    pub temperature: Option<GridFile>,
+    #[serde(default = "default_scale")]
+    pub blackbody_intensity: f64,
+    pub material: Material,
+}
+
+impl TryFrom<VolumeParams> for Volume {
+    type Error = String;
+
+    fn try_from(p: VolumeParams) -> Result<Self, Self::Error> {
+        let mut volume = Volume::new(p.min, p.max, p.density, p.material)?;
+        volume.emission = p.emission;
+        volume.emission_color = p.emission_color;
+        volume.temperature = p.temperature;
+        volume.blackbody_intensity = p.blackbody_intensity;
+        Ok(volume)
+    }
+}
+
+impl Volume {
+    pub fn new(
+        min: Point3D,
+        max: Point3D,
+        density: GridFile,
+        material: Material,
+    ) -> Result<Volume, String> {
+        if !matches!(
+            material,
+            Material::Isotropic(_) | Material::HenyeyGreenstein(_)
+        ) {
+            return Err("Volume needs an Isotropic or HenyeyGreenstein material".to_string());
+        }
+        let size = max - min;
+        if (0..3).any(|axis| !(size[axis] > 0.0 && size[axis].is_finite())) {
+            return Err(format!(
+                "Volume min {:?} is not below max {:?} on every axis",
+                min, max
+            ));
+        }
+        if density.grid.values.iter().any(|&v| v.is_nan() || v < 0.0)
+            || !(density.scale >= 0.0 && density.scale.is_finite())
+        {
+            return Err(format!("{}: negative density", density.path));
+        }
+        // densities are interpolated between voxels, so the largest voxel
+        // bounds the density everywhere
+        let majorant = density.grid.max() * density.scale;
+        Ok(Volume {
+            min,
+            max,
+            density,
+            emission: None,
+            emission_color: white(),
+            temperature: None,
+            blackbody_intensity: 1.0,
+            material,
+            majorant,
+        })
+    }
+
+    // Position in the unit cube spanned by the grids.
+    fn grid_point(&self, p: Point3D) -> Point3D {
+        (p - self.min) / (self.max - self.min)
+    }
+
+    fn density_at(&self, p: Point3D) -> f64 {
+        self.density.sample(self.grid_point(p))
+    }
+
+    fn emitted_at(&self, p: Point3D) -> Option<Srgb> {
+        if self.emission.is_none() && self.temperature.is_none() {
+            return None;
+        }
+        let q = self.grid_point(p);
+        let mut color = Srgb::new(0.0, 0.0, 0.0);
+        if let Some(emission) = &self.emission {
+            let e = emission.sample(q) as f32;
+            let [r, g, b] = self.emission_color;
+            color = Srgb::new(r * e, g * e, b * e);
+        }
+        if let Some(temperature) = &self.temperature {
+            let glow = blackbody(temperature.sample(q));
+            let intensity = self.blackbody_intensity as f32;
+            color = Srgb::new(
+                color.red + intensity * glow.red,
+                color.green + intensity * glow.green,
+                color.blue + intensity * glow.blue,
+            );
+        }
+        Some(color)
+    }
+
+    // Walks the ray through the box with steps drawn for the majorant, the
+    // largest density in the grid, calling `collide` at every tentative
+    // collision until it returns true. Returns the t of that collision.
+    fn track(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        mut collide: impl FnMut(Point3D) -> bool,
+    ) -> Option<f64> {
+        if self.majorant <= 0.0 {
+            return None;
+        }
+        let inv_direction = Point3D::new(1.0, 1.0, 1.0) / ray.direction;
+        let (t0, t1) = self
+            .bounding_box()?
+            .interval(ray, inv_direction, t_min, t_max)?;
+        let rate = self.majorant * ray.direction.length();
+        let mut rng = rand::thread_rng();
+        let mut t = t0;
+        loop {
+            t -= (1.0 - rng.gen::<f64>()).ln() / rate;
+            if t >= t1 {
+                return None;
+            }
+            if collide(ray.at(t)) {
+                return Some(t);
+            }
+        }
+    }
+
+    // Delta tracking: a tentative collision is real with probability
+    // density / majorant, otherwise the ray carries on.
+    fn scatter_t(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        let mut rng = rand::thread_rng();
+        self.track(ray, t_min, t_max, |p| {
+            rng.gen::<f64>() * self.majorant < self.density_at(p)
+        })
+    }
+
+    // Ratio tracking: an estimate of the fraction of light that gets
+    // through, with less noise than counting delta tracking hits.
+    pub fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut transmittance = 1.0;
+        self.track(ray, t_min, t_max, |p| {
+            transmittance *= 1.0 - self.density_at(p) / self.majorant;
+            false
+        });
+        transmittance
+    }
+}
+
+impl Hittable for Volume {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = self.scatter_t(ray, t_min, t_max)?;
+        let point = ray.at(t);
+        // there is no surface, the normal is arbitrary
+        Some(HitRecord {
+            t,
+            point,
+            normal: Point3D::new(1.0, 0.0, 0.0),
+            front_face: true,
+            material: &self.material,
+            u: 0.0,
+            v: 0.0,
+            color: None,
+            emitted: self.emitted_at(point),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Isotropic;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn grid_file(resolution: [usize; 3], values: Vec<f32>, scale: f64) -> GridFile {
+        GridFile {
+            path: "test".to_string(),
+            resolution: Some(resolution),
+            scale,
+            grid: Grid::new(resolution, values).unwrap(),
+        }
+    }
+
+    // A 4 unit cube of density 0.5 with one denser voxel in the far corner,
+    // so the majorant is larger than the density along the test rays.
+    fn cloud() -> Volume {
+        let mut values = vec![0.5; 64];
+        values[63] = 1.0;
+        Volume::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(4.0, 4.0, 4.0),
+            grid_file([4, 4, 4], values, 1.0),
+            Material::Isotropic(Isotropic::new(Srgb::new(0.8, 0.8, 0.8))),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tracking() {
+        let cloud = cloud();
+        let ray = Ray::new(Point3D::new(-1.0, 0.5, 0.5), Point3D::new(2.0, 0.0, 0.0));
+        let n = 20000;
+        let expected = (-0.5f64 * 4.0).exp();
+        let transmittance: f64 = (0..n)
+            .map(|_| cloud.transmittance(&ray, 0.0, f64::INFINITY))
+            .sum::<f64>()
+            / n as f64;
+        assert!((transmittance - expected).abs() < 0.01);
+
+        let passed = (0..n)
+            .filter(|_| cloud.hit(&ray, 0.0, f64::INFINITY).is_none())
+            .count();
+        assert!((passed as f64 / n as f64 - expected).abs() < 0.02);
+
+        let hit = cloud.hit(&ray, 0.0, 1.0);
+        assert!(hit.is_none_or(|h| h.t > 0.5 && h.t < 1.0 && h.emitted.is_none()));
+    }
+
+    #[test]
+    fn test_emission() {
+        let mut fire = cloud();
+        fire.emission = Some(grid_file([1, 1, 1], vec![2.0], 0.5));
+        fire.emission_color = [1.0, 0.5, 0.0];
+        fire.temperature = Some(grid_file([1, 1, 1], vec![1500.0], 1.0));
+        fire.blackbody_intensity = 2.0;
+        let color = fire.emitted_at(Point3D::new(1.0, 1.0, 1.0)).unwrap();
+        assert_approx_eq!(color.red, 3.0);
+        assert!(color.green > 0.5 && color.green < color.red);
+
+        // hotter is bluer
+        let warm = blackbody(1500.0);
+        let hot = blackbody(6500.0);
+        assert!(warm.blue / warm.red < hot.blue / hot.red);
+        assert!(hot.red > 100.0);
+    }
+
+    #[test]
+    fn test_volume_json() {
+        let json = "{\"type\":\"Volume\",\"min\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"max\":{\"x\":1.0,\"y\":1.0,\"z\":1.0},\"density\":{\"path\":\"data/cloud.npy\"},\"material\":{\"Isotropic\":{\"albedo\":[0.9,0.9,0.9]}}}";
+        let body = serde_json::from_str::<crate::body::Body>(json).unwrap();
+        match &body {
+            crate::body::Body::Volume(v) => {
+                assert_eq!(v.density.grid.resolution, [32, 32, 32]);
+                assert!(v.majorant > 0.0);
+            }
+            _ => panic!("expected a Volume"),
+        }
+
+        let flat = json.replace(
+            "\"max\":{\"x\":1.0,\"y\":1.0,\"z\":1.0}",
+            "\"max\":{\"x\":1.0,\"y\":0.0,\"z\":1.0}",
+        );
+        assert!(serde_json::from_str::<crate::body::Body>(&flat).is_err());
+        let mut nan = grid_file([1, 1, 1], vec![1.0], 1.0);
+        nan.grid.values[0] = f32::NAN;
+        assert!(Volume::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(1.0, 1.0, 1.0),
+            nan,
+            Material::Isotropic(Isotropic::new(Srgb::new(0.8, 0.8, 0.8))),
+        )
+        .is_err());
+
+        let missing = json.replace("cloud.npy", "missing.npy");
+        let err = serde_json::from_str::<crate::body::Body>(&missing).unwrap_err();
+        assert!(err.to_string().contains("data/missing.npy"));
+    }
+}
@@ -0,0 +1,329 @@
+use crate::aabb::Aabb;
+use crate::point3d::Point3D;
+use crate::ray::{HitRecord, Hittable, Ray};
+
+// Number of centroid bins evaluated per axis when looking for the cheapest
+// surface area heuristic split.
+const SAH_BINS: usize = 12;
+// Cost of visiting an interior node, relative to one primitive intersection.
+const TRAVERSAL_COST: f64 = 0.125;
+// Leaves larger than this are always split, even if the SAH says otherwise.
+const MAX_LEAF_SIZE: usize = 4;
+
+// Nodes are stored depth first: the left child of an interior node is the
+// next node in the array, `offset` points at the right child. For leaves,
+// `offset`/`count` is a range into `Bvh::indices`.
+#[derive(Debug, Clone)]
+struct BvhNode {
+    bounds: Aabb,
+    offset: usize,
+    count: usize,
+    axis: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Point3D,
+}
+
+// Bounding volume hierarchy over a list of primitives, referenced by index.
+// Primitives without a bounding box (e.g. infinite planes) can't be put into
+// the tree and are tested linearly on every query.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(boxes: &[Option<Aabb>]) -> Bvh {
+        let mut items = Vec::with_capacity(boxes.len());
+        let mut unbounded = Vec::new();
+        for (index, bounds) in boxes.iter().enumerate() {
+            match bounds {
+                Some(bounds) => items.push(BuildItem {
+                    index,
+                    bounds: *bounds,
+                    centroid: bounds.centroid(),
+                }),
+                None => unbounded.push(index),
+            }
+        }
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * items.len()),
+            indices: Vec::with_capacity(items.len()),
+            unbounded,
+        };
+        if !items.is_empty() {
+            bvh.build_recursive(&mut items);
+        }
+        bvh
+    }
+
+    pub fn build<T: Hittable>(objects: &[T]) -> Bvh {
+        let boxes: Vec<Option<Aabb>> = objects.iter().map(|o| o.bounding_box()).collect();
+        Bvh::new(&boxes)
+    }
+
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        if self.unbounded.is_empty() {
+            self.nodes.first().map(|n| n.bounds)
+        } else {
+            None
+        }
+    }
+
+    fn build_recursive(&mut self, items: &mut [BuildItem]) -> usize {
+        let node_index = self.nodes.len();
+        let bounds = items
+            .iter()
+            .skip(1)
+            .fold(items[0].bounds, |acc, item| acc.surrounding(&item.bounds));
+        self.nodes.push(BvhNode {
+            bounds,
+            offset: 0,
+            count: 0,
+            axis: 0,
+        });
+
+        match split_items(items, &bounds) {
+            Some((axis, mid)) => {
+                let (left, right) = items.split_at_mut(mid);
+                self.build_recursive(left);
+                let right_index = self.build_recursive(right);
+                let node = &mut self.nodes[node_index];
+                node.offset = right_index;
+                node.axis = axis;
+            }
+            None => {
+                let node = &mut self.nodes[node_index];
+                node.offset = self.indices.len();
+                node.count = items.len();
+                self.indices.extend(items.iter().map(|item| item.index));
+            }
+        }
+        node_index
+    }
+
+    // Finds the closest hit, asking `hit` to intersect the primitive with the
+    // given index. `hit` receives the current (shrinking) t range.
+    pub fn hit_with<'a, F>(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        mut hit: F,
+    ) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, f64, f64) -> Option<HitRecord<'a>>,
+    {
+        let mut closest_so_far = t_max;
+        let mut hit_record = None;
+        for &index in &self.unbounded {
+            if let Some(h) = hit(index, t_min, closest_so_far) {
+                closest_so_far = h.t;
+                hit_record = Some(h);
+            }
+        }
+        if self.nodes.is_empty() {
+            return hit_record;
+        }
+
+        let inv_direction = Point3D::new(
+            1.0 / ray.direction.x(),
+            1.0 / ray.direction.y(),
+            1.0 / ray.direction.z(),
+        );
+        let direction_negative = [
+            inv_direction.x() < 0.0,
+            inv_direction.y() < 0.0,
+            inv_direction.z() < 0.0,
+        ];
+        let mut stack = Vec::with_capacity(64);
+        let mut node_index = 0;
+        loop {
+            let node = &self.nodes[node_index];
+            if node.bounds.hit(ray, inv_direction, t_min, closest_so_far) {
+                if node.count > 0 {
+                    for &index in &self.indices[node.offset..node.offset + node.count] {
+                        if let Some(h) = hit(index, t_min, closest_so_far) {
+                            closest_so_far = h.t;
+                            hit_record = Some(h);
+                        }
+                    }
+                } else {
+                    // visit the child nearer to the ray origin first
+                    if direction_negative[node.axis] {
+                        stack.push(node_index + 1);
+                        node_index = node.offset;
+                    } else {
+                        stack.push(node.offset);
+                        node_index += 1;
+                    }
+                    continue;
+                }
+            }
+            match stack.pop() {
+                Some(next) => node_index = next,
+                None => break,
+            }
+        }
+        hit_record
+    }
+
+    pub fn hit<'a, T: Hittable>(
+        &self,
+        objects: &'a [T],
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord<'a>> {
+        self.hit_with(ray, t_min, t_max, |index, t_min, t_max| {
+            objects[index].hit(ray, t_min, t_max)
+        })
+    }
+}
+
+// Picks the split with the lowest SAH cost and partitions `items` around it.
+// Returns None when the items should become a leaf.
+fn split_items(items: &mut [BuildItem], bounds: &Aabb) -> Option<(usize, usize)> {
+    if items.len() == 1 {
+        return None;
+    }
+    let centroid_bounds = items.iter().skip(1).fold(
+        Aabb::new(items[0].centroid, items[0].centroid),
+        |acc, item| acc.surrounding(&Aabb::new(item.centroid, item.centroid)),
+    );
+
+    let mut best: Option<(f64, usize, usize)> = None;
+    for axis in 0..3 {
+        let extent = centroid_bounds.max[axis] - centroid_bounds.min[axis];
+        if extent <= 0.0 {
+            continue;
+        }
+        let mut counts = [0usize; SAH_BINS];
+        let mut bin_bounds: [Option<Aabb>; SAH_BINS] = [None; SAH_BINS];
+        for item in items.iter() {
+            let b = bin_index(item.centroid[axis], centroid_bounds.min[axis], extent);
+            counts[b] += 1;
+            bin_bounds[b] = Some(match bin_bounds[b] {
+                Some(bb) => bb.surrounding(&item.bounds),
+                None => item.bounds,
+            });
+        }
+        for split in 0..SAH_BINS - 1 {
+            let (left_count, left_area) = bins_summary(&counts[..=split], &bin_bounds[..=split]);
+            let (right_count, right_area) =
+                bins_summary(&counts[split + 1..], &bin_bounds[split + 1..]);
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + (left_count as f64 * left_area + right_count as f64 * right_area)
+                    / bounds.surface_area();
+            match best {
+                Some((best_cost, _, _)) if best_cost <= cost => {}
+                _ => best = Some((cost, axis, split)),
+            }
+        }
+    }
+
+    match best {
+        Some((cost, axis, split)) if cost < items.len() as f64 || items.len() > MAX_LEAF_SIZE => {
+            let extent = centroid_bounds.max[axis] - centroid_bounds.min[axis];
+            let min = centroid_bounds.min[axis];
+            let mid = partition(items, |item| {
+                bin_index(item.centroid[axis], min, extent) <= split
+            });
+            Some((axis, mid))
+        }
+        Some(_) => None,
+        None if items.len() > MAX_LEAF_SIZE => {
+            // all centroids coincide, SAH can't separate them; split in the
+            // middle so leaves stay small
+            Some((bounds.longest_axis(), items.len() / 2))
+        }
+        None => None,
+    }
+}
+
+fn bin_index(value: f64, min: f64, extent: f64) -> usize {
+    (((value - min) / extent * SAH_BINS as f64) as usize).min(SAH_BINS - 1)
+}
+
+fn bins_summary(counts: &[usize], bounds: &[Option<Aabb>]) -> (usize, f64) {
+    let count = counts.iter().sum();
+    let area = bounds
+        .iter()
+        .flatten()
+        .fold(None, |acc: Option<Aabb>, b| {
+            Some(acc.map_or(*b, |a| a.surrounding(b)))
+        })
+        .map_or(0.0, |b| b.surface_area());
+    (count, area)
+}
+
+fn partition<F: Fn(&BuildItem) -> bool>(items: &mut [BuildItem], pred: F) -> usize {
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Sphere;
+    use crate::materials::{Lambertian, Material};
+    use palette::Srgb;
+    use rand::Rng;
+
+    fn brute_force<'a>(world: &'a [Sphere], ray: &Ray) -> Option<HitRecord<'a>> {
+        let mut closest_so_far = f64::INFINITY;
+        let mut hit_record = None;
+        for sphere in world {
+            if let Some(hit) = sphere.hit(ray, 0.001, closest_so_far) {
+                closest_so_far = hit.t;
+                hit_record = Some(hit);
+            }
+        }
+        hit_record
+    }
+
+    #[test]
+    fn test_bvh_matches_brute_force() {
+        let mut rng = rand::thread_rng();
+        let world: Vec<Sphere> = (0..200)
+            .map(|_| {
+                Sphere::new(
+                    Point3D::random(-10.0, 10.0),
+                    rng.gen_range(0.1..1.0),
+                    Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+                )
+            })
+            .collect();
+        let bvh = Bvh::build(&world);
+        for _ in 0..1000 {
+            let ray = Ray::new(Point3D::random(-12.0, 12.0), Point3D::random(-1.0, 1.0));
+            let expected = brute_force(&world, &ray).map(|h| h.t);
+            let actual = bvh.hit(&world, &ray, 0.001, f64::INFINITY).map(|h| h.t);
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_bvh_empty() {
+        let world: Vec<Sphere> = Vec::new();
+        let bvh = Bvh::build(&world);
+        let ray = Ray::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(0.0, 0.0, 1.0));
+        assert!(bvh.hit(&world, &ray, 0.001, f64::INFINITY).is_none());
+        assert!(bvh.bounding_box().is_none());
+    }
+}
@@ -1,13 +1,63 @@
+use common::warn;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+use crate::onb::Onb;
 use crate::point3d::Point3D;
 use crate::ray::Ray;
 
 #[cfg(test)]
 use assert_approx_eq::assert_approx_eq;
 
+// How directions around the camera map to the image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    // Parallel rays, the image shows what the perspective view shows at
+    // look_at at any distance.
+    Orthographic,
+    // Equidistant fisheye, the angle from the view direction grows evenly
+    // towards the edges and `vfov` (up to 360) spans the image height.
+    Fisheye,
+    // Every direction, longitude across and latitude up the image, for
+    // 360 degree panoramas. Images should be twice as wide as high.
+    Equirectangular,
+}
+
+impl Projection {
+    fn is_perspective(&self) -> bool {
+        *self == Projection::Perspective
+    }
+}
+
+// How the two eyes of a stereo image are packed into one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StereoLayout {
+    SideBySide, // left eye on the left, the image is twice as wide
+    OverUnder,  // left eye on top, the image is twice as high
+}
+
+fn default_eye_separation() -> f64 {
+    0.064
+}
+
+// Renders the view of a left and a right eye `eye_separation` apart, each
+// at the size of the scene. Both eyes look at the same point
+// `convergence` away (by default as far as look_at). With the
+// equirectangular projection this is an omni-directional stereo panorama,
+// the eyes turn with every direction.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Stereo {
+    pub layout: StereoLayout,
+    #[serde(default = "default_eye_separation")]
+    pub eye_separation: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub convergence: Option<f64>,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(from = "CameraParams")]
+#[serde(try_from = "CameraParams")]
 pub struct Camera {
     #[serde(skip_serializing)]
     pub origin: Point3D, // Note, don't serialize any of the computed fields.
@@ -19,25 +69,153 @@ pub struct Camera {
     pub horizontal: Point3D,
     #[serde(skip_serializing)]
     pub vertical: Point3D,
+    #[serde(skip_serializing)]
+    onb: Onb, // u to the right, v up and w backwards
+    #[serde(skip_serializing)]
+    eye_offset: f64, // along u, for one eye of a stereo pair
+    #[serde(skip_serializing)]
+    view_vfov: f64, // from whichever field of view was given
+    #[serde(skip_serializing)]
+    view_aspect: f64, // of the image being rendered
     look_from: Point3D,
     look_at: Point3D,
     vup: Point3D,
-    vfov: f64, // vertical field-of-view in degrees
-    aspect: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vfov: Option<f64>, // vertical field-of-view in degrees
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hfov: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    focal_length_mm: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aspect: Option<f64>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    roll: f64,
+    #[serde(default, skip_serializing_if = "Projection::is_perspective")]
+    pub projection: Projection,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub aperture: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_distance: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blades: Option<usize>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub shutter_open: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub shutter_close: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stereo: Option<Stereo>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct CameraParams {
     pub look_from: Point3D,
     pub look_at: Point3D,
     pub vup: Point3D,
-    pub vfov: f64, // vertical field-of-view in degrees
-    pub aspect: f64,
+    // The field of view is given by one of `vfov` (vertical, in degrees),
+    // `hfov` (horizontal, in degrees) or `focal_length_mm`, the focal
+    // length of a lens on a 35mm full frame camera.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vfov: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hfov: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focal_length_mm: Option<f64>,
+    // Width over height, by default that of the rendered image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aspect: Option<f64>,
+    // Turns the camera counterclockwise around its view direction, in
+    // degrees.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub roll: f64,
+    #[serde(default, skip_serializing_if = "Projection::is_perspective")]
+    pub projection: Projection,
+    // A thin lens `aperture` wide keeps only what is `focus_distance` away
+    // (by default as far as look_at) sharp. With `blades` the aperture is a
+    // polygon rather than round, which shapes out of focus highlights. The
+    // lens only applies to the perspective projection.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub aperture: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_distance: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blades: Option<usize>,
+    // Rays are sent at random times while the shutter is open, so moving
+    // bodies are blurred.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub shutter_open: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub shutter_close: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stereo: Option<Stereo>,
 }
 
-impl From<CameraParams> for Camera {
-    fn from(p: CameraParams) -> Self {
-        Camera::new(p.look_from, p.look_at, p.vup, p.vfov, p.aspect)
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+impl TryFrom<CameraParams> for Camera {
+    type Error = String;
+
+    fn try_from(p: CameraParams) -> Result<Self, Self::Error> {
+        let fields_of_view = [p.vfov, p.hfov, p.focal_length_mm];
+        if fields_of_view.iter().flatten().count() != 1 {
+            return Err("camera needs exactly one of vfov, hfov or focal_length_mm".to_string());
+        }
+        if let Some(angle) = p.vfov.or(p.hfov).filter(|a| !(*a > 0.0 && *a < 180.0)) {
+            return Err(format!(
+                "camera field of view must lie between 0 and 180 degrees, not {}",
+                angle
+            ));
+        }
+        if p.focal_length_mm
+            .is_some_and(|mm| !(mm > 0.0 && mm.is_finite()))
+        {
+            return Err(format!(
+                "camera focal_length_mm must be positive, not {:?}",
+                p.focal_length_mm
+            ));
+        }
+        if p.aspect.is_some_and(|a| a.is_nan() || a <= 0.0) {
+            return Err(format!(
+                "camera aspect must be positive, not {:?}",
+                p.aspect
+            ));
+        }
+        if !(p.aperture >= 0.0 && p.aperture.is_finite()) {
+            return Err(format!(
+                "camera aperture must be 0 or more, not {}",
+                p.aperture
+            ));
+        }
+        if p.focus_distance
+            .is_some_and(|d| !(d > 0.0 && d.is_finite()))
+        {
+            return Err(format!(
+                "camera focus_distance must be positive, not {:?}",
+                p.focus_distance
+            ));
+        }
+        if p.blades.is_some_and(|b| b < 3) {
+            return Err(format!(
+                "a camera aperture needs at least 3 blades, not {:?}",
+                p.blades
+            ));
+        }
+        let camera = Camera {
+            vfov: p.vfov,
+            hfov: p.hfov,
+            focal_length_mm: p.focal_length_mm,
+            aspect: p.aspect,
+            roll: p.roll,
+            ..Camera::new(p.look_from, p.look_at, p.vup, 90.0, 1.0)
+        };
+        Ok(camera
+            .frame(p.aspect.unwrap_or(1.0))
+            .with_projection(p.projection)
+            .with_lens(p.aperture, p.focus_distance, p.blades)
+            .with_shutter(p.shutter_open, p.shutter_close)
+            .with_stereo(p.stereo))
     }
 }
 
@@ -49,15 +227,59 @@ impl Camera {
         vfov: f64, // vertical field-of-view in degrees
         aspect: f64,
     ) -> Camera {
+        let camera = Camera {
+            origin: look_from,
+            lower_left_corner: look_from,
+            focal_length: 0.0,
+            horizontal: look_from,
+            vertical: look_from,
+            onb: Onb::from_w(look_from - look_at),
+            eye_offset: 0.0,
+            view_vfov: vfov,
+            view_aspect: aspect,
+            look_from,
+            look_at,
+            vup,
+            vfov: Some(vfov),
+            hfov: None,
+            focal_length_mm: None,
+            aspect: Some(aspect),
+            roll: 0.0,
+            projection: Projection::Perspective,
+            aperture: 0.0,
+            focus_distance: None,
+            blades: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            stereo: None,
+        };
+        camera.frame(aspect)
+    }
+
+    // Sets up the view for an image with the given aspect ratio.
+    fn frame(self, aspect: f64) -> Camera {
+        let vfov = match (self.vfov, self.hfov, self.focal_length_mm) {
+            (Some(vfov), _, _) => vfov,
+            (_, Some(hfov), _) => {
+                2.0 * ((hfov.to_radians() / 2.0).tan() / aspect)
+                    .atan()
+                    .to_degrees()
+            }
+            // a full frame sensor is 24mm high
+            (_, _, Some(mm)) => 2.0 * (12.0 / mm).atan().to_degrees(),
+            _ => self.view_vfov,
+        };
         let theta = vfov.to_radians();
         let half_height = (theta / 2.0).tan();
         let half_width = aspect * half_height;
 
-        let w = (look_from - look_at).unit_vector();
-        let u = vup.cross(w).unit_vector();
+        let w = (self.look_from - self.look_at).unit_vector();
+        let u = self.vup.cross(w).unit_vector();
         let v = w.cross(u);
+        let (sin, cos) = self.roll.to_radians().sin_cos();
+        let (u, v) = (u * cos + v * sin, v * cos - u * sin);
 
-        let origin = look_from;
+        let origin = self.look_from;
         let lower_left_corner = origin - (u * half_width) - (v * half_height) - w;
         let horizontal = u * 2.0 * half_width;
         let vertical = v * 2.0 * half_height;
@@ -65,22 +287,209 @@ impl Camera {
         Camera {
             origin,
             lower_left_corner,
-            focal_length: (look_from - look_at).length(),
+            focal_length: (self.look_from - self.look_at).length(),
             horizontal,
             vertical,
-            look_from,
-            look_at,
-            vup,
-            vfov,
-            aspect,
+            onb: Onb { u, v, w },
+            view_vfov: vfov,
+            view_aspect: aspect,
+            ..self
+        }
+    }
+
+    // The camera for rendering an image `width` by `height` pixels. Without
+    // an aspect of its own it takes that of the image, with one that does
+    // not match the image would look stretched.
+    pub fn with_image_size(self, width: usize, height: usize) -> Camera {
+        let image_aspect = width as f64 / height as f64;
+        match self.aspect {
+            Some(aspect) => {
+                if (aspect - image_aspect).abs() > 0.01 * image_aspect {
+                    warn!(
+                        "camera aspect {} does not match the {}x{} image, it will look stretched",
+                        aspect, width, height
+                    );
+                }
+                self
+            }
+            None => self.frame(image_aspect),
+        }
+    }
+
+    pub fn with_projection(self, projection: Projection) -> Camera {
+        Camera { projection, ..self }
+    }
+
+    pub fn with_lens(
+        self,
+        aperture: f64,
+        focus_distance: Option<f64>,
+        blades: Option<usize>,
+    ) -> Camera {
+        Camera {
+            aperture,
+            focus_distance,
+            blades,
+            ..self
         }
     }
 
+    pub fn with_shutter(self, shutter_open: f64, shutter_close: f64) -> Camera {
+        Camera {
+            shutter_open,
+            shutter_close,
+            ..self
+        }
+    }
+
+    pub fn with_stereo(self, stereo: Option<Stereo>) -> Camera {
+        Camera { stereo, ..self }
+    }
+
+    // The camera moved `azimuth` degrees around `look_at`, turning about
+    // `vup`. The distance and the elevation in degrees above the plane at
+    // right angles to `vup` are kept unless given.
+    pub fn orbit(&self, azimuth: f64, radius: Option<f64>, elevation: Option<f64>) -> Camera {
+        let axis = self.vup.unit_vector();
+        let offset = self.look_from - self.look_at;
+        let height = offset.dot(axis);
+        let flat = offset - axis * height;
+        let flat = if flat.length() > 1e-9 {
+            flat
+        } else {
+            // looking straight along vup, any direction across it will do
+            self.onb.w - axis * self.onb.w.dot(axis)
+        };
+        let (sin, cos) = azimuth.to_radians().sin_cos();
+        let across = (flat * cos + axis.cross(flat) * sin).unit_vector();
+        let radius = radius.unwrap_or_else(|| offset.length());
+        let elevation = elevation
+            .map(f64::to_radians)
+            .unwrap_or_else(|| height.atan2(flat.length()));
+        let look_from = self.look_at + (across * elevation.cos() + axis * elevation.sin()) * radius;
+        Camera { look_from, ..*self }.frame(self.view_aspect)
+    }
+
+    // The cameras to render the image with, the left and the right eye
+    // for stereo.
+    pub fn views(&self) -> Vec<Camera> {
+        match self.stereo {
+            Some(stereo) => vec![
+                self.eye(-stereo.eye_separation / 2.0),
+                self.eye(stereo.eye_separation / 2.0),
+            ],
+            None => vec![*self],
+        }
+    }
+
+    fn convergence(&self) -> f64 {
+        self.stereo
+            .and_then(|s| s.convergence)
+            .unwrap_or(self.focal_length)
+    }
+
+    fn eye(&self, offset: f64) -> Camera {
+        let mut eye = Camera {
+            eye_offset: offset,
+            ..*self
+        };
+        // panoramas move the eye for every ray instead
+        if self.projection != Projection::Equirectangular {
+            let shift = self.onb.u * offset;
+            eye.origin = self.origin + shift;
+            // an off-axis frustum, so the views meet at the convergence
+            // distance without turning the eyes inwards
+            eye.lower_left_corner = self.lower_left_corner + shift - shift / self.convergence();
+        }
+        eye
+    }
+
     pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-        Ray::new(
-            self.origin,
-            self.lower_left_corner + (self.horizontal * u) + (self.vertical * v) - self.origin,
-        )
+        let time = if self.shutter_close > self.shutter_open {
+            rand::thread_rng().gen_range(self.shutter_open..self.shutter_close)
+        } else {
+            self.shutter_open
+        };
+        let (x, y) = (u - 0.5, v - 0.5);
+        match self.projection {
+            Projection::Perspective => self.perspective_ray(u, v, time),
+            Projection::Orthographic => {
+                let offset = (self.horizontal * x + self.vertical * y) * self.focal_length;
+                Ray::with_time(self.origin + offset, -self.onb.w, time)
+            }
+            Projection::Fisheye => {
+                let (x, y) = (2.0 * x * self.view_aspect, 2.0 * y);
+                let theta = (x * x + y * y).sqrt() * self.view_vfov.to_radians() / 2.0;
+                let phi = y.atan2(x);
+                let direction = Point3D::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    -theta.cos(),
+                );
+                Ray::with_time(self.origin, self.onb.local(direction), time)
+            }
+            Projection::Equirectangular => {
+                let longitude = x * 2.0 * std::f64::consts::PI;
+                let latitude = y * std::f64::consts::PI;
+                let direction = Point3D::new(
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    -latitude.cos() * longitude.cos(),
+                );
+                let direction = self.onb.local(direction);
+                if self.eye_offset == 0.0 {
+                    return Ray::with_time(self.origin, direction, time);
+                }
+                // the eye is on a circle around the origin, at right angles
+                // to the direction it looks in
+                let side = self
+                    .onb
+                    .local(Point3D::new(longitude.cos(), 0.0, longitude.sin()));
+                let origin = self.origin + side * self.eye_offset;
+                let target = self.origin + direction * self.convergence();
+                Ray::with_time(origin, target - origin, time)
+            }
+        }
+    }
+
+    fn perspective_ray(&self, u: f64, v: f64, time: f64) -> Ray {
+        let direction =
+            self.lower_left_corner + (self.horizontal * u) + (self.vertical * v) - self.origin;
+        if self.aperture <= 0.0 {
+            return Ray::with_time(self.origin, direction, time);
+        }
+        // the direction reaches one unit in front of the camera, stretched
+        // it reaches the plane in focus
+        let focus = self.origin + direction * self.focus_distance.unwrap_or(self.focal_length);
+        let (x, y) = self.sample_aperture();
+        let origin = self.origin + self.onb.local(Point3D::new(x, y, 0.0)) * (self.aperture / 2.0);
+        Ray::with_time(origin, focus - origin, time)
+    }
+
+    // Uniform random point on the aperture, scaled to the unit disk.
+    fn sample_aperture(&self) -> (f64, f64) {
+        let mut rng = rand::thread_rng();
+        match self.blades {
+            Some(blades) if blades >= 3 => {
+                // pick one of the triangles between the center and two
+                // neighbouring corners, then a point inside it
+                let step = 2.0 * std::f64::consts::PI / blades as f64;
+                let corner = rng.gen_range(0..blades) as f64 * step;
+                let (mut a, mut b) = (rng.gen::<f64>(), rng.gen::<f64>());
+                if a + b > 1.0 {
+                    (a, b) = (1.0 - a, 1.0 - b);
+                }
+                (
+                    a * corner.cos() + b * (corner + step).cos(),
+                    a * corner.sin() + b * (corner + step).sin(),
+                )
+            }
+            _ => {
+                let r = rng.gen::<f64>().sqrt();
+                let theta = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+                (r * theta.cos(), r * theta.sin())
+            }
+        }
     }
 }
 
@@ -139,3 +548,213 @@ fn test_to_json() {
     assert_eq!(camera.horizontal, c.horizontal);
     assert_eq!(camera.vertical, c.vertical);
 }
+
+#[test]
+fn test_shutter() {
+    let json = "{\"look_from\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"look_at\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"vup\":{\"x\":0.0,\"y\":1.0,\"z\":0.0},\"vfov\":90.0,\"aspect\":1.0,\"shutter_open\":0.25,\"shutter_close\":0.5}";
+    let camera = serde_json::from_str::<Camera>(json).unwrap();
+    assert_eq!(serde_json::to_string(&camera).unwrap(), json);
+    for _ in 0..100 {
+        let time = camera.get_ray(0.5, 0.5).time;
+        assert!((0.25..0.5).contains(&time));
+    }
+}
+
+#[test]
+fn test_thin_lens() {
+    let json = "{\"look_from\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"look_at\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"vup\":{\"x\":0.0,\"y\":1.0,\"z\":0.0},\"vfov\":90.0,\"aspect\":1.0,\"aperture\":0.5,\"focus_distance\":4.0,\"blades\":6}";
+    let camera = serde_json::from_str::<Camera>(json).unwrap();
+    assert_eq!(serde_json::to_string(&camera).unwrap(), json);
+    let round = camera.with_lens(0.5, None, None);
+    for _ in 0..100 {
+        // rays start on the lens and meet again on the plane in focus
+        let ray = camera.get_ray(0.75, 0.5);
+        assert!(ray.origin.length() <= 0.25 && ray.origin.z() == 0.0);
+        let focus = ray.at(-4.0 / ray.direction.z());
+        assert_approx_eq!(focus.x(), 2.0);
+        assert_approx_eq!(focus.y(), 0.0);
+        // the hexagon fits inside the circle, its flat sides are closer in
+        assert!(ray.origin.y().abs() <= 0.25 * 0.75f64.sqrt() + 1e-12);
+
+        let ray = round.get_ray(0.5, 0.5);
+        assert_approx_eq!(ray.at(1.0 / -ray.direction.z()).x(), 0.0);
+    }
+
+    for (from, to) in [
+        ("\"aperture\":0.5", "\"aperture\":-0.5"),
+        ("\"focus_distance\":4.0", "\"focus_distance\":0.0"),
+        ("\"focus_distance\":4.0", "\"focus_distance\":-4.0"),
+        ("\"blades\":6", "\"blades\":2"),
+    ] {
+        let bad = json.replace(from, to);
+        assert!(serde_json::from_str::<Camera>(&bad).is_err(), "{}", to);
+    }
+}
+
+#[test]
+fn test_projections() {
+    let camera = Camera::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Point3D::new(0.0, 0.0, -2.0),
+        Point3D::new(0.0, 1.0, 0.0),
+        90.0,
+        2.0,
+    );
+    let json = serde_json::to_string(&camera.with_projection(Projection::Fisheye)).unwrap();
+    assert!(json.ends_with("\"aspect\":2.0,\"projection\":\"Fisheye\"}"));
+    let fisheye = serde_json::from_str::<Camera>(&json).unwrap();
+    assert_eq!(fisheye.projection, Projection::Fisheye);
+
+    // parallel rays over the 8 by 4 frame the perspective view has at look_at
+    let orthographic = camera.with_projection(Projection::Orthographic);
+    let ray = orthographic.get_ray(1.0, 0.25);
+    assert_approx_eq!(ray.origin.x(), 4.0);
+    assert_approx_eq!(ray.origin.y(), -1.0);
+    assert_approx_eq!(ray.direction.z(), -1.0);
+
+    // 45 degrees at the top edge, twice as far out at the right edge
+    let ray = fisheye.get_ray(0.5, 1.0);
+    assert_approx_eq!(ray.direction.y(), -ray.direction.z());
+    let ray = fisheye.get_ray(1.0, 0.5);
+    assert_approx_eq!(ray.direction.x(), 1.0);
+
+    let panorama = camera.with_projection(Projection::Equirectangular);
+    let ray = panorama.get_ray(0.5, 0.5);
+    assert_approx_eq!(ray.direction.z(), -1.0);
+    let ray = panorama.get_ray(0.0, 0.5);
+    assert_approx_eq!(ray.direction.z(), 1.0);
+    let ray = panorama.get_ray(0.75, 0.5);
+    assert_approx_eq!(ray.direction.x(), 1.0);
+    let ray = panorama.get_ray(0.3, 1.0);
+    assert_approx_eq!(ray.direction.y(), 1.0);
+}
+
+#[test]
+fn test_stereo() {
+    let camera = Camera::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Point3D::new(0.0, 0.0, -1.0),
+        Point3D::new(0.0, 1.0, 0.0),
+        90.0,
+        1.0,
+    )
+    .with_stereo(Some(Stereo {
+        layout: StereoLayout::SideBySide,
+        eye_separation: 0.5,
+        convergence: Some(4.0),
+    }));
+    assert_eq!(
+        Camera::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 0.0, -1.0),
+            Point3D::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+        )
+        .views()
+        .len(),
+        1
+    );
+    let json = serde_json::to_string(&camera).unwrap();
+    assert!(json.ends_with(
+        "\"stereo\":{\"layout\":\"SideBySide\",\"eye_separation\":0.5,\"convergence\":4.0}}"
+    ));
+
+    // the eyes see the same point at the convergence distance
+    let [left, right] = camera.views()[..] else {
+        panic!("expected two views");
+    };
+    assert_approx_eq!(left.origin.x(), -0.25);
+    assert_approx_eq!(right.origin.x(), 0.25);
+    for (u, v) in [(0.5, 0.5), (0.1, 0.8)] {
+        let l = left.get_ray(u, v);
+        let r = right.get_ray(u, v);
+        let at = |ray: Ray| ray.at(-4.0 / ray.direction.z());
+        assert_approx_eq!(at(l).x(), at(r).x());
+        assert_approx_eq!(at(l).y(), at(r).y());
+    }
+
+    // omni-directional stereo, looking right the right eye is behind
+    let panorama = camera.with_projection(Projection::Equirectangular);
+    let right = panorama.views()[1];
+    let ray = right.get_ray(0.75, 0.5);
+    assert_approx_eq!(ray.origin.z(), 0.25);
+    assert_approx_eq!(ray.at(1.0).x(), 4.0);
+    let ray = right.get_ray(0.5, 0.5);
+    assert_approx_eq!(ray.origin.x(), 0.25);
+}
+
+#[test]
+fn test_field_of_view_and_roll() {
+    let json = "{\"look_from\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"look_at\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"vup\":{\"x\":0.0,\"y\":1.0,\"z\":0.0},\"hfov\":90.0,\"roll\":90.0}";
+    let camera = serde_json::from_str::<Camera>(json).unwrap();
+    assert_eq!(serde_json::to_string(&camera).unwrap(), json);
+
+    // the aspect comes from the image, 90 degrees across it either way
+    let camera = camera.with_image_size(200, 100);
+    assert_approx_eq!(camera.horizontal.length(), 2.0);
+    assert_approx_eq!(camera.vertical.length(), 1.0);
+    // rolled a quarter turn, the right of the image points up
+    assert_approx_eq!(camera.horizontal.y(), 2.0);
+    assert_approx_eq!(camera.vertical.x(), -1.0);
+
+    // a 12mm lens on full frame sees 90 degrees vertically
+    let json = json.replace("\"hfov\":90.0,\"roll\":90.0", "\"focal_length_mm\":12.0");
+    let camera = serde_json::from_str::<Camera>(&json)
+        .unwrap()
+        .with_image_size(100, 100);
+    assert_approx_eq!(camera.vertical.length(), 2.0);
+    // an explicit aspect is kept
+    let camera = Camera::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Point3D::new(0.0, 0.0, -1.0),
+        Point3D::new(0.0, 1.0, 0.0),
+        90.0,
+        1.0,
+    );
+    assert_approx_eq!(camera.with_image_size(300, 100).horizontal.length(), 2.0);
+
+    let both = json.replace("\"focal", "\"vfov\":40.0,\"focal");
+    assert!(serde_json::from_str::<Camera>(&both).is_err());
+    let neither = json.replace(",\"focal_length_mm\":12.0", "");
+    assert!(serde_json::from_str::<Camera>(&neither).is_err());
+    for field_of_view in [
+        "\"vfov\":0.0",
+        "\"vfov\":180.0",
+        "\"hfov\":-10.0",
+        "\"hfov\":200.0",
+        "\"focal_length_mm\":0.0",
+        "\"focal_length_mm\":-35.0",
+    ] {
+        let bad = json.replace("\"focal_length_mm\":12.0", field_of_view);
+        assert!(
+            serde_json::from_str::<Camera>(&bad).is_err(),
+            "{}",
+            field_of_view
+        );
+    }
+}
+
+#[test]
+fn test_orbit() {
+    let camera = Camera::new(
+        Point3D::new(0.0, 1.0, 2.0),
+        Point3D::new(0.0, 1.0, 0.0),
+        Point3D::new(0.0, 1.0, 0.0),
+        40.0,
+        1.5,
+    );
+    let quarter = camera.orbit(90.0, None, None);
+    assert_approx_eq!(quarter.look_from.x(), 2.0);
+    assert_approx_eq!(quarter.look_from.y(), 1.0);
+    assert_approx_eq!(quarter.look_from.z(), 0.0);
+    let ray = quarter.get_ray(0.5, 0.5);
+    assert_approx_eq!(ray.direction.unit_vector().x(), -1.0);
+
+    let above = camera.orbit(180.0, Some(4.0), Some(30.0));
+    assert_approx_eq!(above.look_from.y(), 3.0);
+    assert_approx_eq!(above.look_from.z(), -(12.0f64.sqrt()));
+    assert_approx_eq!((above.look_from - above.look_at).length(), 4.0);
+    assert_eq!(above.view_aspect, 1.5);
+    assert_eq!(above.vfov, Some(40.0));
+}
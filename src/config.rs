@@ -1,61 +1,88 @@
-use jpeg_decoder::Decoder;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
+use crate::animation::Animation;
 use crate::body::Body;
 use crate::camera::Camera;
+use crate::environment::Environment;
+use crate::lights::LightSource;
 #[cfg(test)]
 use std::fs;
 
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(try_from = "SkyParams")]
 pub struct Sky {
     // If provided, the sky will be rendered using the equirectangular
-    // projected texture loaded from an image file at this path. Else,
-    // a light blue colored sky will be used.
-    #[serde_as(as = "TextureOptionPixelsAsPath")]
-    pub texture: Option<(Vec<u8>, usize, usize, String)>,
+    // image at this path, the same as an `environment` that is not turned
+    // or scaled. Else, a light blue colored sky will be used.
+    #[serde_as(as = "TextureOptionEnvironmentAsPath")]
+    pub texture: Option<Environment>,
+    // An image of the surroundings with its own rotation and intensity,
+    // instead of `texture`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<Environment>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct SkyParams {
+    #[serde_as(as = "TextureOptionEnvironmentAsPath")]
+    #[serde(default)]
+    pub texture: Option<Environment>,
+    #[serde(default)]
+    pub environment: Option<Environment>,
+}
+
+impl TryFrom<SkyParams> for Sky {
+    type Error = String;
+
+    fn try_from(p: SkyParams) -> Result<Self, Self::Error> {
+        if p.texture.is_some() && p.environment.is_some() {
+            return Err("a sky has either a texture or an environment, not both".to_string());
+        }
+        Ok(Sky {
+            texture: p.texture,
+            environment: p.environment,
+        })
+    }
 }
 
 impl Sky {
     pub fn new_default_sky() -> Sky {
-        Sky { texture: None }
+        Sky {
+            texture: None,
+            environment: None,
+        }
     }
-}
 
-fn load_texture_image(path: &str) -> (Vec<u8>, usize, usize, String) {
-    let file = File::open(path).expect(path);
-    let mut decoder = Decoder::new(BufReader::new(file));
-    let pixels = decoder.decode().expect("failed to decode image");
-    let metadata = decoder.info().unwrap();
-    (
-        pixels,
-        metadata.width as usize,
-        metadata.height as usize,
-        path.to_string(),
-    )
+    // The image the sky is made of, if not the default one.
+    pub fn environment_map(&self) -> Option<&Environment> {
+        self.environment.as_ref().or(self.texture.as_ref())
+    }
 }
 
 serde_with::serde_conv!(
-    TextureOptionPixelsAsPath,
-    Option<(Vec<u8>, usize, usize, String)>,
-    |texture: &Option<(Vec<u8>, usize, usize, String)>| {
+    TextureOptionEnvironmentAsPath,
+    Option<Environment>,
+    |texture: &Option<Environment>| {
         match texture {
-            Some(tuple) => tuple.3.clone(),
+            Some(environment) => environment.path.clone(),
             None => "".to_string(),
         }
     },
-    |value: &str| -> Result<_, std::convert::Infallible> {
+    |value: &str| -> Result<_, String> {
         match value {
             "" => Ok(None),
-            _ => Ok(Some(load_texture_image(value))),
+            _ => Environment::load(value).map(Some),
         }
     }
 );
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(try_from = "ConfigParams")]
 pub struct Config {
     pub width: usize,
     pub height: usize,
@@ -64,6 +91,69 @@ pub struct Config {
     pub sky: Option<Sky>,
     pub camera: Camera,
     pub objects: Vec<Body>,
+    // Lights that are not bodies, see `LightSource`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<LightSource>,
+    // Test every body for every ray instead of using a BVH. Only useful for
+    // debugging the acceleration structure.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub brute_force: bool,
+    // Named bodies that are only rendered through Instance objects.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub geometries: BTreeMap<String, Arc<Body>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub animation: Option<Animation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfigParams {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: u32,
+    pub max_depth: usize,
+    pub sky: Option<Sky>,
+    pub camera: Camera,
+    pub objects: Vec<Body>,
+    #[serde(default)]
+    pub lights: Vec<LightSource>,
+    #[serde(default)]
+    pub brute_force: bool,
+    #[serde(default)]
+    pub geometries: BTreeMap<String, Body>,
+    #[serde(default)]
+    pub animation: Option<Animation>,
+}
+
+impl TryFrom<ConfigParams> for Config {
+    type Error = String;
+
+    fn try_from(p: ConfigParams) -> Result<Self, Self::Error> {
+        // geometries can't refer to other geometries
+        let empty = BTreeMap::new();
+        let mut geometries = BTreeMap::new();
+        for (name, mut body) in p.geometries {
+            body.link(&empty)
+                .map_err(|e| format!("geometry {:?}: {}", name, e))?;
+            geometries.insert(name, Arc::new(body));
+        }
+        let mut objects = p.objects;
+        for body in objects.iter_mut() {
+            body.link(&geometries)?;
+        }
+        Ok(Config {
+            width: p.width,
+            height: p.height,
+            samples_per_pixel: p.samples_per_pixel,
+            max_depth: p.max_depth,
+            sky: p.sky,
+            camera: p.camera,
+            objects,
+            lights: p.lights,
+            brute_force: p.brute_force,
+            geometries,
+            animation: p.animation,
+        })
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -75,6 +165,8 @@ mod tests {
     use crate::materials::Material;
     use crate::materials::Metal;
     use crate::point3d::Point3D;
+    use crate::ray::Hittable;
+    use palette::Srgb;
     use rand::Rng;
     #[test]
     fn test_to_json() {
@@ -96,9 +188,13 @@ mod tests {
                 0.5,
                 Material::Lambertian(Lambertian::new(Srgb::new(0.8, 0.3, 0.3))),
             ))],
+            lights: Vec::new(),
+            brute_force: false,
+            geometries: BTreeMap::new(),
+            animation: None,
         };
         let serialized = serde_json::to_string(&config).unwrap();
-        assert_eq!("{\"width\":100,\"height\":100,\"samples_per_pixel\":1,\"max_depth\":1,\"sky\":{\"texture\":\"\"},\"camera\":{\"look_from\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"look_at\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"vup\":{\"x\":0.0,\"y\":1.0,\"z\":0.0},\"vfov\":90.0,\"aspect\":1.0},\"objects\":[{\"center\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"radius\":0.5,\"material\":{\"Lambertian\":{\"albedo\":[0.8,0.3,0.3]}}}]}", serialized);
+        assert_eq!("{\"width\":100,\"height\":100,\"samples_per_pixel\":1,\"max_depth\":1,\"sky\":{\"texture\":\"\"},\"camera\":{\"look_from\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"look_at\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"vup\":{\"x\":0.0,\"y\":1.0,\"z\":0.0},\"vfov\":90.0,\"aspect\":1.0},\"objects\":[{\"type\":\"Sphere\",\"center\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"radius\":0.5,\"material\":{\"Lambertian\":{\"albedo\":[0.8,0.3,0.3]}}}]}", serialized);
     }
 
     #[test]
@@ -121,38 +217,68 @@ mod tests {
                 0.5,
                 Material::Lambertian(Lambertian::new(Srgb::new(0.8, 0.3, 0.3))),
             ))],
+            lights: Vec::new(),
+            brute_force: false,
+            geometries: BTreeMap::new(),
+            animation: None,
         };
         let serialized = serde_json::to_string(&config).unwrap();
-        assert_eq!("{\"width\":100,\"height\":100,\"samples_per_pixel\":1,\"max_depth\":1,\"sky\":null,\"camera\":{\"look_from\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"look_at\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"vup\":{\"x\":0.0,\"y\":1.0,\"z\":0.0},\"vfov\":90.0,\"aspect\":1.0},\"objects\":[{\"center\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"radius\":0.5,\"material\":{\"Lambertian\":{\"albedo\":[0.8,0.3,0.3]}}}]}", serialized);
+        assert_eq!("{\"width\":100,\"height\":100,\"samples_per_pixel\":1,\"max_depth\":1,\"sky\":null,\"camera\":{\"look_from\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"look_at\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"vup\":{\"x\":0.0,\"y\":1.0,\"z\":0.0},\"vfov\":90.0,\"aspect\":1.0},\"objects\":[{\"type\":\"Sphere\",\"center\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"radius\":0.5,\"material\":{\"Lambertian\":{\"albedo\":[0.8,0.3,0.3]}}}]}", serialized);
         let _ = serde_json::from_str::<Config>(&serialized).expect("Unable to parse json");
 
         // This scene contains a sky texture at data/earth,jpg
-        let scene_json = "{\"width\":100,\"height\":100,\"samples_per_pixel\":1,\"max_depth\":1,\"sky\":{\"texture\":\"data/earth.jpg\"},\"camera\":{\"look_from\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"look_at\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"vup\":{\"x\":0.0,\"y\":1.0,\"z\":0.0},\"vfov\":90.0,\"aspect\":1.0},\"objects\":[{\"center\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"radius\":0.5,\"material\":{\"Lambertian\":{\"albedo\":[0.8,0.3,0.3]}}}]}";
+        let scene_json = "{\"width\":100,\"height\":100,\"samples_per_pixel\":1,\"max_depth\":1,\"sky\":{\"texture\":\"data/earth.jpg\"},\"camera\":{\"look_from\":{\"x\":0.0,\"y\":0.0,\"z\":0.0},\"look_at\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"vup\":{\"x\":0.0,\"y\":1.0,\"z\":0.0},\"vfov\":90.0,\"aspect\":1.0},\"objects\":[{\"type\":\"Sphere\",\"center\":{\"x\":0.0,\"y\":0.0,\"z\":-1.0},\"radius\":0.5,\"material\":{\"Lambertian\":{\"albedo\":[0.8,0.3,0.3]}}}]}";
         let scene = serde_json::from_str::<Config>(&scene_json).expect("Unable to parse json");
 
         assert_eq!(
             match scene.sky {
                 Some(sky) => {
                     match sky.texture {
-                        Some(tuple) => (tuple.1, tuple.2, tuple.3),
+                        Some(environment) => {
+                            let (width, height) = environment.size();
+                            (width, height, environment.path.clone())
+                        }
                         _ => (0, 0, "".to_string()),
                     }
                 }
                 _ => (0, 0, "".to_string()),
             },
             (2048, 1024, "data/earth.jpg".to_string())
-        )
+        );
+
+        // one image or the other
+        let both = scene_json.replace(
+            "\"texture\":\"data/earth.jpg\"",
+            "\"texture\":\"data/earth.jpg\",\"environment\":{\"path\":\"data/sky.hdr\"}",
+        );
+        assert!(serde_json::from_str::<Config>(&both).is_err());
+    }
+
+    #[test]
+    fn test_instances() {
+        let json = fs::read("data/instances.json").expect("Unable to read file");
+        let scene = serde_json::from_slice::<Config>(&json).expect("Unable to parse json");
+        let mut instances = 0;
+        for body in &scene.objects {
+            if let Body::Instance(instance) = body {
+                assert!(instance.bounding_box().is_some());
+                instances += 1;
+            }
+        }
+        assert_eq!(instances, 25);
+        // every instance shares the model loaded for the geometry
+        assert_eq!(Arc::strong_count(&scene.geometries["cube"]), instances + 1);
+
+        let unknown = String::from_utf8(json)
+            .unwrap()
+            .replace("\"geometry\": \"cube\"", "\"geometry\": \"sphere\"");
+        let error = serde_json::from_str::<Config>(&unknown).unwrap_err();
+        assert!(error.to_string().contains("unknown geometry \"sphere\""));
     }
 
     fn _make_cover_world() -> Vec<Body> {
         let mut world = Vec::new();
 
-        world.push(Sphere::new(
-            Point3D::new(0.0, -1000.0, 0.0),
-            1000.0,
-            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
-        ));
-
         let mut rng = rand::thread_rng();
 
         for a in -11..11 {
@@ -215,7 +341,14 @@ mod tests {
             1.0,
             Material::Metal(Metal::new(Srgb::new(0.7, 0.6, 0.5), 0.0)),
         ));
-        world.into_iter().map(|x| Body::Sphere(x)).collect()
+        let ground = Plane::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 1.0, 0.0),
+            Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+        );
+        std::iter::once(Body::Plane(ground))
+            .chain(world.into_iter().map(|x| Body::Sphere(x)))
+            .collect()
     }
 
     #[test]
@@ -234,6 +367,10 @@ mod tests {
                 800.0 / 600.0,
             ),
             objects: _make_cover_world(),
+            lights: Vec::new(),
+            brute_force: false,
+            geometries: BTreeMap::new(),
+            animation: None,
         };
         let serialized = serde_json::to_string_pretty(&config).unwrap();
         fs::write("/tmp/cover_scene.json", serialized).unwrap();
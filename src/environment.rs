@@ -0,0 +1,342 @@
+use palette::Srgb;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::path::Path;
+
+use crate::loaders::hdr::load_hdr_image;
+use crate::point3d::Point3D;
+
+// Light coming from all around the scene, from an equirectangular image:
+// longitude goes across from -180 degrees on the left to 180 on the
+// right with -z in the middle, latitude goes from straight up at the top
+// to straight down at the bottom, the same as an Equirectangular camera.
+// Radiance .hdr and OpenEXR images hold the light as it is, 8-bit images
+// are taken as values from 0 to 1. The map is turned by `rotation`
+// degrees clockwise seen from above and scaled by `intensity`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "EnvironmentParams")]
+pub struct Environment {
+    pub path: String,
+    pub rotation: f64,
+    pub intensity: f64,
+    #[serde(skip_serializing)]
+    pixels: Vec<[f32; 3]>,
+    #[serde(skip_serializing)]
+    width: usize,
+    #[serde(skip_serializing)]
+    height: usize,
+    #[serde(skip_serializing)]
+    distribution: Distribution2D,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentParams {
+    pub path: String,
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default = "default_intensity")]
+    pub intensity: f64,
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+impl TryFrom<EnvironmentParams> for Environment {
+    type Error = String;
+
+    fn try_from(p: EnvironmentParams) -> Result<Self, Self::Error> {
+        if !(p.intensity >= 0.0 && p.intensity.is_finite()) {
+            return Err(format!("{}: bad intensity {}", p.path, p.intensity));
+        }
+        let mut environment = Environment::load(&p.path)?;
+        environment.rotation = p.rotation;
+        environment.intensity = p.intensity;
+        Ok(environment)
+    }
+}
+
+impl Environment {
+    // A map from `width` times `height` pixels, row by row from the top.
+    pub fn new(pixels: Vec<[f32; 3]>, width: usize, height: usize) -> Result<Environment, String> {
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            return Err(format!(
+                "{} pixels don't make a {}x{} image",
+                pixels.len(),
+                width,
+                height
+            ));
+        }
+        if pixels
+            .iter()
+            .flatten()
+            .any(|c| !(*c >= 0.0 && c.is_finite()))
+        {
+            return Err("pixels must be finite and not negative".to_string());
+        }
+        // brighter pixels are picked more often, and pixels near the
+        // poles less as they cover less of the sphere
+        let weights = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, &[r, g, b])| {
+                let theta = PI * ((i / width) as f64 + 0.5) / height as f64;
+                (0.2126 * r + 0.7152 * g + 0.0722 * b) as f64 * theta.sin()
+            })
+            .collect();
+        Ok(Environment {
+            path: String::new(),
+            rotation: 0.0,
+            intensity: 1.0,
+            pixels,
+            width,
+            height,
+            distribution: Distribution2D::new(weights, width, height),
+        })
+    }
+
+    // The map in the image at `path`, not turned or scaled.
+    pub fn load(path: &str) -> Result<Environment, String> {
+        let (pixels, width, height) =
+            load_hdr_image(Path::new(path)).map_err(|e| format!("{:#}", e))?;
+        let mut environment =
+            Environment::new(pixels, width, height).map_err(|e| format!("{}: {}", path, e))?;
+        environment.path = path.to_string();
+        Ok(environment)
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    // Position of a direction on the image, from 0 to 1 across and down.
+    fn uv(&self, direction: Point3D) -> (f64, f64) {
+        let d = direction.unit_vector();
+        let longitude = d.x().atan2(-d.z()) - self.rotation.to_radians();
+        let u = (0.5 + longitude / (2.0 * PI)).rem_euclid(1.0);
+        let v = d.y().clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+
+    fn pixel(&self, u: f64, v: f64) -> (usize, usize) {
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        (x, y)
+    }
+
+    // Light arriving from the surroundings along `direction`.
+    pub fn radiance(&self, direction: Point3D) -> Srgb {
+        let (u, v) = self.uv(direction);
+        let (x, y) = self.pixel(u, v);
+        let [r, g, b] = self.pixels[y * self.width + x];
+        let intensity = self.intensity as f32;
+        Srgb::new(intensity * r, intensity * g, intensity * b)
+    }
+
+    // Density over solid angle of `sample` picking `direction`.
+    pub fn pdf(&self, direction: Point3D) -> f64 {
+        let (u, v) = self.uv(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let (x, y) = self.pixel(u, v);
+        self.distribution.pdf(x, y) / (2.0 * PI * PI * sin_theta)
+    }
+
+    // A direction picked in proportion to the light coming from it, with
+    // its density over solid angle. None if the map is black.
+    pub fn sample(&self) -> Option<(Point3D, f64)> {
+        let mut rng = rand::thread_rng();
+        let (u, v) = self.distribution.sample(rng.gen(), rng.gen())?;
+        let longitude = 2.0 * PI * (u - 0.5) + self.rotation.to_radians();
+        let theta = PI * v;
+        let direction = Point3D::new(
+            theta.sin() * longitude.sin(),
+            theta.cos(),
+            -theta.sin() * longitude.cos(),
+        );
+        let pdf = self.pdf(direction);
+        if pdf > 0.0 {
+            Some((direction, pdf))
+        } else {
+            None
+        }
+    }
+}
+
+// Picks pixels of an image in proportion to their weights: first a row by
+// the sum of its weights, then a pixel in that row.
+#[derive(Debug, Clone)]
+struct Distribution2D {
+    width: usize,
+    height: usize,
+    weights: Vec<f64>,
+    // Running sums of the row sums, from 0 to `total`.
+    rows: Vec<f64>,
+    // Running sums within every row, `width + 1` of them per row.
+    columns: Vec<f64>,
+    total: f64,
+}
+
+impl Distribution2D {
+    fn new(weights: Vec<f64>, width: usize, height: usize) -> Distribution2D {
+        let mut rows = vec![0.0];
+        let mut columns = Vec::with_capacity(height * (width + 1));
+        for row in weights.chunks(width) {
+            let mut sum = 0.0;
+            columns.push(sum);
+            for weight in row {
+                sum += weight;
+                columns.push(sum);
+            }
+            rows.push(rows[rows.len() - 1] + sum);
+        }
+        let total = rows[height];
+        Distribution2D {
+            width,
+            height,
+            weights,
+            rows,
+            columns,
+            total,
+        }
+    }
+
+    // Density over the image from 0 to 1 both ways.
+    fn pdf(&self, x: usize, y: usize) -> f64 {
+        if self.total <= 0.0 {
+            return 0.0;
+        }
+        self.weights[y * self.width + x] * (self.width * self.height) as f64 / self.total
+    }
+
+    // Maps two uniform random numbers to a position on the image, from 0
+    // to 1 across and down.
+    fn sample(&self, r1: f64, r2: f64) -> Option<(f64, f64)> {
+        if self.total <= 0.0 {
+            return None;
+        }
+        // the last entry with a running sum at or below the target, which
+        // skips rows and pixels of no weight
+        let find = |sums: &[f64], target: f64| {
+            let i = sums.partition_point(|&sum| sum <= target).max(1) - 1;
+            let i = i.min(sums.len() - 2);
+            let (low, high) = (sums[i], sums[i + 1]);
+            let fraction = if high > low {
+                ((target - low) / (high - low)).clamp(0.0, 1.0)
+            } else {
+                0.5
+            };
+            (i, fraction)
+        };
+        let (y, dy) = find(&self.rows, r1 * self.total);
+        let row = &self.columns[y * (self.width + 1)..(y + 1) * (self.width + 1)];
+        let (x, dx) = find(row, r2 * row[self.width]);
+        Some((
+            (x as f64 + dx) / self.width as f64,
+            (y as f64 + dy) / self.height as f64,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        // a different color in every quarter of longitude, the top row red
+        let pixels = (0..8)
+            .map(|i| match i {
+                0..=3 => [1.0, 0.0, 0.0],
+                _ => [0.0, (i - 4) as f32, 1.0],
+            })
+            .collect();
+        let mut environment = Environment::new(pixels, 4, 2).unwrap();
+        let down = |x: f64, z: f64| Point3D::new(x, -0.5, z);
+        assert_eq!(environment.radiance(Point3D::new(0.0, 1.0, 0.0)).red, 1.0);
+        assert_eq!(environment.radiance(down(-1.0, 1.0)).green, 0.0);
+        assert_eq!(environment.radiance(down(-1.0, -1.0)).green, 1.0);
+        assert_eq!(environment.radiance(down(1.0, -1.0)).green, 2.0);
+        assert_eq!(environment.radiance(down(1.0, 1.0)).green, 3.0);
+        // a quarter turn brings what was behind to the back left
+        environment.rotation = 90.0;
+        environment.intensity = 2.0;
+        assert_eq!(environment.radiance(down(-1.0, 1.0)).green, 6.0);
+        assert_eq!(environment.radiance(down(1.0, -1.0)).green, 2.0);
+
+        assert!(Environment::new(vec![[1.0; 3]; 3], 2, 2).is_err());
+        assert!(Environment::new(vec![[-1.0; 3]; 4], 2, 2).is_err());
+    }
+
+    #[test]
+    fn test_sampling() {
+        // a dim sky with a bright patch, and a black ground
+        let (width, height) = (16, 8);
+        let pixels = (0..width * height)
+            .map(|i| match (i % width, i / width) {
+                (5, 2) => [50.0, 40.0, 30.0],
+                (_, y) if y < height / 2 => [0.2, 0.3, 0.5],
+                _ => [0.0; 3],
+            })
+            .collect();
+        let mut environment = Environment::new(pixels, width, height).unwrap();
+        environment.rotation = 30.0;
+        // the light from all directions, summed over the pixels
+        let expected: f64 = (0..width * height)
+            .map(|i| {
+                let y = (i / width) as f64;
+                let solid_angle = 2.0 * PI / width as f64
+                    * ((PI * y / height as f64).cos() - (PI * (y + 1.0) / height as f64).cos());
+                environment.pixels[i][0] as f64 * solid_angle
+            })
+            .sum();
+        let n = 100000;
+        let mut estimate = 0.0;
+        for _ in 0..n {
+            let (direction, pdf) = environment.sample().unwrap();
+            assert!(direction.y() > -1e-9);
+            estimate += environment.radiance(direction).red as f64 / pdf;
+        }
+        estimate /= n as f64;
+        assert!(
+            (estimate - expected).abs() < 0.01 * expected,
+            "{} {}",
+            estimate,
+            expected
+        );
+
+        let black = Environment::new(vec![[0.0; 3]; 4], 2, 2).unwrap();
+        assert!(black.sample().is_none());
+        assert_eq!(black.pdf(Point3D::new(0.0, 0.0, -1.0)), 0.0);
+    }
+
+    #[test]
+    fn test_load() {
+        let json = r#"{"path": "data/sky.hdr", "rotation": 45.0, "intensity": 0.5}"#;
+        let environment = serde_json::from_str::<Environment>(json).unwrap();
+        assert_eq!((environment.width, environment.height), (512, 256));
+        // the sun is far brighter than an 8-bit image could hold
+        assert!(environment.pixels.iter().any(|p| p[0] > 100.0));
+        assert_eq!(
+            serde_json::to_string(&environment).unwrap(),
+            r#"{"path":"data/sky.hdr","rotation":45.0,"intensity":0.5}"#
+        );
+
+        let image = image::Rgb32FImage::from_pixel(4, 2, image::Rgb([0.25, 2.5, 25.0]));
+        image.save("/tmp/test_environment.exr").unwrap();
+        let json = r#"{"path": "/tmp/test_environment.exr"}"#;
+        let environment = serde_json::from_str::<Environment>(json).unwrap();
+        let color = environment.radiance(Point3D::new(1.0, 0.0, 0.0));
+        assert_eq!((color.red, color.green, color.blue), (0.25, 2.5, 25.0));
+
+        assert!(serde_json::from_str::<Environment>(r#"{"path": "data/missing.hdr"}"#).is_err());
+        assert!(serde_json::from_str::<Environment>(
+            r#"{"path": "data/sky.hdr", "brightness": 2.0}"#
+        )
+        .is_err());
+    }
+}
@@ -1,7 +1,15 @@
+pub mod aabb;
+pub mod animation;
 pub mod body;
+pub mod bvh;
 pub mod camera;
 pub mod config;
+pub mod environment;
+pub mod lights;
+pub mod loaders;
 pub mod materials;
+pub mod matrix;
+pub mod onb;
 pub mod point3d;
 pub mod ray;
 pub mod raytracer;
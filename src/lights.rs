@@ -0,0 +1,593 @@
+use palette::Srgb;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::loaders::ies::{load_ies, IesProfile};
+use crate::onb::Onb;
+use crate::point3d::Point3D;
+use std::path::Path;
+
+#[cfg(test)]
+use assert_approx_eq::assert_approx_eq;
+
+// Lights that are not part of the geometry of the scene. They light
+// surfaces and volumes, but neither the camera nor mirrors see them, for
+// that use a body with an `Emissive` material.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum LightSource {
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+    Rect(RectLight),
+}
+
+// Light arriving at a point from a light source along `direction`, a unit
+// vector towards the light, which is `distance` away. `light` is already
+// divided by the probability density of picking that direction.
+pub struct LightSample {
+    pub direction: Point3D,
+    pub distance: f64,
+    pub light: Srgb,
+}
+
+impl LightSource {
+    // Picks where the light at `point` comes from, None if it gets none.
+    pub fn sample(&self, point: Point3D) -> Option<LightSample> {
+        match self {
+            LightSource::Point(p) => p.sample(point),
+            LightSource::Spot(s) => s.sample(point),
+            LightSource::Directional(d) => d.sample(),
+            LightSource::Rect(r) => r.sample(point),
+        }
+    }
+}
+
+fn white() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_falloff() -> f64 {
+    2.0
+}
+
+fn check_intensity(intensity: f64) -> Result<(), String> {
+    if !(intensity >= 0.0 && intensity.is_finite()) {
+        return Err(format!(
+            "light intensity {} is not a number >= 0",
+            intensity
+        ));
+    }
+    Ok(())
+}
+
+fn check_falloff(falloff: f64) -> Result<(), String> {
+    if !(falloff >= 0.0 && falloff.is_finite()) {
+        return Err(format!("light falloff {} is not a number >= 0", falloff));
+    }
+    Ok(())
+}
+
+fn scaled(color: [f32; 3], scale: f64) -> Srgb {
+    let [r, g, b] = color;
+    let scale = scale as f32;
+    Srgb::new(scale * r, scale * g, scale * b)
+}
+
+// Direction with an angle of at most acos(cos_max) to `axis`, uniformly
+// distributed over that cone.
+fn random_in_cone(axis: Point3D, cos_max: f64) -> Point3D {
+    let mut rng = rand::thread_rng();
+    let z = 1.0 + rng.gen::<f64>() * (cos_max - 1.0);
+    let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    Onb::from_w(axis).local(Point3D::new(r * phi.cos(), r * phi.sin(), z))
+}
+
+// Photometric profile of a light fixture, read from the IES file at
+// `path`. It scales the intensity of a light by direction, so that only
+// the brightest direction of the fixture gets all of it. Vertical angle 0
+// points down the axis of the light and horizontal angle 0 towards x, or
+// z for lights pointing along x.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "IesFileParams")]
+pub struct IesFile {
+    pub path: String,
+    #[serde(skip_serializing)]
+    profile: IesProfile,
+    #[serde(skip_serializing)]
+    max_candela: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IesFileParams {
+    pub path: String,
+}
+
+impl TryFrom<IesFileParams> for IesFile {
+    type Error = String;
+
+    fn try_from(p: IesFileParams) -> Result<Self, Self::Error> {
+        let profile = load_ies(Path::new(&p.path)).map_err(|e| format!("{:#}", e))?;
+        let max_candela = profile.max_candela();
+        if max_candela <= 0.0 {
+            return Err(format!("{}: the fixture gives off no light", p.path));
+        }
+        Ok(IesFile {
+            path: p.path,
+            profile,
+            max_candela,
+        })
+    }
+}
+
+impl IesFile {
+    // Fraction of the intensity going along `direction` from a light
+    // pointing along `axis`.
+    fn factor(&self, axis: Point3D, direction: Point3D) -> f64 {
+        let w = axis.unit_vector();
+        let reference = if w.x().abs() > 0.9 {
+            Point3D::new(0.0, 0.0, 1.0)
+        } else {
+            Point3D::new(1.0, 0.0, 0.0)
+        };
+        let u = (reference - w * reference.dot(w)).unit_vector();
+        let v = w.cross(u);
+        let d = direction.unit_vector();
+        let vertical = d.dot(w).clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = d.dot(v).atan2(d.dot(u)).to_degrees();
+        self.profile.candela_at(vertical, horizontal) / self.max_candela
+    }
+}
+
+// Light from a single point, `intensity` times `color` at a distance of
+// one, falling off with the distance to the power `falloff`. That is 2
+// for real lights, less makes the light reach further. With an `ies`
+// profile the light is a fixture pointing down.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "PointLightParams")]
+pub struct PointLight {
+    pub position: Point3D,
+    pub color: [f32; 3],
+    pub intensity: f64,
+    pub falloff: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ies: Option<IesFile>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PointLightParams {
+    pub position: Point3D,
+    #[serde(default = "white")]
+    pub color: [f32; 3],
+    pub intensity: f64,
+    #[serde(default = "default_falloff")]
+    pub falloff: f64,
+    #[serde(default)]
+    pub ies: Option<IesFile>,
+}
+
+impl TryFrom<PointLightParams> for PointLight {
+    type Error = String;
+
+    fn try_from(p: PointLightParams) -> Result<Self, Self::Error> {
+        check_falloff(p.falloff)?;
+        let mut point = PointLight::new(p.position, p.color, p.intensity)?;
+        point.falloff = p.falloff;
+        point.ies = p.ies;
+        Ok(point)
+    }
+}
+
+impl PointLight {
+    pub fn new(position: Point3D, color: [f32; 3], intensity: f64) -> Result<PointLight, String> {
+        check_intensity(intensity)?;
+        Ok(PointLight {
+            position,
+            color,
+            intensity,
+            falloff: default_falloff(),
+            ies: None,
+        })
+    }
+
+    fn sample(&self, point: Point3D) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let profile = match &self.ies {
+            Some(ies) => ies.factor(Point3D::new(0.0, -1.0, 0.0), -to_light),
+            None => 1.0,
+        };
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            light: scaled(
+                self.color,
+                profile * self.intensity / distance.powf(self.falloff),
+            ),
+        })
+    }
+}
+
+// A point light that only shines into a cone around `direction`, `angle`
+// degrees across. Over the outermost `edge` degrees it fades out smoothly.
+// An `ies` profile points along `direction` too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "SpotLightParams")]
+pub struct SpotLight {
+    pub position: Point3D,
+    pub direction: Point3D,
+    pub color: [f32; 3],
+    pub intensity: f64,
+    pub falloff: f64,
+    pub angle: f64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub edge: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ies: Option<IesFile>,
+    #[serde(skip_serializing)]
+    cos_outer: f64,
+    #[serde(skip_serializing)]
+    cos_inner: f64,
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpotLightParams {
+    pub position: Point3D,
+    pub direction: Point3D,
+    #[serde(default = "white")]
+    pub color: [f32; 3],
+    pub intensity: f64,
+    #[serde(default = "default_falloff")]
+    pub falloff: f64,
+    pub angle: f64,
+    #[serde(default)]
+    pub edge: f64,
+    #[serde(default)]
+    pub ies: Option<IesFile>,
+}
+
+impl TryFrom<SpotLightParams> for SpotLight {
+    type Error = String;
+
+    fn try_from(p: SpotLightParams) -> Result<Self, Self::Error> {
+        check_falloff(p.falloff)?;
+        let mut spot = SpotLight::new(p.position, p.direction, p.color, p.intensity, p.angle)?;
+        spot.falloff = p.falloff;
+        spot.ies = p.ies;
+        spot.with_edge(p.edge)
+    }
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point3D,
+        direction: Point3D,
+        color: [f32; 3],
+        intensity: f64,
+        angle: f64,
+    ) -> Result<SpotLight, String> {
+        if direction.near_zero() {
+            return Err("spot light needs a direction".to_string());
+        }
+        check_intensity(intensity)?;
+        if !(angle > 0.0 && angle <= 180.0) {
+            return Err(format!("spot light angle {} is not in (0, 180]", angle));
+        }
+        let cos_outer = (angle / 2.0).to_radians().cos();
+        Ok(SpotLight {
+            position,
+            direction,
+            color,
+            intensity,
+            falloff: default_falloff(),
+            angle,
+            edge: 0.0,
+            ies: None,
+            cos_outer,
+            cos_inner: cos_outer,
+        })
+    }
+
+    pub fn with_edge(self, edge: f64) -> Result<SpotLight, String> {
+        if !(0.0..=self.angle / 2.0).contains(&edge) {
+            return Err(format!(
+                "spot light edge {} is not between 0 and half the angle",
+                edge
+            ));
+        }
+        Ok(SpotLight {
+            edge,
+            cos_inner: (self.angle / 2.0 - edge).to_radians().cos(),
+            ..self
+        })
+    }
+
+    // How much of the light goes in a direction with an angle with cosine
+    // `cos_angle` to the axis of the cone.
+    fn cone(&self, cos_angle: f64) -> f64 {
+        if cos_angle <= self.cos_outer {
+            return 0.0;
+        }
+        if cos_angle >= self.cos_inner {
+            return 1.0;
+        }
+        let t = (cos_angle - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    fn sample(&self, point: Point3D) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let direction = to_light / distance;
+        let mut cone = self.cone(-direction.dot(self.direction.unit_vector()));
+        if let Some(ies) = &self.ies {
+            cone *= ies.factor(self.direction, -direction);
+        }
+        if cone <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            light: scaled(
+                self.color,
+                cone * self.intensity / distance.powf(self.falloff),
+            ),
+        })
+    }
+}
+
+// Light from very far away shining along `direction`, like the sun. A
+// surface facing it gets `intensity` times `color`. With an
+// `angular_diameter` in degrees the light comes from a disk that size in
+// the sky, which softens shadows, the sun is about 0.53 degrees across.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "DirectionalLightParams")]
+pub struct DirectionalLight {
+    pub direction: Point3D,
+    pub color: [f32; 3],
+    pub intensity: f64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub angular_diameter: f64,
+    #[serde(skip_serializing)]
+    cos_max: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirectionalLightParams {
+    pub direction: Point3D,
+    #[serde(default = "white")]
+    pub color: [f32; 3],
+    pub intensity: f64,
+    #[serde(default)]
+    pub angular_diameter: f64,
+}
+
+impl TryFrom<DirectionalLightParams> for DirectionalLight {
+    type Error = String;
+
+    fn try_from(p: DirectionalLightParams) -> Result<Self, Self::Error> {
+        DirectionalLight::new(p.direction, p.color, p.intensity, p.angular_diameter)
+    }
+}
+
+impl DirectionalLight {
+    pub fn new(
+        direction: Point3D,
+        color: [f32; 3],
+        intensity: f64,
+        angular_diameter: f64,
+    ) -> Result<DirectionalLight, String> {
+        if direction.near_zero() {
+            return Err("directional light needs a direction".to_string());
+        }
+        check_intensity(intensity)?;
+        if !(0.0..180.0).contains(&angular_diameter) {
+            return Err(format!(
+                "angular diameter {} is not in [0, 180)",
+                angular_diameter
+            ));
+        }
+        Ok(DirectionalLight {
+            direction,
+            color,
+            intensity,
+            angular_diameter,
+            cos_max: (angular_diameter / 2.0).to_radians().cos(),
+        })
+    }
+
+    fn sample(&self) -> Option<LightSample> {
+        let towards = -self.direction.unit_vector();
+        // the light divided by the density of directions in the disk is
+        // the same for all of them
+        let direction = if self.angular_diameter > 0.0 {
+            random_in_cone(towards, self.cos_max)
+        } else {
+            towards
+        };
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            light: scaled(self.color, self.intensity),
+        })
+    }
+}
+
+// Area light spanned by the edges `u` and `v` from `corner`, like a Quad,
+// that gives off `intensity` times `color` in linear radiance from the
+// side the normal u x v points to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RectLightParams")]
+pub struct RectLight {
+    pub corner: Point3D,
+    pub u: Point3D,
+    pub v: Point3D,
+    pub color: [f32; 3],
+    pub intensity: f64,
+    #[serde(skip_serializing)]
+    normal: Point3D,
+    #[serde(skip_serializing)]
+    area: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RectLightParams {
+    pub corner: Point3D,
+    pub u: Point3D,
+    pub v: Point3D,
+    #[serde(default = "white")]
+    pub color: [f32; 3],
+    pub intensity: f64,
+}
+
+impl TryFrom<RectLightParams> for RectLight {
+    type Error = String;
+
+    fn try_from(p: RectLightParams) -> Result<Self, Self::Error> {
+        RectLight::new(p.corner, p.u, p.v, p.color, p.intensity)
+    }
+}
+
+impl RectLight {
+    pub fn new(
+        corner: Point3D,
+        u: Point3D,
+        v: Point3D,
+        color: [f32; 3],
+        intensity: f64,
+    ) -> Result<RectLight, String> {
+        let n = u.cross(v);
+        let area = n.length();
+        if area <= 0.0 {
+            return Err("the edges of a rect light are parallel".to_string());
+        }
+        check_intensity(intensity)?;
+        Ok(RectLight {
+            corner,
+            u,
+            v,
+            color,
+            intensity,
+            normal: n / area,
+            area,
+        })
+    }
+
+    // Picks a point uniformly over the area, the density per unit solid
+    // angle of its direction is distance^2 / (cos * area).
+    fn sample(&self, point: Point3D) -> Option<LightSample> {
+        let mut rng = rand::thread_rng();
+        let on_light = self.corner + self.u * rng.gen::<f64>() + self.v * rng.gen::<f64>();
+        let to_light = on_light - point;
+        let distance = to_light.length();
+        let direction = to_light / distance;
+        let cosine = -direction.dot(self.normal);
+        if distance <= 0.0 || cosine <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            light: scaled(
+                self.color,
+                self.intensity * cosine * self.area / (distance * distance),
+            ),
+        })
+    }
+}
+
+#[test]
+fn test_point_and_spot_lights() {
+    let point = PointLight::new(Point3D::new(0.0, 2.0, 0.0), [1.0, 0.5, 0.0], 8.0).unwrap();
+    let sample = LightSource::Point(point)
+        .sample(Point3D::new(0.0, 0.0, 0.0))
+        .unwrap();
+    assert_approx_eq!(sample.direction.y(), 1.0);
+    assert_approx_eq!(sample.distance, 2.0);
+    assert_eq!(sample.light, Srgb::new(2.0, 1.0, 0.0));
+
+    let json = r#"{"type":"Spot","position":{"x":0.0,"y":2.0,"z":0.0},"direction":{"x":0.0,"y":-1.0,"z":0.0},"color":[1.0,1.0,1.0],"intensity":4.0,"falloff":0.0,"angle":90.0,"edge":15.0}"#;
+    let spot = serde_json::from_str::<LightSource>(json).unwrap();
+    assert_eq!(serde_json::to_string(&spot).unwrap(), json);
+    // fully lit up to 30 degrees off the axis, dark beyond 45
+    let at = |x: f64| spot.sample(Point3D::new(x, 0.0, 0.0)).map(|s| s.light.red);
+    assert_eq!(at(0.0), Some(4.0));
+    assert_eq!(at(1.0), Some(4.0));
+    let soft = at(1.8).unwrap();
+    assert!(soft > 0.0 && soft < 4.0);
+    assert!(at(2.5).is_none());
+
+    let too_soft = json.replace("\"edge\":15.0", "\"edge\":50.0");
+    assert!(serde_json::from_str::<LightSource>(&too_soft).is_err());
+
+    // the downlight profile is brightest straight along the axis
+    let json = r#"{"type":"Point","position":{"x":0.0,"y":2.0,"z":0.0},"color":[1.0,1.0,1.0],"intensity":4.0,"falloff":0.0,"ies":{"path":"data/downlight.ies"}}"#;
+    let fixture = serde_json::from_str::<LightSource>(json).unwrap();
+    assert_eq!(serde_json::to_string(&fixture).unwrap(), json);
+    let at = |p: Point3D| fixture.sample(p).map_or(0.0, |s| s.light.red);
+    assert_approx_eq!(at(Point3D::new(0.0, 0.0, 0.0)), 4.0);
+    let side = at(Point3D::new(2.0, 0.0, 0.0));
+    assert!(side > 0.0 && side < 4.0);
+    assert_approx_eq!(at(Point3D::new(0.0, 0.0, 2.0)), side);
+    assert_eq!(at(Point3D::new(0.0, 3.0, 0.0)), 0.0);
+    let missing = json.replace("downlight", "no_such_fixture");
+    assert!(serde_json::from_str::<LightSource>(&missing).is_err());
+
+    // misspelled fields and negative amounts are errors
+    let typo = json.replace("\"falloff\"", "\"fallof\"");
+    assert!(serde_json::from_str::<LightSource>(&typo).is_err());
+    let negative = json.replace("\"intensity\":4.0", "\"intensity\":-4.0");
+    assert!(serde_json::from_str::<LightSource>(&negative).is_err());
+    let negative = json.replace("\"falloff\":0.0", "\"falloff\":-1.0");
+    assert!(serde_json::from_str::<LightSource>(&negative).is_err());
+    assert!(PointLight::new(Point3D::new(0.0, 0.0, 0.0), white(), f64::NAN).is_err());
+}
+
+#[test]
+fn test_directional_and_rect_lights() {
+    let sun = DirectionalLight::new(Point3D::new(0.0, -1.0, 0.0), white(), 3.0, 10.0).unwrap();
+    for _ in 0..100 {
+        let sample = sun.sample().unwrap();
+        assert!(sample.direction.y() >= 5.0f64.to_radians().cos() - 1e-9);
+        assert_eq!(sample.light.red, 3.0);
+    }
+
+    // the light of a small rect light far away is close to that of a
+    // point light with intensity radiance * area
+    let rect = RectLight::new(
+        Point3D::new(-0.05, 10.0, -0.05),
+        Point3D::new(0.1, 0.0, 0.0),
+        Point3D::new(0.0, 0.0, 0.1),
+        white(),
+        100.0,
+    )
+    .unwrap();
+    let sample = rect.sample(Point3D::new(0.0, 0.0, 0.0)).unwrap();
+    assert!((sample.light.red - 0.01).abs() < 1e-4);
+    // it only shines downwards
+    assert!(rect.sample(Point3D::new(0.0, 20.0, 0.0)).is_none());
+    assert!(RectLight::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Point3D::new(1.0, 0.0, 0.0),
+        Point3D::new(2.0, 0.0, 0.0),
+        white(),
+        1.0
+    )
+    .is_err());
+}
@@ -0,0 +1,800 @@
+use common::warn;
+use eyre::*;
+use palette::Srgb;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::aabb::Aabb;
+use crate::body::{Body, Mesh};
+use crate::camera::Camera;
+use crate::config::{Config, Sky};
+use crate::materials::{Emissive, Glass, Lambertian, Material, Metal, Texture};
+use crate::matrix::Matrix4;
+use crate::point3d::Point3D;
+use crate::ray::Hittable;
+
+// Only the parts of the glTF 2.0 schema the importer uses.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Gltf {
+    scene: Option<usize>,
+    #[serde(default)]
+    scenes: Vec<GltfScene>,
+    #[serde(default)]
+    nodes: Vec<GltfNode>,
+    #[serde(default)]
+    meshes: Vec<GltfMesh>,
+    #[serde(default)]
+    accessors: Vec<GltfAccessor>,
+    #[serde(default)]
+    buffer_views: Vec<GltfBufferView>,
+    #[serde(default)]
+    buffers: Vec<GltfBuffer>,
+    #[serde(default)]
+    materials: Vec<GltfMaterial>,
+    #[serde(default)]
+    textures: Vec<GltfTexture>,
+    #[serde(default)]
+    images: Vec<GltfImage>,
+    #[serde(default)]
+    cameras: Vec<GltfCamera>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfScene {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfNode {
+    #[serde(default)]
+    children: Vec<usize>,
+    mesh: Option<usize>,
+    camera: Option<usize>,
+    matrix: Option<[f64; 16]>,
+    translation: Option<[f64; 3]>,
+    rotation: Option<[f64; 4]>,
+    scale: Option<[f64; 3]>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfMesh {
+    primitives: Vec<GltfPrimitive>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfPrimitive {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    #[serde(default = "default_mode")]
+    mode: u32,
+}
+
+fn default_mode() -> u32 {
+    4
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfAccessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    sparse: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfBufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfBuffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfMaterial {
+    pbr_metallic_roughness: Option<GltfPbr>,
+    #[serde(default)]
+    emissive_factor: [f32; 3],
+    #[serde(default)]
+    double_sided: bool,
+    #[serde(default)]
+    extensions: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfPbr {
+    #[serde(default = "default_base_color")]
+    base_color_factor: [f32; 4],
+    base_color_texture: Option<GltfTextureInfo>,
+    #[serde(default = "default_factor")]
+    metallic_factor: f64,
+    #[serde(default = "default_factor")]
+    roughness_factor: f64,
+}
+
+fn default_base_color() -> [f32; 4] {
+    [1.0, 1.0, 1.0, 1.0]
+}
+
+fn default_factor() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfTextureInfo {
+    index: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfTexture {
+    source: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfImage {
+    uri: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfCamera {
+    perspective: Option<GltfPerspective>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfPerspective {
+    yfov: f64,
+    aspect_ratio: Option<f64>,
+}
+
+const GLB_MAGIC: u32 = 0x4654_6C67; // "glTF"
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .ok_or_else(|| eyre!("unexpected end of GLB file"))?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// Splits a binary .glb container into its JSON and (optional) BIN chunks.
+fn parse_glb(data: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    if read_u32(data, 0)? != GLB_MAGIC {
+        bail!("not a GLB file");
+    }
+    if read_u32(data, 4)? != 2 {
+        bail!("only glTF 2.0 is supported");
+    }
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let length = read_u32(data, offset)? as usize;
+        let kind = read_u32(data, offset + 4)?;
+        let chunk = data
+            .get(offset + 8..offset + 8 + length)
+            .ok_or_else(|| eyre!("GLB chunk out of range"))?;
+        match kind {
+            GLB_CHUNK_JSON => json = Some(chunk),
+            GLB_CHUNK_BIN => bin = Some(chunk),
+            _ => {}
+        }
+        offset += 8 + length;
+    }
+    Ok((
+        json.ok_or_else(|| eyre!("GLB file without JSON chunk"))?,
+        bin,
+    ))
+}
+
+fn decode_base64(input: &str) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in input.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' | b'\n' | b'\r' => continue,
+            _ => bail!("invalid base64 character {:?}", c as char),
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(output)
+}
+
+// Resolves a buffer or image uri: embedded data uris are decoded, anything
+// else is read relative to the directory of the glTF file. Only local
+// files are supported.
+fn load_uri(uri: &str, base: &Path) -> Result<Vec<u8>> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, payload) = data
+            .split_once(";base64,")
+            .ok_or_else(|| eyre!("only base64 data uris are supported"))?;
+        return decode_base64(payload);
+    }
+    if uri.contains("://") {
+        bail!("only local files are supported, got {}", uri);
+    }
+    let path = base.join(uri);
+    fs::read(&path).with_context(|| format!("reading {}", path.display()))
+}
+
+struct GltfLoader<'a> {
+    gltf: Gltf,
+    buffers: Vec<Vec<u8>>,
+    base: &'a Path,
+}
+
+impl<'a> GltfLoader<'a> {
+    // Reads an accessor as a flat list of f64, returning the values and the
+    // number of components per element.
+    fn read_accessor(&self, index: usize) -> Result<(Vec<f64>, usize)> {
+        let accessor = self
+            .gltf
+            .accessors
+            .get(index)
+            .ok_or_else(|| eyre!("accessor {} out of range", index))?;
+        if accessor.sparse.is_some() {
+            bail!("sparse accessors are not supported");
+        }
+        let components = match accessor.kind.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            other => bail!("unsupported accessor type {}", other),
+        };
+        let (size, max): (usize, f64) = match accessor.component_type {
+            5120 => (1, 127.0),
+            5121 => (1, 255.0),
+            5122 => (2, 32767.0),
+            5123 => (2, 65535.0),
+            5125 => (4, 4294967295.0),
+            5126 => (4, 1.0),
+            other => bail!("unsupported component type {}", other),
+        };
+        let view_index = match accessor.buffer_view {
+            Some(v) => v,
+            // no buffer view means all zeros
+            None => return Ok((vec![0.0; accessor.count * components], components)),
+        };
+        let view = self
+            .gltf
+            .buffer_views
+            .get(view_index)
+            .ok_or_else(|| eyre!("buffer view {} out of range", view_index))?;
+        let buffer = self
+            .buffers
+            .get(view.buffer)
+            .ok_or_else(|| eyre!("buffer {} out of range", view.buffer))?;
+        let stride = view.byte_stride.unwrap_or(size * components);
+        let start = view.byte_offset + accessor.byte_offset;
+        let end = start + stride * accessor.count.saturating_sub(1) + size * components;
+        if accessor.count > 0 && (end > view.byte_offset + view.byte_length || end > buffer.len()) {
+            bail!("accessor {} out of range of its buffer", index);
+        }
+
+        let mut values = Vec::with_capacity(accessor.count * components);
+        for i in 0..accessor.count {
+            for c in 0..components {
+                let o = start + i * stride + c * size;
+                let b = &buffer[o..o + size];
+                let value = match accessor.component_type {
+                    5120 => b[0] as i8 as f64,
+                    5121 => b[0] as f64,
+                    5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                };
+                values.push(if accessor.normalized {
+                    (value / max).max(-1.0)
+                } else {
+                    value
+                });
+            }
+        }
+        Ok((values, components))
+    }
+
+    // Reads a vertex attribute, which must have one of the `expected`
+    // component counts.
+    fn read_attribute(
+        &self,
+        index: usize,
+        name: &str,
+        expected: &[usize],
+    ) -> Result<(Vec<f64>, usize)> {
+        let (values, components) = self.read_accessor(index)?;
+        if !expected.contains(&components) {
+            bail!(
+                "{} has {} components per vertex, expected {:?}",
+                name,
+                components,
+                expected
+            );
+        }
+        Ok((values, components))
+    }
+
+    // Maps a metallic-roughness material onto the closest of our materials.
+    fn material(&self, index: Option<usize>) -> Result<Material> {
+        let gltf_material = match index.and_then(|i| self.gltf.materials.get(i)) {
+            Some(m) => m,
+            None => {
+                return Ok(Material::Lambertian(Lambertian::new(Srgb::new(
+                    0.8, 0.8, 0.8,
+                ))))
+            }
+        };
+        if gltf_material.emissive_factor.iter().any(|&c| c > 0.0) {
+            let [r, g, b] = gltf_material.emissive_factor;
+            let strength = gltf_material
+                .extensions
+                .get("KHR_materials_emissive_strength")
+                .and_then(|e| e.get("emissiveStrength"))
+                .and_then(|f| f.as_f64())
+                .unwrap_or(1.0);
+            if !(strength >= 0.0 && strength.is_finite()) {
+                bail!("emissive strength {} is not a number >= 0", strength);
+            }
+            let mut emissive = Emissive::new(Srgb::new(r, g, b), strength);
+            emissive.two_sided = gltf_material.double_sided;
+            return Ok(Material::Emissive(emissive));
+        }
+        let transmission = gltf_material
+            .extensions
+            .get("KHR_materials_transmission")
+            .and_then(|e| e.get("transmissionFactor"))
+            .and_then(|f| f.as_f64())
+            .unwrap_or(0.0);
+        if transmission > 0.5 {
+            let ior = gltf_material
+                .extensions
+                .get("KHR_materials_ior")
+                .and_then(|e| e.get("ior"))
+                .and_then(|f| f.as_f64())
+                .unwrap_or(1.5);
+            return Ok(Material::Glass(Glass::new(ior)));
+        }
+        let pbr = match &gltf_material.pbr_metallic_roughness {
+            Some(pbr) => pbr,
+            None => {
+                return Ok(Material::Lambertian(Lambertian::new(Srgb::new(
+                    1.0, 1.0, 1.0,
+                ))))
+            }
+        };
+        let [r, g, b, _] = pbr.base_color_factor;
+        let base_color = Srgb::new(r, g, b);
+        if pbr.metallic_factor >= 0.5 {
+            return Ok(Material::Metal(Metal::new(
+                base_color,
+                pbr.roughness_factor,
+            )));
+        }
+        let image_uri = pbr
+            .base_color_texture
+            .as_ref()
+            .and_then(|t| self.gltf.textures.get(t.index))
+            .and_then(|t| t.source)
+            .and_then(|s| self.gltf.images.get(s))
+            .and_then(|i| i.uri.as_ref());
+        match image_uri {
+            // textures are decoded from files next to the scene, embedded
+            // images fall back to the base color
+            Some(uri) if !uri.starts_with("data:") => {
+                let path = self.base.join(uri);
+                let texture =
+                    Texture::new(base_color, &path.to_string_lossy(), 0.0).map_err(|e| eyre!(e))?;
+                Ok(Material::Texture(texture))
+            }
+            Some(_) => {
+                warn!("embedded glTF images are not supported, using the base color");
+                Ok(Material::Lambertian(Lambertian::new(base_color)))
+            }
+            None => {
+                if pbr.base_color_texture.is_some() {
+                    warn!("glTF images in buffers are not supported, using the base color");
+                }
+                Ok(Material::Lambertian(Lambertian::new(base_color)))
+            }
+        }
+    }
+
+    fn primitive(&self, primitive: &GltfPrimitive, transform: &Matrix4) -> Result<Option<Mesh>> {
+        let position = match primitive.attributes.get("POSITION") {
+            Some(p) => *p,
+            None => return Ok(None),
+        };
+        let (positions, _) = self.read_attribute(position, "POSITION", &[3])?;
+        let vertices: Vec<Point3D> = positions
+            .chunks(3)
+            .map(|p| transform.transform_point(Point3D::new(p[0], p[1], p[2])))
+            .collect();
+
+        let normal_matrix = transform
+            .inverse()
+            .unwrap_or_else(Matrix4::identity)
+            .transpose();
+        let normals = match primitive.attributes.get("NORMAL") {
+            Some(n) => self
+                .read_attribute(*n, "NORMAL", &[3])?
+                .0
+                .chunks(3)
+                .map(|n| {
+                    normal_matrix
+                        .transform_vector(Point3D::new(n[0], n[1], n[2]))
+                        .unit_vector()
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        // glTF puts the texture origin at the top left, our textures at the
+        // bottom left
+        let uvs = match primitive.attributes.get("TEXCOORD_0") {
+            Some(t) => self
+                .read_attribute(*t, "TEXCOORD_0", &[2])?
+                .0
+                .chunks(2)
+                .map(|t| [t[0], 1.0 - t[1]])
+                .collect(),
+            None => Vec::new(),
+        };
+        let colors = match primitive.attributes.get("COLOR_0") {
+            Some(c) => {
+                let (values, components) = self.read_attribute(*c, "COLOR_0", &[3, 4])?;
+                values
+                    .chunks(components)
+                    .map(|c| [c[0] as f32, c[1] as f32, c[2] as f32])
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        let order: Vec<usize> = match primitive.indices {
+            Some(i) => self
+                .read_accessor(i)?
+                .0
+                .iter()
+                .map(|i| *i as usize)
+                .collect(),
+            None => (0..vertices.len()).collect(),
+        };
+        let mut indices: Vec<[usize; 3]> = match primitive.mode {
+            4 => order.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+            5 => (0..order.len().saturating_sub(2))
+                .map(|i| {
+                    if i % 2 == 0 {
+                        [order[i], order[i + 1], order[i + 2]]
+                    } else {
+                        [order[i + 1], order[i], order[i + 2]]
+                    }
+                })
+                .collect(),
+            6 => (1..order.len().saturating_sub(1))
+                .map(|i| [order[0], order[i], order[i + 1]])
+                .collect(),
+            // points and lines have no surface to render
+            _ => return Ok(None),
+        };
+        // mirroring transforms flip the winding
+        if transform.determinant3() < 0.0 {
+            for triangle in indices.iter_mut() {
+                triangle.swap(1, 2);
+            }
+        }
+
+        let mesh = Mesh::new(
+            vertices,
+            indices,
+            normals,
+            uvs,
+            self.material(primitive.material)?,
+        )
+        .and_then(|m| m.with_colors(colors))
+        .map_err(|e| eyre!(e))?;
+        Ok(Some(mesh))
+    }
+
+    fn node_transform(node: &GltfNode) -> Matrix4 {
+        if let Some(matrix) = &node.matrix {
+            return Matrix4::from_column_major(matrix);
+        }
+        let t = node.translation.unwrap_or([0.0; 3]);
+        let r = node.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+        let s = node.scale.unwrap_or([1.0; 3]);
+        Matrix4::translation(Point3D::new(t[0], t[1], t[2]))
+            * Matrix4::rotation_quaternion(r)
+            * Matrix4::scale(Point3D::new(s[0], s[1], s[2]))
+    }
+
+    fn visit(
+        &self,
+        node_index: usize,
+        parent: &Matrix4,
+        depth: usize,
+        scene: &mut ImportedScene,
+    ) -> Result<()> {
+        if depth > 64 {
+            bail!("node hierarchy too deep, is it cyclic?");
+        }
+        let node = self
+            .gltf
+            .nodes
+            .get(node_index)
+            .ok_or_else(|| eyre!("node {} out of range", node_index))?;
+        let transform = *parent * Self::node_transform(node);
+        if let Some(mesh_index) = node.mesh {
+            let mesh = self
+                .gltf
+                .meshes
+                .get(mesh_index)
+                .ok_or_else(|| eyre!("mesh {} out of range", mesh_index))?;
+            for primitive in &mesh.primitives {
+                if let Some(m) = self.primitive(primitive, &transform)? {
+                    scene.objects.push(Body::Mesh(m));
+                }
+            }
+        }
+        if let (Some(camera_index), None) = (node.camera, &scene.camera) {
+            let perspective = self
+                .gltf
+                .cameras
+                .get(camera_index)
+                .and_then(|c| c.perspective.as_ref());
+            if let Some(p) = perspective {
+                // glTF cameras look down their local -z with +y up
+                let look_from = transform.transform_point(Point3D::new(0.0, 0.0, 0.0));
+                let forward = transform.transform_vector(Point3D::new(0.0, 0.0, -1.0));
+                let vup = transform.transform_vector(Point3D::new(0.0, 1.0, 0.0));
+                scene.camera = Some((look_from, look_from + forward, vup, p.yfov.to_degrees()));
+                scene.aspect = p.aspect_ratio;
+            }
+        }
+        for child in &node.children {
+            self.visit(*child, &transform, depth + 1, scene)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct ImportedScene {
+    objects: Vec<Body>,
+    camera: Option<(Point3D, Point3D, Point3D, f64)>,
+    aspect: Option<f64>,
+}
+
+// Loads a .gltf or .glb file and turns its default scene into a render
+// configuration. Meshes are baked into world space, the first camera found
+// is used, and sampling settings get the same defaults as our examples.
+pub fn load_gltf(path: &Path) -> Result<Config> {
+    let data = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    let (json, bin) = if data.starts_with(b"glTF") {
+        parse_glb(&data)?
+    } else {
+        (data.as_slice(), None)
+    };
+    let gltf: Gltf = serde_json::from_slice(json).context("parsing glTF json")?;
+
+    let mut buffers = Vec::with_capacity(gltf.buffers.len());
+    for (i, buffer) in gltf.buffers.iter().enumerate() {
+        let bytes = match (&buffer.uri, bin) {
+            (Some(uri), _) => load_uri(uri, base)?,
+            // the first buffer of a GLB file may refer to the BIN chunk
+            (None, Some(bin)) if i == 0 => bin.to_vec(),
+            (None, _) => bail!("buffer {} has no data", i),
+        };
+        if bytes.len() < buffer.byte_length {
+            bail!("buffer {} is shorter than its byteLength", i);
+        }
+        buffers.push(bytes);
+    }
+
+    let loader = GltfLoader {
+        gltf,
+        buffers,
+        base,
+    };
+    let roots: Vec<usize> = match loader.gltf.scenes.get(loader.gltf.scene.unwrap_or(0)) {
+        Some(scene) => scene.nodes.clone(),
+        None => (0..loader.gltf.nodes.len()).collect(),
+    };
+    let mut scene = ImportedScene::default();
+    for root in roots {
+        loader.visit(root, &Matrix4::identity(), 0, &mut scene)?;
+    }
+
+    let width = 800;
+    let aspect = scene.aspect.unwrap_or(800.0 / 600.0);
+    let height = (width as f64 / aspect).round() as usize;
+    let camera = match scene.camera {
+        Some((look_from, look_at, vup, vfov)) => Camera::new(look_from, look_at, vup, vfov, aspect),
+        None => default_camera(&scene.objects, aspect),
+    };
+    Ok(Config {
+        width,
+        height,
+        samples_per_pixel: 64,
+        max_depth: 50,
+        sky: Some(Sky::new_default_sky()),
+        camera,
+        objects: scene.objects,
+        lights: Vec::new(),
+        brute_force: false,
+        geometries: BTreeMap::new(),
+        animation: None,
+    })
+}
+
+// Frames the bounding box of the scene from the front when the file has no
+// camera.
+fn default_camera(objects: &[Body], aspect: f64) -> Camera {
+    let bounds = objects
+        .iter()
+        .filter_map(|o| o.bounding_box())
+        .reduce(|a, b| a.surrounding(&b))
+        .unwrap_or_else(|| Aabb::new(Point3D::new(-1.0, -1.0, -1.0), Point3D::new(1.0, 1.0, 1.0)));
+    let center = bounds.centroid();
+    let radius = ((bounds.max - bounds.min).length() / 2.0).max(1e-3);
+    let vfov: f64 = 40.0;
+    let distance = radius / (vfov.to_radians() / 2.0).sin();
+    Camera::new(
+        center + Point3D::new(0.0, 0.0, distance),
+        center,
+        Point3D::new(0.0, 1.0, 0.0),
+        vfov,
+        aspect,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(decode_base64("aGVsbG8gd29ybGQ=").unwrap(), b"hello world");
+        assert!(decode_base64("a*b").is_err());
+    }
+
+    #[test]
+    fn test_load_gltf_triangle() {
+        let config = load_gltf(Path::new("data/triangle.gltf")).unwrap();
+        assert_eq!(config.objects.len(), 1);
+        // the node translates the triangle by -1 along z
+        let ray = Ray::new(Point3D::new(0.25, 0.25, 1.0), Point3D::new(0.0, 0.0, -1.0));
+        let hit = config.objects[0].hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-9);
+        assert!(matches!(hit.material, Material::Metal(_)));
+        assert_eq!(config.camera.origin, Point3D::new(0.0, 0.0, 3.0));
+    }
+
+    #[test]
+    fn test_load_glb_triangle() {
+        let json = fs::read("data/triangle.gltf").unwrap();
+        let gltf: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        let uri = gltf["buffers"][0]["uri"].as_str().unwrap();
+        let bin = decode_base64(uri.split_once(";base64,").unwrap().1).unwrap();
+        let mut glb_json = gltf.clone();
+        glb_json["buffers"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("uri");
+        let mut json_chunk = serde_json::to_vec(&glb_json).unwrap();
+        json_chunk.resize(json_chunk.len().next_multiple_of(4), b' ');
+        let mut glb = Vec::new();
+        glb.extend(GLB_MAGIC.to_le_bytes());
+        glb.extend(2u32.to_le_bytes());
+        glb.extend(((12 + 8 + json_chunk.len() + 8 + bin.len()) as u32).to_le_bytes());
+        glb.extend((json_chunk.len() as u32).to_le_bytes());
+        glb.extend(GLB_CHUNK_JSON.to_le_bytes());
+        glb.extend(&json_chunk);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(GLB_CHUNK_BIN.to_le_bytes());
+        glb.extend(&bin);
+        fs::write("/tmp/triangle.glb", glb).unwrap();
+
+        let config = load_gltf(Path::new("/tmp/triangle.glb")).unwrap();
+        assert_eq!(config.objects.len(), 1);
+    }
+
+    #[test]
+    fn test_load_gltf_texture() {
+        let json = fs::read("data/triangle.gltf").unwrap();
+        let mut gltf: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        let pbr = &mut gltf["materials"][0]["pbrMetallicRoughness"];
+        pbr["metallicFactor"] = serde_json::json!(0.0);
+        pbr["baseColorFactor"] = serde_json::json!([0.5, 1.0, 1.0, 1.0]);
+        pbr["baseColorTexture"] = serde_json::json!({"index": 0});
+        gltf["textures"] = serde_json::json!([{"source": 0}]);
+        gltf["images"] = serde_json::json!([{"uri": "/tmp/test_gltf_texture.png"}]);
+        image::RgbImage::from_pixel(2, 2, image::Rgb([255, 0, 0]))
+            .save("/tmp/test_gltf_texture.png")
+            .unwrap();
+        fs::write("/tmp/textured.gltf", serde_json::to_vec(&gltf).unwrap()).unwrap();
+        let config = load_gltf(Path::new("/tmp/textured.gltf")).unwrap();
+        match config.objects[0].material() {
+            // the base color factor tints the texture
+            Some(Material::Texture(t)) => assert_eq!(t.get_albedo(0.5, 0.5).red, 0.5),
+            m => panic!("Wrong material {:?}", m),
+        }
+
+        // a missing image is an error, not a crash
+        gltf["images"] = serde_json::json!([{"uri": "/tmp/no_such_texture.png"}]);
+        fs::write("/tmp/textured.gltf", serde_json::to_vec(&gltf).unwrap()).unwrap();
+        assert!(load_gltf(Path::new("/tmp/textured.gltf")).is_err());
+    }
+
+    #[test]
+    fn test_load_gltf_mistyped_attributes() {
+        let json = fs::read("data/triangle.gltf").unwrap();
+        let gltf: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        // positions read as pairs, and colors read from the scalar indices
+        let mut positions = gltf.clone();
+        positions["accessors"][0]["type"] = serde_json::json!("VEC2");
+        let mut colors = gltf.clone();
+        colors["meshes"][0]["primitives"][0]["attributes"]["COLOR_0"] = serde_json::json!(1);
+        for (name, mistyped) in [("positions", positions), ("colors", colors)] {
+            let path = format!("/tmp/mistyped_{}.gltf", name);
+            fs::write(&path, serde_json::to_vec(&mistyped).unwrap()).unwrap();
+            assert!(load_gltf(Path::new(&path)).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_load_gltf_emissive_strength() {
+        let json = fs::read("data/triangle.gltf").unwrap();
+        let mut gltf: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        gltf["materials"][0]["emissiveFactor"] = serde_json::json!([1.0, 1.0, 1.0]);
+        gltf["materials"][0]["extensions"] =
+            serde_json::json!({"KHR_materials_emissive_strength": {"emissiveStrength": 5.0}});
+        fs::write("/tmp/emissive.gltf", serde_json::to_vec(&gltf).unwrap()).unwrap();
+        let config = load_gltf(Path::new("/tmp/emissive.gltf")).unwrap();
+        match config.objects[0].material() {
+            Some(Material::Emissive(e)) => assert_eq!(e.intensity, 5.0),
+            m => panic!("Wrong material {:?}", m),
+        }
+
+        gltf["materials"][0]["extensions"] =
+            serde_json::json!({"KHR_materials_emissive_strength": {"emissiveStrength": -5.0}});
+        fs::write("/tmp/emissive.gltf", serde_json::to_vec(&gltf).unwrap()).unwrap();
+        assert!(load_gltf(Path::new("/tmp/emissive.gltf")).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use eyre::*;
+use std::fs;
+use std::path::Path;
+
+use crate::point3d::Point3D;
+
+// Scalar values at the centers of the voxels of a regular 3D grid, indexed
+// [x][y][z] with z changing fastest (NumPy's default order).
+#[derive(Debug, Clone, Default)]
+pub struct Grid {
+    pub resolution: [usize; 3],
+    pub values: Vec<f32>,
+}
+
+impl Grid {
+    pub fn new(resolution: [usize; 3], values: Vec<f32>) -> Result<Grid> {
+        let [nx, ny, nz] = resolution;
+        if nx * ny * nz != values.len() {
+            bail!(
+                "{} values do not fill a {}x{}x{} grid",
+                values.len(),
+                nx,
+                ny,
+                nz
+            );
+        }
+        if values.is_empty() {
+            bail!("empty grid");
+        }
+        if let Some(value) = values.iter().find(|v| !v.is_finite()) {
+            bail!("grid value {} is not a finite number", value);
+        }
+        Ok(Grid { resolution, values })
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [_, ny, nz] = self.resolution;
+        self.values[(x * ny + y) * nz + z] as f64
+    }
+
+    pub fn max(&self) -> f64 {
+        self.values.iter().fold(0.0f32, |a, &b| a.max(b)) as f64
+    }
+
+    // Trilinear interpolation at `p` in [0, 1]^3, the unit cube spanned by
+    // the grid. Values are clamped to the outermost voxels.
+    pub fn sample(&self, p: Point3D) -> f64 {
+        let cell = |axis: usize| {
+            let n = self.resolution[axis];
+            let g = (p[axis] * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            let i = (g.floor() as usize).min(n - 1);
+            (i, (i + 1).min(n - 1), g - i as f64)
+        };
+        let (x0, x1, fx) = cell(0);
+        let (y0, y1, fy) = cell(1);
+        let (z0, z1, fz) = cell(2);
+        let lerp = |a: f64, b: f64, f: f64| a + (b - a) * f;
+        let row = |y: usize, z: usize| lerp(self.get(x0, y, z), self.get(x1, y, z), fx);
+        let plane = |z: usize| lerp(row(y0, z), row(y1, z), fy);
+        lerp(plane(z0), plane(z1), fz)
+    }
+}
+
+// Value of `key` in the Python dict literal of a .npy header.
+fn header_field<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{}'", key))? + key.len() + 2;
+    let rest = header[start..].trim_start().strip_prefix(':')?.trim_start();
+    let end = if rest.starts_with('(') {
+        rest.find(')')? + 1
+    } else {
+        rest.find([',', '}'])?
+    };
+    Some(rest[..end].trim())
+}
+
+// NumPy .npy file holding a 3D array of 32 or 64 bit floats.
+pub fn parse_npy(data: &[u8]) -> Result<Grid> {
+    if data.len() < 10 || &data[..6] != b"\x93NUMPY" {
+        bail!("not a .npy file");
+    }
+    let (header_len, header_start) = match data[6] {
+        1 => (u16::from_le_bytes([data[8], data[9]]) as usize, 10),
+        2 | 3 if data.len() >= 12 => (
+            u32::from_le_bytes([data[8], data[9], data[10], data[11]]) as usize,
+            12,
+        ),
+        version => bail!("unsupported .npy version {}", version),
+    };
+    let body_start = header_start + header_len;
+    if data.len() < body_start {
+        bail!("truncated .npy header");
+    }
+    let header = std::str::from_utf8(&data[header_start..body_start]).context("invalid header")?;
+
+    let descr = header_field(header, "descr").context("no descr in header")?;
+    let size = match descr.trim_matches(|c| c == '\'' || c == '"') {
+        "<f4" => 4,
+        "<f8" => 8,
+        other => bail!("unsupported dtype {}, expected <f4 or <f8", other),
+    };
+    let fortran_order = header_field(header, "fortran_order") == Some("True");
+    let shape = header_field(header, "shape").context("no shape in header")?;
+    let shape = shape
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<usize>())
+        .collect::<std::result::Result<Vec<usize>, _>>()
+        .context("invalid shape")?;
+    let [nx, ny, nz] = shape[..] else {
+        bail!("expected a 3D array, got shape {:?}", shape);
+    };
+
+    let body = &data[body_start..];
+    if body.len() < nx * ny * nz * size {
+        bail!("truncated .npy data");
+    }
+    let read = |i: usize| {
+        let b = &body[i * size..(i + 1) * size];
+        if size == 4 {
+            f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        } else {
+            f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32
+        }
+    };
+    let mut values = Vec::with_capacity(nx * ny * nz);
+    for x in 0..nx {
+        for y in 0..ny {
+            for z in 0..nz {
+                // Fortran order has x changing fastest
+                let i = if fortran_order {
+                    (z * ny + y) * nx + x
+                } else {
+                    (x * ny + y) * nz + z
+                };
+                values.push(read(i));
+            }
+        }
+    }
+    Grid::new([nx, ny, nz], values)
+}
+
+// Headerless little-endian 32 bit floats in the same order as `Grid`.
+pub fn parse_raw(data: &[u8], resolution: [usize; 3]) -> Result<Grid> {
+    let values = data
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    Grid::new(resolution, values)
+}
+
+// Loads a .npy file, or a raw file of floats when `resolution` is given.
+pub fn load_grid(path: &Path, resolution: Option<[usize; 3]>) -> Result<Grid> {
+    let data = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let grid = match resolution {
+        Some(resolution) => parse_raw(&data, resolution),
+        None => parse_npy(&data),
+    };
+    grid.with_context(|| format!("parsing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn npy(descr: &str, fortran_order: bool, shape: &str, body: &[u8]) -> Vec<u8> {
+        let mut header = format!(
+            "{{'descr': '{}', 'fortran_order': {}, 'shape': {}, }}",
+            descr,
+            if fortran_order { "True" } else { "False" },
+            shape
+        );
+        // the header is padded so the data starts at a multiple of 64
+        while (10 + header.len() + 1) % 64 != 0 {
+            header.push(' ');
+        }
+        header.push('\n');
+        let mut data = b"\x93NUMPY\x01\x00".to_vec();
+        data.extend((header.len() as u16).to_le_bytes());
+        data.extend(header.as_bytes());
+        data.extend(body);
+        data
+    }
+
+    #[test]
+    fn test_parse_npy() {
+        let values: Vec<u8> = (0..8).flat_map(|i| (i as f32).to_le_bytes()).collect();
+        let grid = parse_npy(&npy("<f4", false, "(2, 2, 2)", &values)).unwrap();
+        assert_eq!(grid.resolution, [2, 2, 2]);
+        assert_eq!(grid.get(1, 0, 1), 5.0);
+        assert_eq!(grid.max(), 7.0);
+
+        let values: Vec<u8> = (0..6).flat_map(|i| (i as f64).to_le_bytes()).collect();
+        let grid = parse_npy(&npy("<f8", true, "(3, 2, 1)", &values)).unwrap();
+        assert_eq!(grid.resolution, [3, 2, 1]);
+        assert_eq!(grid.get(1, 1, 0), 4.0);
+
+        assert!(parse_npy(&npy("<f4", false, "(8,)", &[0; 32])).is_err());
+        assert!(parse_npy(&npy("<i4", false, "(2, 2, 2)", &[0; 32])).is_err());
+        assert!(parse_npy(&npy("<f4", false, "(2, 2, 2)", &[0; 16])).is_err());
+    }
+
+    #[test]
+    fn test_sample() {
+        let values: Vec<u8> = [0.0f32, 1.0].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let grid = parse_raw(&values, [2, 1, 1]).unwrap();
+        // voxel centers are at x = 0.25 and x = 0.75
+        assert_eq!(grid.sample(Point3D::new(0.1, 0.5, 0.5)), 0.0);
+        assert_eq!(grid.sample(Point3D::new(0.5, 0.0, 1.0)), 0.5);
+        assert_eq!(grid.sample(Point3D::new(0.625, 0.5, 0.5)), 0.75);
+        assert!(parse_raw(&values, [2, 2, 1]).is_err());
+        assert!(Grid::new([2, 1, 1], vec![0.0, f32::NAN]).is_err());
+    }
+}
@@ -0,0 +1,34 @@
+use eyre::*;
+use image::codecs::hdr::HdrDecoder;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// Pixels of an image in linear light, row by row from the top, with its
+// width and height. Radiance .hdr files are read with their own decoder
+// as the image crate maps them to 8 bits on the way through `open`.
+pub fn load_hdr_image(path: &Path) -> Result<(Vec<[f32; 3]>, usize, usize)> {
+    let is_radiance = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("hdr") || e.eq_ignore_ascii_case("pic"));
+    if is_radiance {
+        let file = File::open(path).with_context(|| format!("{}", path.display()))?;
+        let decoder =
+            HdrDecoder::new(BufReader::new(file)).with_context(|| format!("{}", path.display()))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder
+            .read_image_hdr()
+            .with_context(|| format!("{}", path.display()))?;
+        return Ok((
+            pixels.into_iter().map(|pixel| pixel.0).collect(),
+            metadata.width as usize,
+            metadata.height as usize,
+        ));
+    }
+    let image = image::open(path)
+        .with_context(|| format!("{}", path.display()))?
+        .into_rgb32f();
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    Ok((image.pixels().map(|pixel| pixel.0).collect(), width, height))
+}
@@ -0,0 +1,229 @@
+use eyre::*;
+use std::fs;
+use std::path::Path;
+
+// Candela values of a light fixture by direction, from an IESNA LM-63
+// photometric file. Only type C photometry, the one used for nearly all
+// building fixtures, is supported: vertical angles go from 0 straight down
+// to 180 straight up and horizontal angles turn around the vertical axis.
+#[derive(Debug, Clone, Default)]
+pub struct IesProfile {
+    pub vertical_angles: Vec<f64>,
+    pub horizontal_angles: Vec<f64>,
+    // For every horizontal angle the values at all vertical angles.
+    pub candela: Vec<Vec<f64>>,
+}
+
+pub fn load_ies(path: &Path) -> Result<IesProfile> {
+    let text = fs::read_to_string(path).with_context(|| format!("{}", path.display()))?;
+    parse_ies(&text).with_context(|| format!("{}", path.display()))
+}
+
+pub fn parse_ies(text: &str) -> Result<IesProfile> {
+    let mut lines = text.lines();
+    let tilt = loop {
+        match lines.next() {
+            Some(line) if line.trim_start().starts_with("TILT=") => {
+                break line.trim_start()["TILT=".len()..].trim().to_string()
+            }
+            Some(_) => continue,
+            None => bail!("no TILT= line"),
+        }
+    };
+    let mut numbers = lines
+        .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+        .filter(|field| !field.is_empty())
+        .map(|field| {
+            field
+                .parse::<f64>()
+                .map_err(|e| eyre!("{:?} is not a number: {}", field, e))
+        });
+    let mut next = move || {
+        numbers
+            .next()
+            .unwrap_or_else(|| Err(eyre!("file ends early")))
+    };
+    match tilt.as_str() {
+        "NONE" => {}
+        "INCLUDE" => {
+            // the lamp to luminaire geometry, then tilt angles and factors
+            next()?;
+            let count = next()? as usize;
+            for _ in 0..2 * count {
+                next()?;
+            }
+        }
+        // a file name, its tilt data is not read
+        _ => bail!("TILT={} is not supported, only NONE or INCLUDE", tilt),
+    }
+
+    let _lamps = next()?;
+    let _lumens_per_lamp = next()?;
+    let multiplier = next()?;
+    let vertical_count = next()? as usize;
+    let horizontal_count = next()? as usize;
+    let photometric_type = next()? as usize;
+    let _units = next()?;
+    let (_width, _length, _height) = (next()?, next()?, next()?);
+    let ballast_factor = next()?;
+    let _ballast_lamp_factor = next()?;
+    let _input_watts = next()?;
+    if photometric_type != 1 {
+        bail!(
+            "photometric type {} is not supported, only type C (1)",
+            photometric_type
+        );
+    }
+    if vertical_count == 0 || horizontal_count == 0 {
+        bail!("no angles");
+    }
+
+    let mut angles = |count: usize| -> Result<Vec<f64>> {
+        let angles = (0..count).map(|_| next()).collect::<Result<Vec<f64>>>()?;
+        if angles.windows(2).any(|pair| pair[1] <= pair[0]) {
+            bail!("angles are not in increasing order");
+        }
+        Ok(angles)
+    };
+    let vertical_angles = angles(vertical_count)?;
+    let horizontal_angles = angles(horizontal_count)?;
+    let scale = multiplier * ballast_factor;
+    let candela = (0..horizontal_count)
+        .map(|_| {
+            (0..vertical_count)
+                .map(|_| Ok(next()? * scale))
+                .collect::<Result<Vec<f64>>>()
+        })
+        .collect::<Result<Vec<Vec<f64>>>>()?;
+    Ok(IesProfile {
+        vertical_angles,
+        horizontal_angles,
+        candela,
+    })
+}
+
+// Index of the angle at or below `angle` and the fraction of the way to
+// the next one, clamped to the ends of `angles`.
+fn bracket(angles: &[f64], angle: f64) -> (usize, usize, f64) {
+    let next = angles.partition_point(|&a| a <= angle);
+    if next == 0 {
+        return (0, 0, 0.0);
+    }
+    if next == angles.len() {
+        return (next - 1, next - 1, 0.0);
+    }
+    let (a, b) = (angles[next - 1], angles[next]);
+    (next - 1, next, (angle - a) / (b - a))
+}
+
+impl IesProfile {
+    pub fn max_candela(&self) -> f64 {
+        self.candela
+            .iter()
+            .flatten()
+            .fold(0.0, |a: f64, &b| a.max(b))
+    }
+
+    // Files only list the horizontal angles needed for the symmetry of
+    // the fixture, this maps any angle onto them.
+    fn fold_horizontal(&self, angle: f64) -> f64 {
+        let angle = angle.rem_euclid(360.0);
+        let first = self.horizontal_angles[0];
+        let last = self.horizontal_angles[self.horizontal_angles.len() - 1];
+        let mirror = |angle: f64, axis: f64| {
+            if angle > axis {
+                2.0 * axis - angle
+            } else {
+                angle
+            }
+        };
+        if last == 0.0 {
+            // the same all around
+            0.0
+        } else if last == 90.0 {
+            // symmetric in all four quadrants
+            mirror(mirror(angle, 180.0), 90.0)
+        } else if last == 180.0 {
+            // symmetric about the 0-180 degree plane
+            mirror(angle, 180.0)
+        } else if first == 90.0 && last == 270.0 {
+            // symmetric about the 90-270 degree plane
+            if angle < 90.0 {
+                180.0 - angle
+            } else if angle > 270.0 {
+                540.0 - angle
+            } else {
+                angle
+            }
+        } else {
+            angle
+        }
+    }
+
+    // Candela towards the vertical and horizontal angles in degrees,
+    // interpolated between the angles of the table. None is given off
+    // outside its vertical angles.
+    pub fn candela_at(&self, vertical: f64, horizontal: f64) -> f64 {
+        let first = self.vertical_angles[0];
+        let last = self.vertical_angles[self.vertical_angles.len() - 1];
+        if vertical < first || vertical > last {
+            return 0.0;
+        }
+        let (v0, v1, fv) = bracket(&self.vertical_angles, vertical);
+        let (h0, h1, fh) = bracket(&self.horizontal_angles, self.fold_horizontal(horizontal));
+        let lerp = |a: f64, b: f64, f: f64| a + (b - a) * f;
+        let column = |h: usize| lerp(self.candela[h][v0], self.candela[h][v1], fv);
+        lerp(column(h0), column(h1), fh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    const QUADRANT: &str = "IESNA:LM-63-2002
+[TEST] quadrant symmetric
+[MANUFAC] none
+TILT=INCLUDE
+1
+2
+0 90
+1.0 1.0
+1 1000 2.0 3 2 1 1 0.5 0.5 0.0
+1.0 1.0 20
+0 45 90
+0 90
+100 50 0
+200, 100, 0
+";
+
+    #[test]
+    fn test_parse_ies() {
+        let profile = parse_ies(QUADRANT).unwrap();
+        assert_eq!(profile.vertical_angles, vec![0.0, 45.0, 90.0]);
+        assert_eq!(profile.horizontal_angles, vec![0.0, 90.0]);
+        // the candela multiplier is applied
+        assert_eq!(profile.max_candela(), 400.0);
+        assert_approx_eq!(profile.candela_at(0.0, 0.0), 200.0);
+        assert_approx_eq!(profile.candela_at(22.5, 0.0), 150.0);
+        assert_approx_eq!(profile.candela_at(45.0, 45.0), 150.0);
+        // mirrored into the first quadrant
+        assert_approx_eq!(profile.candela_at(0.0, 270.0), 400.0);
+        assert_approx_eq!(profile.candela_at(0.0, 180.0), 200.0);
+        assert_approx_eq!(profile.candela_at(0.0, -45.0), 300.0);
+        // a downlight is dark above
+        assert_eq!(profile.candela_at(120.0, 0.0), 0.0);
+
+        let truncated = &QUADRANT[..QUADRANT.len() - 10];
+        assert!(parse_ies(truncated).is_err());
+        assert!(parse_ies("no tilt line").is_err());
+        let type_a = QUADRANT.replace("3 2 1 1", "3 2 3 1");
+        assert!(parse_ies(&type_a).is_err());
+        let tilt_file = QUADRANT.replace("TILT=INCLUDE", "TILT=lamp.tlt");
+        assert!(parse_ies(&tilt_file).is_err());
+
+        let profile = load_ies(Path::new("data/downlight.ies")).unwrap();
+        assert!(profile.candela_at(0.0, 0.0) > profile.candela_at(60.0, 0.0));
+    }
+}
@@ -0,0 +1,102 @@
+use crate::point3d::Point3D;
+
+pub mod gltf;
+pub mod grid;
+pub mod hdr;
+pub mod ies;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+// Splits a planar polygon into triangles by ear clipping, returning indices
+// into `polygon`. Handles concave polygons; degenerate input falls back to
+// a triangle fan.
+pub fn triangulate(polygon: &[Point3D]) -> Vec<[usize; 3]> {
+    let n = polygon.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    // Newell's method, robust for slightly non-planar polygons
+    let mut normal = Point3D::new(0.0, 0.0, 0.0);
+    for i in 0..n {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % n];
+        normal = normal
+            + Point3D::new(
+                (a.y() - b.y()) * (a.z() + b.z()),
+                (a.z() - b.z()) * (a.x() + b.x()),
+                (a.x() - b.x()) * (a.y() + b.y()),
+            );
+    }
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+    while remaining.len() > 3 && !normal.near_zero() {
+        let m = remaining.len();
+        let corner = |i: usize| {
+            (
+                remaining[(i + m - 1) % m],
+                remaining[i],
+                remaining[(i + 1) % m],
+            )
+        };
+        let ear = (0..m).find(|&i| {
+            let (a, b, c) = corner(i);
+            let (pa, pb, pc) = (polygon[a], polygon[b], polygon[c]);
+            if (pb - pa).cross(pc - pb).dot(normal) <= 0.0 {
+                return false; // reflex corner
+            }
+            !remaining.iter().any(|&j| {
+                j != a && j != b && j != c && point_in_triangle(polygon[j], pa, pb, pc, normal)
+            })
+        });
+        match ear {
+            Some(i) => {
+                let (a, b, c) = corner(i);
+                triangles.push([a, b, c]);
+                remaining.remove(i);
+            }
+            None => break,
+        }
+    }
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
+
+fn point_in_triangle(p: Point3D, a: Point3D, b: Point3D, c: Point3D, normal: Point3D) -> bool {
+    (b - a).cross(p - a).dot(normal) >= 0.0
+        && (c - b).cross(p - b).dot(normal) >= 0.0
+        && (a - c).cross(p - c).dot(normal) >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_triangulate_concave() {
+        // an L shape, the corner at index 3 is reflex
+        let polygon = vec![
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(2.0, 0.0, 0.0),
+            Point3D::new(2.0, 1.0, 0.0),
+            Point3D::new(1.0, 1.0, 0.0),
+            Point3D::new(1.0, 2.0, 0.0),
+            Point3D::new(0.0, 2.0, 0.0),
+        ];
+        let triangles = triangulate(&polygon);
+        assert_eq!(triangles.len(), 4);
+        let area: f64 = triangles
+            .iter()
+            .map(|[a, b, c]| {
+                (polygon[*b] - polygon[*a])
+                    .cross(polygon[*c] - polygon[*a])
+                    .length()
+                    / 2.0
+            })
+            .sum();
+        assert!((area - 3.0).abs() < 1e-9);
+    }
+}
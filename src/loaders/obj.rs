@@ -0,0 +1,443 @@
+use eyre::*;
+use palette::Srgb;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::loaders::triangulate;
+use crate::materials::{Glass, Lambertian, Material, Metal, Texture};
+use crate::point3d::Point3D;
+
+// One group of faces sharing a material, with attributes already unified
+// into a single index space so it can be turned into a `Mesh` directly.
+#[derive(Debug, Clone, Default)]
+pub struct ObjGroup {
+    pub name: String,
+    pub material: Option<String>,
+    pub vertices: Vec<Point3D>,
+    pub indices: Vec<[usize; 3]>,
+    pub normals: Vec<Point3D>,
+    pub uvs: Vec<[f64; 2]>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ObjModel {
+    pub groups: Vec<ObjGroup>,
+    pub material_libraries: Vec<String>,
+}
+
+// A face corner as written in the file: position, texcoord and normal
+// indices, already resolved to zero based indices.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct GroupBuilder {
+    group: ObjGroup,
+    corners: HashMap<Corner, usize>,
+    missing_normals: bool,
+    missing_uvs: bool,
+}
+
+impl GroupBuilder {
+    fn new(name: &str, material: Option<String>) -> GroupBuilder {
+        GroupBuilder {
+            group: ObjGroup {
+                name: name.to_string(),
+                material,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn corner(
+        &mut self,
+        corner: Corner,
+        positions: &[Point3D],
+        texcoords: &[[f64; 2]],
+        normals: &[Point3D],
+    ) -> usize {
+        if let Some(index) = self.corners.get(&corner) {
+            return *index;
+        }
+        let (v, vt, vn) = corner;
+        let index = self.group.vertices.len();
+        self.group.vertices.push(positions[v]);
+        match vt {
+            Some(vt) => self.group.uvs.push(texcoords[vt]),
+            None => self.missing_uvs = true,
+        }
+        match vn {
+            Some(vn) => self.group.normals.push(normals[vn]),
+            None => self.missing_normals = true,
+        }
+        self.corners.insert(corner, index);
+        index
+    }
+
+    fn finish(mut self) -> Option<ObjGroup> {
+        if self.group.indices.is_empty() {
+            return None;
+        }
+        // attributes only make sense if every vertex of the group has them
+        if self.missing_normals {
+            self.group.normals.clear();
+        }
+        if self.missing_uvs {
+            self.group.uvs.clear();
+        }
+        Some(self.group)
+    }
+}
+
+fn parse_floats<'a>(fields: impl Iterator<Item = &'a str>, line_number: usize) -> Result<Vec<f64>> {
+    fields
+        .map(|f| {
+            f.parse::<f64>()
+                .with_context(|| format!("line {}: invalid number {:?}", line_number, f))
+        })
+        .collect()
+}
+
+// OBJ indices are 1 based, negative values count back from the most
+// recently defined element.
+fn resolve_index(field: &str, count: usize, line_number: usize) -> Result<usize> {
+    let index: i64 = field
+        .parse()
+        .with_context(|| format!("line {}: invalid index {:?}", line_number, field))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        bail!("line {}: index {} out of range", line_number, index);
+    }
+    Ok(resolved as usize)
+}
+
+pub fn parse_obj(source: &str) -> Result<ObjModel> {
+    let mut positions = Vec::new();
+    let mut texcoords = Vec::new();
+    let mut normals = Vec::new();
+    let mut model = ObjModel::default();
+    let mut group_name = String::from("default");
+    let mut material = None;
+    let mut builder = GroupBuilder::new(&group_name, None);
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut fields = line.split_whitespace();
+        let keyword = match fields.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        match keyword {
+            "v" => {
+                let v = parse_floats(fields, line_number)?;
+                if v.len() < 3 {
+                    bail!("line {}: vertex needs 3 coordinates", line_number);
+                }
+                positions.push(Point3D::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let n = parse_floats(fields, line_number)?;
+                if n.len() < 3 {
+                    bail!("line {}: normal needs 3 coordinates", line_number);
+                }
+                normals.push(Point3D::new(n[0], n[1], n[2]).unit_vector());
+            }
+            "vt" => {
+                let t = parse_floats(fields, line_number)?;
+                if t.is_empty() {
+                    bail!("line {}: texture coordinate needs a value", line_number);
+                }
+                texcoords.push([t[0], t.get(1).copied().unwrap_or(0.0)]);
+            }
+            "f" => {
+                let mut face = Vec::new();
+                for field in fields {
+                    let mut parts = field.split('/');
+                    let v =
+                        resolve_index(parts.next().unwrap_or(""), positions.len(), line_number)?;
+                    let vt = match parts.next() {
+                        Some("") | None => None,
+                        Some(p) => Some(resolve_index(p, texcoords.len(), line_number)?),
+                    };
+                    let vn = match parts.next() {
+                        Some("") | None => None,
+                        Some(p) => Some(resolve_index(p, normals.len(), line_number)?),
+                    };
+                    face.push((v, vt, vn));
+                }
+                if face.len() < 3 {
+                    bail!("line {}: face needs at least 3 vertices", line_number);
+                }
+                let polygon: Vec<Point3D> = face.iter().map(|c| positions[c.0]).collect();
+                for [a, b, c] in triangulate(&polygon) {
+                    let a = builder.corner(face[a], &positions, &texcoords, &normals);
+                    let b = builder.corner(face[b], &positions, &texcoords, &normals);
+                    let c = builder.corner(face[c], &positions, &texcoords, &normals);
+                    builder.group.indices.push([a, b, c]);
+                }
+            }
+            "g" | "o" => {
+                group_name = fields.collect::<Vec<_>>().join(" ");
+                let finished = std::mem::replace(
+                    &mut builder,
+                    GroupBuilder::new(&group_name, material.clone()),
+                );
+                model.groups.extend(finished.finish());
+            }
+            "usemtl" => {
+                material = fields.next().map(|s| s.to_string());
+                let finished = std::mem::replace(
+                    &mut builder,
+                    GroupBuilder::new(&group_name, material.clone()),
+                );
+                model.groups.extend(finished.finish());
+            }
+            "mtllib" => model
+                .material_libraries
+                .extend(fields.map(|s| s.to_string())),
+            // smoothing groups, lines, points and free-form geometry are ignored
+            _ => {}
+        }
+    }
+    model.groups.extend(builder.finish());
+    Ok(model)
+}
+
+#[derive(Debug, Clone)]
+struct MtlMaterial {
+    diffuse: [f32; 3],
+    specular: [f32; 3],
+    shininess: f64,
+    index_of_refraction: f64,
+    dissolve: f64,
+    illum: u32,
+    diffuse_map: Option<String>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: [0.8, 0.8, 0.8],
+            specular: [0.0, 0.0, 0.0],
+            shininess: 0.0,
+            index_of_refraction: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+            diffuse_map: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    // Maps the Phong style MTL description onto the closest of our
+    // materials: transparent surfaces become glass, surfaces whose specular
+    // color dominates become metal and everything else is diffuse.
+    fn to_material(&self, base: &Path) -> Result<Material> {
+        let diffuse = Srgb::new(self.diffuse[0], self.diffuse[1], self.diffuse[2]);
+        let max = |c: [f32; 3]| c[0].max(c[1]).max(c[2]);
+        let material = if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7) {
+            Material::Glass(Glass::new(self.index_of_refraction))
+        } else if max(self.specular) > max(self.diffuse) || self.illum == 3 {
+            // Phong exponent to a roughness-like fuzz, sharp at high exponents
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt();
+            Material::Metal(Metal::new(
+                Srgb::new(self.specular[0], self.specular[1], self.specular[2]),
+                fuzz,
+            ))
+        } else {
+            match &self.diffuse_map {
+                Some(map) => {
+                    let path = base.join(map);
+                    Material::Texture(
+                        Texture::new(diffuse, &path.to_string_lossy(), 0.0)
+                            .map_err(|e| eyre!(e))?,
+                    )
+                }
+                _ => Material::Lambertian(Lambertian::new(diffuse)),
+            }
+        };
+        Ok(material)
+    }
+}
+
+fn parse_color<'a>(fields: impl Iterator<Item = &'a str>, line_number: usize) -> Result<[f32; 3]> {
+    let c = parse_floats(fields, line_number)?;
+    match c.len() {
+        1 => Ok([c[0] as f32; 3]),
+        n if n >= 3 => Ok([c[0] as f32, c[1] as f32, c[2] as f32]),
+        _ => bail!("line {}: color needs 1 or 3 components", line_number),
+    }
+}
+
+// Parses an MTL library. Texture paths are resolved relative to `base`.
+pub fn parse_mtl(source: &str, base: &Path) -> Result<HashMap<String, Material>> {
+    let mut parsed: Vec<(String, MtlMaterial)> = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut fields = line.split_whitespace();
+        let keyword = match fields.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        if keyword == "newmtl" {
+            let name = fields.collect::<Vec<_>>().join(" ");
+            parsed.push((name, MtlMaterial::default()));
+            continue;
+        }
+        let current = match parsed.last_mut() {
+            Some((_, m)) => m,
+            None => continue,
+        };
+        let scalar = |fields: std::str::SplitWhitespace| -> Result<f64> {
+            parse_floats(fields, line_number)?
+                .first()
+                .copied()
+                .ok_or_else(|| eyre!("line {}: missing value", line_number))
+        };
+        match keyword {
+            "Kd" => current.diffuse = parse_color(fields, line_number)?,
+            "Ks" => current.specular = parse_color(fields, line_number)?,
+            "Ns" => current.shininess = scalar(fields)?,
+            "Ni" => current.index_of_refraction = scalar(fields)?,
+            "d" => current.dissolve = scalar(fields)?,
+            "Tr" => current.dissolve = 1.0 - scalar(fields)?,
+            "illum" => current.illum = scalar(fields)? as u32,
+            // options such as -s or -o come before the file name
+            "map_Kd" => current.diffuse_map = fields.last().map(|s| s.to_string()),
+            _ => {}
+        }
+    }
+    parsed
+        .into_iter()
+        .map(|(name, m)| {
+            let material = m
+                .to_material(base)
+                .with_context(|| format!("material {}", name))?;
+            Ok((name, material))
+        })
+        .collect()
+}
+
+pub fn load_obj(path: &Path) -> Result<ObjModel> {
+    let source = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    parse_obj(&source).with_context(|| format!("parsing {}", path.display()))
+}
+
+// Loads every material library referenced by `model`, relative to the
+// directory of the OBJ file.
+pub fn load_obj_materials(path: &Path, model: &ObjModel) -> Result<HashMap<String, Material>> {
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials = HashMap::new();
+    for library in &model.material_libraries {
+        let mtl_path = base.join(library);
+        let source = fs::read_to_string(&mtl_path)
+            .with_context(|| format!("reading {}", mtl_path.display()))?;
+        let parsed =
+            parse_mtl(&source, base).with_context(|| format!("parsing {}", mtl_path.display()))?;
+        materials.extend(parsed);
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_obj() {
+        let source = "
+# a quad and a triangle in two groups
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+g quad
+usemtl red
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g tri
+f -4 -3 -2
+";
+        let model = parse_obj(source).unwrap();
+        assert_eq!(model.groups.len(), 2);
+        let quad = &model.groups[0];
+        assert_eq!(quad.name, "quad");
+        assert_eq!(quad.material, Some("red".to_string()));
+        assert_eq!(quad.vertices.len(), 4);
+        assert_eq!(quad.indices.len(), 2);
+        assert_eq!(quad.normals.len(), 4);
+        assert_eq!(quad.uvs.len(), 4);
+        let tri = &model.groups[1];
+        assert_eq!(tri.name, "tri");
+        assert_eq!(tri.indices, vec![[0, 1, 2]]);
+        assert!(tri.normals.is_empty());
+        assert!(tri.uvs.is_empty());
+        assert!(parse_obj("f 1 2 3").is_err());
+    }
+
+    #[test]
+    fn test_parse_mtl() {
+        let source = "
+newmtl red
+Kd 0.8 0.1 0.1
+newmtl mirror
+Kd 0.1 0.1 0.1
+Ks 0.9 0.9 0.9
+Ns 1000
+newmtl glass
+Ni 1.33
+d 0.2
+";
+        let materials = parse_mtl(source, Path::new("")).unwrap();
+        assert!(matches!(materials["red"], Material::Lambertian(_)));
+        assert!(matches!(materials["mirror"], Material::Metal(_)));
+        match &materials["glass"] {
+            Material::Glass(g) => assert_eq!(g.index_of_refraction, 1.33),
+            _ => panic!("Wrong material type"),
+        }
+
+        // grayscale maps become gray textures, missing ones are errors
+        let materials =
+            parse_mtl("newmtl ground\nmap_Kd terrain.jpg\n", Path::new("data")).unwrap();
+        match &materials["ground"] {
+            Material::Texture(t) => {
+                let albedo = t.get_albedo(0.5, 0.5);
+                assert_eq!(albedo.red, albedo.green);
+                assert_eq!(albedo.red, albedo.blue);
+            }
+            _ => panic!("Wrong material type"),
+        }
+        // Kd tints the map
+        let materials = parse_mtl(
+            "newmtl red\nKd 1 0 0\nmap_Kd terrain.jpg\n",
+            Path::new("data"),
+        )
+        .unwrap();
+        match &materials["red"] {
+            Material::Texture(t) => {
+                let albedo = t.get_albedo(0.5, 0.5);
+                assert!(albedo.red > 0.0);
+                assert_eq!((albedo.green, albedo.blue), (0.0, 0.0));
+            }
+            _ => panic!("Wrong material type"),
+        }
+        let error = parse_mtl("newmtl lost\nmap_Kd missing.jpg\n", Path::new("data")).unwrap_err();
+        let message = format!("{:#}", error);
+        assert!(
+            message.contains("lost") && message.contains("missing.jpg"),
+            "{}",
+            message
+        );
+    }
+}
@@ -0,0 +1,372 @@
+use eyre::*;
+use std::fs;
+use std::path::Path;
+
+use crate::loaders::triangulate;
+use crate::point3d::Point3D;
+
+#[derive(Debug, Clone, Default)]
+pub struct PlyModel {
+    pub vertices: Vec<Point3D>,
+    pub indices: Vec<[usize; 3]>,
+    pub normals: Vec<Point3D>,
+    pub uvs: Vec<[f64; 2]>,
+    pub colors: Vec<[f32; 3]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<ScalarType> {
+        Ok(match name {
+            "char" | "int8" => ScalarType::Int8,
+            "uchar" | "uint8" => ScalarType::UInt8,
+            "short" | "int16" => ScalarType::Int16,
+            "ushort" | "uint16" => ScalarType::UInt16,
+            "int" | "int32" => ScalarType::Int32,
+            "uint" | "uint32" => ScalarType::UInt32,
+            "float" | "float32" => ScalarType::Float32,
+            "double" | "float64" => ScalarType::Float64,
+            _ => bail!("unknown property type {:?}", name),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    // Colors stored as integers are scaled to [0, 1], floats are taken as is.
+    fn is_integer(&self) -> bool {
+        !matches!(self, ScalarType::Float32 | ScalarType::Float64)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PropertyKind {
+    Scalar(ScalarType),
+    List(ScalarType, ScalarType),
+}
+
+#[derive(Debug, Clone)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Reads scalar values either from whitespace separated text or from the
+// binary body, so element parsing doesn't care about the format.
+struct Reader<'a> {
+    format: Format,
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn next_token(&mut self) -> Result<&'a str> {
+        while self.position < self.data.len() && self.data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        let start = self.position;
+        while self.position < self.data.len() && !self.data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        if start == self.position {
+            bail!("unexpected end of file");
+        }
+        Ok(std::str::from_utf8(&self.data[start..self.position])?)
+    }
+
+    fn read(&mut self, scalar: ScalarType) -> Result<f64> {
+        if self.format == Format::Ascii {
+            let token = self.next_token()?;
+            return token
+                .parse::<f64>()
+                .with_context(|| format!("invalid number {:?}", token));
+        }
+        let size = scalar.size();
+        if self.position + size > self.data.len() {
+            bail!("unexpected end of file");
+        }
+        let mut bytes = [0u8; 8];
+        bytes[..size].copy_from_slice(&self.data[self.position..self.position + size]);
+        self.position += size;
+        if self.format == Format::BinaryBigEndian {
+            bytes[..size].reverse();
+        }
+        Ok(match scalar {
+            ScalarType::Int8 => bytes[0] as i8 as f64,
+            ScalarType::UInt8 => bytes[0] as f64,
+            ScalarType::Int16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarType::UInt16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarType::Int32 => {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+            }
+            ScalarType::UInt32 => {
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+            }
+            ScalarType::Float32 => {
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+            }
+            ScalarType::Float64 => f64::from_le_bytes(bytes),
+        })
+    }
+}
+
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut position = 0;
+    let mut first = true;
+    loop {
+        let end = data[position..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or_else(|| eyre!("missing end_header"))?;
+        let line = std::str::from_utf8(&data[position..position + end])?.trim();
+        position += end + 1;
+        if first {
+            if line != "ply" {
+                bail!("not a PLY file");
+            }
+            first = false;
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["format", f, _] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => bail!("unknown PLY format {:?}", f),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse()?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| eyre!("property before element"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::List(
+                        ScalarType::parse(count_type)?,
+                        ScalarType::parse(item_type)?,
+                    ),
+                });
+            }
+            ["property", scalar_type, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| eyre!("property before element"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::Scalar(ScalarType::parse(scalar_type)?),
+                });
+            }
+            ["end_header"] => break,
+            // comments, obj_info and blank lines
+            _ => {}
+        }
+    }
+    let format = format.ok_or_else(|| eyre!("missing format line"))?;
+    Ok((format, elements, position))
+}
+
+pub fn parse_ply(data: &[u8]) -> Result<PlyModel> {
+    let (format, elements, body_start) = parse_header(data)?;
+    let mut reader = Reader {
+        format,
+        data,
+        position: body_start,
+    };
+    let mut model = PlyModel::default();
+    for element in &elements {
+        let find = |name: &str| element.properties.iter().position(|p| p.name == name);
+        let position = [find("x"), find("y"), find("z")];
+        let normal = [find("nx"), find("ny"), find("nz")];
+        let uv = [
+            find("u")
+                .or_else(|| find("s"))
+                .or_else(|| find("texture_u")),
+            find("v")
+                .or_else(|| find("t"))
+                .or_else(|| find("texture_v")),
+        ];
+        let color = [find("red"), find("green"), find("blue")];
+        let indices = find("vertex_indices").or_else(|| find("vertex_index"));
+
+        for _ in 0..element.count {
+            let mut scalars = vec![0.0; element.properties.len()];
+            let mut list = Vec::new();
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.kind {
+                    PropertyKind::Scalar(t) => scalars[i] = reader.read(t)?,
+                    PropertyKind::List(count_type, item_type) => {
+                        let count = reader.read(count_type)? as usize;
+                        let values = (0..count)
+                            .map(|_| reader.read(item_type))
+                            .collect::<Result<Vec<f64>>>()?;
+                        if Some(i) == indices {
+                            list = values;
+                        }
+                    }
+                }
+            }
+            match element.name.as_str() {
+                "vertex" => {
+                    if let [Some(x), Some(y), Some(z)] = position {
+                        model
+                            .vertices
+                            .push(Point3D::new(scalars[x], scalars[y], scalars[z]));
+                    }
+                    if let [Some(x), Some(y), Some(z)] = normal {
+                        model
+                            .normals
+                            .push(Point3D::new(scalars[x], scalars[y], scalars[z]).unit_vector());
+                    }
+                    if let [Some(u), Some(v)] = uv {
+                        model.uvs.push([scalars[u], scalars[v]]);
+                    }
+                    if let [Some(r), Some(g), Some(b)] = color {
+                        let channel = |i: usize| match element.properties[i].kind {
+                            PropertyKind::Scalar(t) if t.is_integer() => {
+                                (scalars[i] / 255.0) as f32
+                            }
+                            _ => scalars[i] as f32,
+                        };
+                        model.colors.push([channel(r), channel(g), channel(b)]);
+                    }
+                }
+                "face" => {
+                    let face = list.iter().map(|i| *i as usize).collect::<Vec<usize>>();
+                    if let Some(i) = face.iter().find(|i| **i >= model.vertices.len()) {
+                        bail!("face index {} out of range", i);
+                    }
+                    let polygon: Vec<Point3D> = face.iter().map(|i| model.vertices[*i]).collect();
+                    for [a, b, c] in triangulate(&polygon) {
+                        model.indices.push([face[a], face[b], face[c]]);
+                    }
+                }
+                // edges, materials and application specific elements
+                _ => {}
+            }
+        }
+    }
+    Ok(model)
+}
+
+pub fn load_ply(path: &Path) -> Result<PlyModel> {
+    let data = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    parse_ply(&data).with_context(|| format!("parsing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII_PLY: &str = "ply
+format ascii 1.0
+comment a single colored quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+
+    fn binary_ply(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian {
+            "binary_big_endian"
+        } else {
+            "binary_little_endian"
+        };
+        let mut data = format!(
+            "ply\nformat {} 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar uint vertex_indices\nend_header\n",
+            format
+        )
+        .into_bytes();
+        for v in [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            for c in v {
+                data.extend(if big_endian {
+                    c.to_be_bytes()
+                } else {
+                    c.to_le_bytes()
+                });
+            }
+        }
+        data.push(3);
+        for i in [0u32, 1, 2] {
+            data.extend(if big_endian {
+                i.to_be_bytes()
+            } else {
+                i.to_le_bytes()
+            });
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_ascii_ply() {
+        let model = parse_ply(ASCII_PLY.as_bytes()).unwrap();
+        assert_eq!(model.vertices.len(), 4);
+        assert_eq!(model.indices.len(), 2);
+        assert_eq!(model.colors[0], [1.0, 0.0, 0.0]);
+        assert_eq!(model.colors[3], [1.0, 1.0, 1.0]);
+        assert!(model.normals.is_empty());
+    }
+
+    #[test]
+    fn test_parse_binary_ply() {
+        for big_endian in [false, true] {
+            let model = parse_ply(&binary_ply(big_endian)).unwrap();
+            assert_eq!(model.vertices[1], Point3D::new(1.0, 0.0, 0.0));
+            assert_eq!(model.indices, vec![[0, 1, 2]]);
+        }
+        let mut truncated = binary_ply(false);
+        truncated.truncate(truncated.len() - 2);
+        assert!(parse_ply(&truncated).is_err());
+    }
+}
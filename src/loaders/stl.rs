@@ -0,0 +1,162 @@
+use eyre::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::point3d::Point3D;
+
+// STL stores every triangle with its own copy of the corners; identical
+// corners are merged so the result can be used as an indexed mesh.
+#[derive(Debug, Clone, Default)]
+pub struct StlModel {
+    pub vertices: Vec<Point3D>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+#[derive(Default)]
+struct StlBuilder {
+    model: StlModel,
+    seen: HashMap<[u64; 3], usize>,
+}
+
+impl StlBuilder {
+    fn vertex(&mut self, p: Point3D) -> usize {
+        let key = [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+        let vertices = &mut self.model.vertices;
+        *self.seen.entry(key).or_insert_with(|| {
+            vertices.push(p);
+            vertices.len() - 1
+        })
+    }
+
+    fn triangle(&mut self, corners: [Point3D; 3]) {
+        let [a, b, c] = corners.map(|p| self.vertex(p));
+        self.model.indices.push([a, b, c]);
+    }
+}
+
+fn is_binary(data: &[u8]) -> bool {
+    // Binary files may also start with "solid", so rely on the size implied
+    // by the triangle count instead.
+    if data.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    data.len() == 84 + count * 50
+}
+
+fn parse_binary_stl(data: &[u8]) -> Result<StlModel> {
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    let mut builder = StlBuilder::default();
+    let read = |offset: usize| {
+        f32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ]) as f64
+    };
+    for i in 0..count {
+        // skip the 12 byte facet normal, it is recomputed from the winding
+        let base = 84 + i * 50 + 12;
+        let corner = |j: usize| {
+            let offset = base + j * 12;
+            Point3D::new(read(offset), read(offset + 4), read(offset + 8))
+        };
+        builder.triangle([corner(0), corner(1), corner(2)]);
+    }
+    Ok(builder.model)
+}
+
+fn parse_ascii_stl(source: &str) -> Result<StlModel> {
+    let mut builder = StlBuilder::default();
+    let mut corners = Vec::with_capacity(3);
+    for (i, line) in source.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("vertex") => {
+                let v = fields
+                    .map(|f| f.parse::<f64>())
+                    .collect::<std::result::Result<Vec<f64>, _>>()
+                    .with_context(|| format!("line {}: invalid vertex", i + 1))?;
+                if v.len() != 3 {
+                    bail!("line {}: vertex needs 3 coordinates", i + 1);
+                }
+                corners.push(Point3D::new(v[0], v[1], v[2]));
+            }
+            Some("endloop") => {
+                // facets with more than 3 corners are not valid STL
+                if corners.len() != 3 {
+                    bail!("line {}: facet with {} vertices", i + 1, corners.len());
+                }
+                builder.triangle([corners[0], corners[1], corners[2]]);
+                corners.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(builder.model)
+}
+
+pub fn parse_stl(data: &[u8]) -> Result<StlModel> {
+    if is_binary(data) {
+        parse_binary_stl(data)
+    } else {
+        let source = std::str::from_utf8(data).context("not a binary or ASCII STL file")?;
+        if !source.trim_start().starts_with("solid") {
+            bail!("not a binary or ASCII STL file");
+        }
+        parse_ascii_stl(source)
+    }
+}
+
+pub fn load_stl(path: &Path) -> Result<StlModel> {
+    let data = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    parse_stl(&data).with_context(|| format!("parsing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ascii_stl() {
+        let source = "solid square
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 1 1 0
+  endloop
+endfacet
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 1 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid square
+";
+        let model = parse_stl(source.as_bytes()).unwrap();
+        assert_eq!(model.vertices.len(), 4);
+        assert_eq!(model.indices, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn test_parse_binary_stl() {
+        // the header deliberately starts with "solid" like many exporters do
+        let mut data = b"solid binary".to_vec();
+        data.resize(80, 0);
+        data.extend(1u32.to_le_bytes());
+        for c in [
+            0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+        ] {
+            data.extend(c.to_le_bytes());
+        }
+        data.extend([0, 0]);
+        let model = parse_stl(&data).unwrap();
+        assert_eq!(model.vertices[1], Point3D::new(1.0, 0.0, 0.0));
+        assert_eq!(model.indices, vec![[0, 1, 2]]);
+    }
+}
@@ -1,37 +1,241 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use common::{info, setup_logs, LogLevel};
 use eyre::*;
 use raytracer::config::Config;
+use raytracer::loaders::gltf::load_gltf;
 use raytracer::raytracer::render;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[clap(
     version = "1.0",
     author = "Jakku Sakura",
-    about = "Render scenes using a raytracer"
+    about = "Render scenes using a raytracer",
+    args_conflicts_with_subcommands = true,
+    arg_required_else_help = true
 )]
 struct Arguments {
-    #[clap(help = "Sets the path to the configuration file")]
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(flatten)]
+    scene: Option<SceneArguments>,
+
+    #[clap(
+        long,
+        value_parser = parse_frames,
+        help = "Renders frames N..M (or just N) of the scene's animation, all of them by default. %04d in the output file is replaced by the frame number, else it is added to the name"
+    )]
+    frames: Option<(usize, usize)>,
+}
+
+#[derive(Args, Debug)]
+struct SceneArguments {
+    #[clap(help = "Sets the path to the configuration file, or a .gltf/.glb scene")]
     config_file: PathBuf,
 
     #[clap(help = "Sets the path to the output file")]
     output_file: PathBuf,
+
+    #[clap(
+        long,
+        help = "Intersect every object for every ray instead of using a BVH"
+    )]
+    brute_force: bool,
+
+    #[clap(long, help = "Overrides the image width of the scene")]
+    width: Option<usize>,
+
+    #[clap(long, help = "Overrides the image height of the scene")]
+    height: Option<usize>,
+
+    #[clap(long, help = "Overrides the samples per pixel of the scene")]
+    samples_per_pixel: Option<u32>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[clap(
+        about = "Orbits the camera around look_at and renders the frames, numbered like with --frames"
+    )]
+    Turntable {
+        #[clap(flatten)]
+        scene: SceneArguments,
+
+        #[clap(long, default_value_t = 36, help = "Number of frames")]
+        frames: usize,
+
+        #[clap(
+            long,
+            default_value_t = 360.0,
+            help = "Degrees to turn over all frames"
+        )]
+        degrees: f64,
+
+        #[clap(
+            long,
+            help = "Distance from look_at, by default that of the scene's camera"
+        )]
+        radius: Option<f64>,
+
+        #[clap(
+            long,
+            help = "Degrees above the plane through look_at at right angles to vup, by default that of the scene's camera"
+        )]
+        elevation: Option<f64>,
+    },
+}
+
+fn parse_frames(value: &str) -> std::result::Result<(usize, usize), String> {
+    let parse = |s: &str| {
+        s.trim()
+            .parse::<usize>()
+            .map_err(|e| format!("{:?}: {}", s, e))
+    };
+    let (first, last) = match value.split_once("..") {
+        Some((first, last)) => (parse(first)?, parse(last)?),
+        None => (parse(value)?, parse(value)?),
+    };
+    if last < first {
+        return Err(format!("{} comes before {}", last, first));
+    }
+    std::result::Result::Ok((first, last))
+}
+
+fn frame_path(output: &Path, frame: usize) -> PathBuf {
+    let name = output.to_string_lossy();
+    if name.contains("%04d") {
+        return PathBuf::from(name.replace("%04d", &format!("{:04}", frame)));
+    }
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    match output.extension() {
+        Some(ext) => {
+            output.with_file_name(format!("{}_{:04}.{}", stem, frame, ext.to_string_lossy()))
+        }
+        None => output.with_file_name(format!("{}_{:04}", stem, frame)),
+    }
+}
+
+fn load_scene(path: &Path) -> Result<Config> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gltf") | Some("glb") => load_gltf(path).context("Unable to import glTF scene"),
+        _ => {
+            let json = fs::read(path).context("Unable to read config file.")?;
+            serde_json::from_slice(&json).context("Unable to parse config json")
+        }
+    }
+}
+
+fn load_scene_with_overrides(args: &SceneArguments) -> Result<Config> {
+    let mut scene = load_scene(&args.config_file)?;
+    scene.brute_force |= args.brute_force;
+    scene.width = args.width.unwrap_or(scene.width);
+    scene.height = args.height.unwrap_or(scene.height);
+    scene.samples_per_pixel = args.samples_per_pixel.unwrap_or(scene.samples_per_pixel);
+    Ok(scene)
+}
+
+fn turntable(
+    args: &SceneArguments,
+    frames: usize,
+    degrees: f64,
+    radius: Option<f64>,
+    elevation: Option<f64>,
+) -> Result<()> {
+    let mut scene = load_scene_with_overrides(args)?;
+    let camera = scene.camera;
+    for frame in 0..frames {
+        let azimuth = degrees * frame as f64 / frames as f64;
+        scene.camera = camera.orbit(azimuth, radius, elevation);
+        let output_file = frame_path(&args.output_file, frame);
+        info!(
+            "Rendering {} at {:.1} degrees -> {}",
+            args.config_file.display(),
+            azimuth,
+            output_file.display()
+        );
+        render(&output_file, &scene)?;
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
     setup_logs(LogLevel::Info)?;
-    let args: Arguments = Arguments::parse();
+    let arguments: Arguments = Arguments::parse();
+    let args = match (arguments.command, arguments.scene) {
+        (
+            Some(Command::Turntable {
+                scene,
+                frames,
+                degrees,
+                radius,
+                elevation,
+            }),
+            _,
+        ) => return turntable(&scene, frames, degrees, radius, elevation),
+        (None, Some(scene)) => scene,
+        (None, None) => bail!("no configuration file given"),
+    };
 
-    let json = fs::read(&args.config_file).context("Unable to read config file.")?;
-    let scene: Config = serde_json::from_slice(&json).context("Unable to parse config json")?;
-
-    info!(
-        "Rendering {} -> {}",
-        args.config_file.display(),
-        args.output_file.display()
-    );
-    render(&args.output_file, scene)?;
+    let mut scene = load_scene_with_overrides(&args)?;
+    let animation = match (scene.animation.clone(), arguments.frames) {
+        (Some(animation), _) => animation,
+        (None, Some(_)) => bail!("{} has no animation", args.config_file.display()),
+        (None, None) => {
+            info!(
+                "Rendering {} -> {}",
+                args.config_file.display(),
+                args.output_file.display()
+            );
+            return render(&args.output_file, &scene);
+        }
+    };
+    // the scene is loaded once and changed for every frame
+    let (first, last) = arguments
+        .frames
+        .unwrap_or((0, animation.frames.saturating_sub(1)));
+    for frame in first..=last {
+        animation
+            .apply(&mut scene, frame as f64)
+            .map_err(|e| eyre!("frame {}: {}", frame, e))?;
+        let output_file = frame_path(&args.output_file, frame);
+        info!(
+            "Rendering {} frame {} -> {}",
+            args.config_file.display(),
+            frame,
+            output_file.display()
+        );
+        render(&output_file, &scene)?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_frames() {
+        assert_eq!(parse_frames("3").unwrap(), (3, 3));
+        assert_eq!(parse_frames("2..5").unwrap(), (2, 5));
+        assert!(parse_frames("5..3").is_err());
+        assert!(parse_frames("a..3").is_err());
+    }
+
+    #[test]
+    fn test_frame_path() {
+        assert_eq!(
+            frame_path(Path::new("out/frame_%04d.png"), 7),
+            PathBuf::from("out/frame_0007.png")
+        );
+        assert_eq!(
+            frame_path(Path::new("out/render.png"), 12),
+            PathBuf::from("out/render_0012.png")
+        );
+        assert_eq!(
+            frame_path(Path::new("render"), 3),
+            PathBuf::from("render_0003")
+        );
+    }
+}
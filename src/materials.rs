@@ -1,17 +1,42 @@
-use jpeg_decoder::Decoder;
 use palette::Srgb;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
-use std::fs::File;
-use std::io::BufReader;
 
+use crate::onb::Onb;
 use crate::point3d::Point3D;
 use crate::ray::HitRecord;
 use crate::ray::Ray;
 
 pub trait Scatterable {
     fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Option<Ray>, Srgb)>;
+
+    // Probability density, per unit solid angle, of `scatter` sending the
+    // ray on along `scattered`. Materials that have one scatter with a
+    // weight of their albedo, so albedo times this density of the light
+    // arriving along `scattered` leaves along the ray, which is what
+    // aiming at lights needs. Mirrors and glass pick a single direction.
+    fn scattering_pdf(&self, _ray: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+}
+
+// Direction around `normal` with a density of cos(theta) / pi, that of
+// light scattered by a perfectly diffuse surface.
+fn random_cosine_direction(normal: Point3D) -> Point3D {
+    let mut rng = rand::thread_rng();
+    let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+    let r2 = rng.gen::<f64>();
+    Onb::from_w(normal).local(Point3D::new(
+        phi.cos() * r2.sqrt(),
+        phi.sin() * r2.sqrt(),
+        (1.0 - r2).sqrt(),
+    ))
+}
+
+fn cosine_pdf(hit_record: &HitRecord, scattered: &Ray) -> f64 {
+    let cosine = hit_record.normal.dot(scattered.direction.unit_vector());
+    cosine.max(0.0) / std::f64::consts::PI
 }
 
 // https://docs.rs/serde_with/1.9.4/serde_with/macro.serde_conv.html
@@ -29,7 +54,7 @@ serde_with::serde_conv!(
     TexturePixelsAsPath,
     Vec<u8>,
     |_pixels: &Vec<u8>| "/tmp/texture.jpg",
-    |value: &str| -> Result<_, std::convert::Infallible> { Ok(load_texture_image(value).0) }
+    |value: &str| -> Result<_, String> { Ok(load_rgb_image(value)?.0) }
 );
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -39,6 +64,9 @@ pub enum Material {
     Glass(Glass),
     Texture(Texture),
     Light(Light),
+    Emissive(Emissive),
+    Isotropic(Isotropic),
+    HenyeyGreenstein(HenyeyGreenstein),
 }
 
 impl Scatterable for Material {
@@ -49,7 +77,56 @@ impl Scatterable for Material {
             Material::Glass(g) => g.scatter(ray, hit_record),
             Material::Texture(t) => t.scatter(ray, hit_record),
             Material::Light(l) => l.scatter(ray, hit_record),
+            Material::Emissive(e) => e.scatter(ray, hit_record),
+            Material::Isotropic(i) => i.scatter(ray, hit_record),
+            Material::HenyeyGreenstein(h) => h.scatter(ray, hit_record),
+        }
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f64 {
+        match self {
+            Material::Lambertian(l) => l.scattering_pdf(ray, hit_record, scattered),
+            Material::Texture(t) => t.scattering_pdf(ray, hit_record, scattered),
+            Material::Isotropic(i) => i.scattering_pdf(ray, hit_record, scattered),
+            Material::HenyeyGreenstein(h) => h.scattering_pdf(ray, hit_record, scattered),
+            _ => 0.0,
+        }
+    }
+}
+
+impl Material {
+    // Whether surfaces of this material give off light, the bodies made of
+    // it are aimed at for direct lighting.
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, Material::Light(_) | Material::Emissive(_))
+    }
+
+    // Changes one parameter in place, e.g. for the next frame of an
+    // animation. Colors take three values, everything else one.
+    pub fn set_param(&mut self, name: &str, value: &[f64]) -> Result<(), String> {
+        let color = || match value {
+            [r, g, b] => Ok(Srgb::new(*r as f32, *g as f32, *b as f32)),
+            _ => Err(format!("{} needs 3 values, not {}", name, value.len())),
+        };
+        let number = || match value {
+            [x] => Ok(*x),
+            _ => Err(format!("{} needs 1 value, not {}", name, value.len())),
+        };
+        match (self, name) {
+            (Material::Lambertian(l), "albedo") => l.albedo = color()?,
+            (Material::Metal(m), "albedo") => m.albedo = color()?,
+            (Material::Metal(m), "fuzz") => m.fuzz = number()?,
+            (Material::Glass(g), "index_of_refraction") => g.index_of_refraction = number()?,
+            (Material::Texture(t), "albedo") => t.albedo = color()?,
+            (Material::Texture(t), "h_offset") => t.h_offset = number()?,
+            (Material::Emissive(e), "color") => e.color = color()?,
+            (Material::Emissive(e), "intensity") => e.intensity = number()?,
+            (Material::Isotropic(i), "albedo") => i.albedo = color()?,
+            (Material::HenyeyGreenstein(h), "albedo") => h.albedo = color()?,
+            (Material::HenyeyGreenstein(h), "g") => h.g = number()?.clamp(-0.99, 0.99),
+            _ => return Err(format!("the material has no parameter {:?}", name)),
         }
+        Ok(())
     }
 }
 
@@ -68,6 +145,114 @@ impl Scatterable for Light {
     }
 }
 
+fn default_intensity() -> f64 {
+    1.0
+}
+
+// A surface that gives off `color` times `intensity` in linear radiance,
+// so brighter than white for intensities above 1. An emission `texture`
+// image, looked up at the texture coordinates of the hit, scales the color.
+// Only the front of the surface glows unless it is `two_sided`.
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "EmissiveParams")]
+pub struct Emissive {
+    #[serde_as(as = "SrgbAsArray")]
+    pub color: Srgb,
+    pub intensity: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub texture: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub two_sided: bool,
+    #[serde(skip_serializing)]
+    pixels: Option<(Vec<u8>, usize, usize)>,
+}
+
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmissiveParams {
+    #[serde_as(as = "SrgbAsArray")]
+    pub color: Srgb,
+    #[serde(default = "default_intensity")]
+    pub intensity: f64,
+    #[serde(default)]
+    pub texture: Option<String>,
+    #[serde(default)]
+    pub two_sided: bool,
+}
+
+impl TryFrom<EmissiveParams> for Emissive {
+    type Error = String;
+
+    fn try_from(p: EmissiveParams) -> Result<Self, Self::Error> {
+        if !(p.intensity >= 0.0 && p.intensity.is_finite()) {
+            return Err(format!("intensity {} is not a number >= 0", p.intensity));
+        }
+        let pixels = match &p.texture {
+            Some(path) => Some(load_rgb_image(path)?),
+            None => None,
+        };
+        Ok(Emissive {
+            color: p.color,
+            intensity: p.intensity,
+            texture: p.texture,
+            two_sided: p.two_sided,
+            pixels,
+        })
+    }
+}
+
+impl Emissive {
+    pub fn new(color: Srgb, intensity: f64) -> Emissive {
+        Emissive {
+            color,
+            intensity,
+            texture: None,
+            two_sided: false,
+            pixels: None,
+        }
+    }
+
+    // Light given off at the hit, black on the back of one sided surfaces.
+    pub fn emitted(&self, hit_record: &HitRecord) -> Srgb {
+        if !hit_record.front_face && !self.two_sided {
+            return Srgb::new(0.0, 0.0, 0.0);
+        }
+        let scale = self.intensity as f32;
+        let (r, g, b) = match &self.pixels {
+            Some((pixels, width, height)) => {
+                let x = (hit_record.u.rem_euclid(1.0) * *width as f64) as usize;
+                // tiled texture coordinates wrap like those of a Texture
+                let v = if (0.0..=1.0).contains(&hit_record.v) {
+                    hit_record.v
+                } else {
+                    hit_record.v.rem_euclid(1.0)
+                };
+                let y = ((1.0 - v) * (*height - 1) as f64) as usize;
+                let base = 3 * (y * width + x.min(width - 1));
+                (
+                    pixels[base] as f32 / 255.0,
+                    pixels[base + 1] as f32 / 255.0,
+                    pixels[base + 2] as f32 / 255.0,
+                )
+            }
+            None => (1.0, 1.0, 1.0),
+        };
+        Srgb::new(
+            scale * r * self.color.red,
+            scale * g * self.color.green,
+            scale * b * self.color.blue,
+        )
+    }
+}
+
+impl Scatterable for Emissive {
+    fn scatter(&self, _ray: &Ray, hit_record: &HitRecord) -> Option<(Option<Ray>, Srgb)> {
+        Some((None, self.emitted(hit_record)))
+    }
+}
+
 #[serde_with::serde_as]
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 pub struct Lambertian {
@@ -81,17 +266,29 @@ impl Lambertian {
     }
 }
 
+// Multiplies an albedo by the vertex color of the surface, if it has one.
+fn tint(albedo: Srgb, hit_record: &HitRecord) -> Srgb {
+    match hit_record.color {
+        Some(c) => Srgb::new(
+            albedo.red * c.red,
+            albedo.green * c.green,
+            albedo.blue * c.blue,
+        ),
+        None => albedo,
+    }
+}
+
 impl Scatterable for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit_record: &HitRecord) -> Option<(Option<Ray>, Srgb)> {
-        let mut scatter_direction = hit_record.normal + Point3D::random_in_unit_sphere();
-        if scatter_direction.near_zero() {
-            scatter_direction = hit_record.normal;
-        }
-        let target = hit_record.point + scatter_direction;
-        let scattered = Ray::new(hit_record.point, target - hit_record.point);
-        let attenuation = self.albedo;
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Option<Ray>, Srgb)> {
+        let direction = random_cosine_direction(hit_record.normal);
+        let scattered = Ray::with_time(hit_record.point, direction, ray.time);
+        let attenuation = tint(self.albedo, hit_record);
         Some((Some(scattered), attenuation))
     }
+
+    fn scattering_pdf(&self, _ray: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f64 {
+        cosine_pdf(hit_record, scattered)
+    }
 }
 
 #[serde_with::serde_as]
@@ -115,11 +312,12 @@ fn reflect(v: Point3D, n: Point3D) -> Point3D {
 impl Scatterable for Metal {
     fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Option<Ray>, Srgb)> {
         let reflected = reflect(ray.direction, hit_record.normal);
-        let scattered = Ray::new(
+        let scattered = Ray::with_time(
             hit_record.point,
             reflected + Point3D::random_in_unit_sphere() * self.fuzz,
+            ray.time,
         );
-        let attenuation = self.albedo;
+        let attenuation = tint(self.albedo, hit_record);
         if scattered.direction.dot(hit_record.normal) > 0.0 {
             Some((Some(scattered), attenuation))
         } else {
@@ -188,11 +386,11 @@ impl Scatterable for Glass {
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         if cannot_refract || reflectance(cos_theta, refraction_ratio) > rng.gen::<f64>() {
             let reflected = reflect(unit_direction, hit_record.normal);
-            let scattered = Ray::new(hit_record.point, reflected);
+            let scattered = Ray::with_time(hit_record.point, reflected, ray.time);
             Some((Some(scattered), attenuation))
         } else {
             let direction = refract(unit_direction, hit_record.normal, refraction_ratio);
-            let scattered = Ray::new(hit_record.point, direction);
+            let scattered = Ray::with_time(hit_record.point, direction, ray.time);
             Some((Some(scattered), attenuation))
         }
     }
@@ -210,72 +408,200 @@ pub struct Texture {
     h_offset: f64,
 }
 
-fn load_texture_image(path: &str) -> (Vec<u8>, u64, u64) {
-    let file = File::open(path).expect(path);
-    let mut decoder = Decoder::new(BufReader::new(file));
-    let pixels = decoder.decode().expect("failed to decode image");
-    let metadata = decoder.info().unwrap();
-    (pixels, metadata.width as u64, metadata.height as u64)
+// Decodes an 8 or 16-bit image to one brightness value in [0, 1] per
+// pixel, row by row from the top. Color images are converted to luma.
+pub(crate) fn load_grayscale_image(path: &str) -> Result<(Vec<f64>, usize, usize), String> {
+    let image = image::open(path)
+        .map_err(|e| format!("{}: {}", path, e))?
+        .into_luma16();
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let values = image
+        .into_raw()
+        .iter()
+        .map(|&l| l as f64 / 65535.0)
+        .collect();
+    Ok((values, width, height))
+}
+
+// Decodes a JPEG, PNG or other 8-bit image to three bytes per pixel, row by
+// row from the top. Grayscale images are spread over all three.
+pub(crate) fn load_rgb_image(path: &str) -> Result<(Vec<u8>, usize, usize), String> {
+    let image = image::open(path)
+        .map_err(|e| format!("{}: {}", path, e))?
+        .into_rgb8();
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    Ok((image.into_raw(), width, height))
 }
 
 impl Texture {
-    pub fn new(albedo: Srgb, texture_path: &str, rot: f64) -> Texture {
-        let file = File::open(texture_path).expect("failed to open texture file");
-        let mut decoder = Decoder::new(BufReader::new(file));
-        let pixels = decoder.decode().expect("failed to decode image");
-        let metadata = decoder.info().unwrap();
-        Texture {
+    pub fn new(albedo: Srgb, texture_path: &str, rot: f64) -> Result<Texture, String> {
+        let (pixels, width, height) = load_rgb_image(texture_path)?;
+        Ok(Texture {
             albedo,
             pixels,
-            width: metadata.width as u64,
-            height: metadata.height as u64,
+            width: width as u64,
+            height: height as u64,
             h_offset: rot,
-        }
+        })
     }
 
     pub fn get_albedo(&self, u: f64, v: f64) -> Srgb {
-        let mut rot = u + self.h_offset;
-        if rot > 1.0 {
-            rot = rot - 1.0;
-        }
-        let uu = rot * (self.width) as f64;
+        // mesh texture coordinates may lie outside [0, 1], wrap them
+        let rot = (u + self.h_offset).rem_euclid(1.0);
+        let uu = (rot * (self.width) as f64).min((self.width - 1) as f64);
+        let v = if (0.0..=1.0).contains(&v) {
+            v
+        } else {
+            v.rem_euclid(1.0)
+        };
         let vv = (1.0 - v) * (self.height - 1) as f64;
         let base_pixel =
             (3 * ((vv.floor() as u64) * self.width as u64 + (uu.floor() as u64))) as usize;
         let pixel_r = self.pixels[base_pixel];
         let pixel_g = self.pixels[base_pixel + 1];
         let pixel_b = self.pixels[base_pixel + 2];
+        // the albedo tints the texture
         Srgb::new(
-            pixel_r as f32 / 255.0,
-            pixel_g as f32 / 255.0,
-            pixel_b as f32 / 255.0,
+            self.albedo.red * pixel_r as f32 / 255.0,
+            self.albedo.green * pixel_g as f32 / 255.0,
+            self.albedo.blue * pixel_b as f32 / 255.0,
         )
     }
 }
 
 impl Scatterable for Texture {
-    fn scatter(&self, _ray: &Ray, hit_record: &HitRecord) -> Option<(Option<Ray>, Srgb)> {
-        let mut scatter_direction = hit_record.normal + Point3D::random_in_unit_sphere();
-        if scatter_direction.near_zero() {
-            scatter_direction = hit_record.normal;
-        }
-        let target = hit_record.point + scatter_direction;
-        let scattered = Ray::new(hit_record.point, target - hit_record.point);
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Option<Ray>, Srgb)> {
+        let direction = random_cosine_direction(hit_record.normal);
+        let scattered = Ray::with_time(hit_record.point, direction, ray.time);
         let attenuation = self.get_albedo(hit_record.u, hit_record.v);
         Some((Some(scattered), attenuation))
     }
+
+    fn scattering_pdf(&self, _ray: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f64 {
+        cosine_pdf(hit_record, scattered)
+    }
+}
+// Phase function of a participating medium that scatters light equally in
+// all directions, see `ConstantMedium`.
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Isotropic {
+    #[serde_as(as = "SrgbAsArray")]
+    pub albedo: Srgb,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Srgb) -> Isotropic {
+        Isotropic { albedo }
+    }
+}
+
+impl Scatterable for Isotropic {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Option<Ray>, Srgb)> {
+        let direction = Point3D::random_in_unit_sphere().unit_vector();
+        let scattered = Ray::with_time(hit_record.point, direction, ray.time);
+        Some((Some(scattered), self.albedo))
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * std::f64::consts::PI)
+    }
+}
+
+// Phase function of a participating medium that prefers to scatter light
+// forwards (g > 0, e.g. fog and clouds) or backwards (g < 0). g = 0 is
+// isotropic.
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(try_from = "HenyeyGreensteinParams")]
+pub struct HenyeyGreenstein {
+    #[serde_as(as = "SrgbAsArray")]
+    pub albedo: Srgb,
+    pub g: f64,
 }
+
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Deserialize)]
+pub struct HenyeyGreensteinParams {
+    #[serde_as(as = "SrgbAsArray")]
+    pub albedo: Srgb,
+    pub g: f64,
+}
+
+impl TryFrom<HenyeyGreensteinParams> for HenyeyGreenstein {
+    type Error = String;
+
+    fn try_from(p: HenyeyGreensteinParams) -> Result<Self, Self::Error> {
+        // |g| = 1 scatters along a single direction, the phase function
+        // has no density there
+        if !(p.g > -1.0 && p.g < 1.0) {
+            return Err(format!("g must lie strictly between -1 and 1, not {}", p.g));
+        }
+        Ok(HenyeyGreenstein::new(p.albedo, p.g))
+    }
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Srgb, g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein { albedo, g }
+    }
+
+    // Probability density of scattering by an angle with cosine
+    // `cos_theta`, per unit solid angle.
+    pub fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.g;
+        let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * std::f64::consts::PI * denominator * denominator.sqrt())
+    }
+
+    fn sample_cos_theta(&self, xi: f64) -> f64 {
+        let g = self.g;
+        if g.abs() < 1e-3 {
+            return 1.0 - 2.0 * xi;
+        }
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+        ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+    }
+}
+
+impl Scatterable for HenyeyGreenstein {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Option<Ray>, Srgb)> {
+        let mut rng = rand::thread_rng();
+        let cos_theta = self.sample_cos_theta(rng.gen::<f64>());
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+        let direction = Onb::from_w(ray.direction).local(Point3D::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ));
+        let scattered = Ray::with_time(hit_record.point, direction, ray.time);
+        Some((Some(scattered), self.albedo))
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, _hit_record: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = ray
+            .direction
+            .unit_vector()
+            .dot(scattered.direction.unit_vector());
+        self.phase(cos_theta)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_texture() {
-        let _world = Material::Texture(Texture::new(
-            Srgb::new(1.0, 1.0, 1.0),
-            "data/earth.jpg",
-            0.0,
-        ));
+        let texture = Texture::new(Srgb::new(1.0, 1.0, 1.0), "data/earth.jpg", 0.0).unwrap();
+        // tiled coordinates repeat both ways
+        assert_eq!(texture.get_albedo(1.3, -0.6), texture.get_albedo(0.3, 0.4));
+        let tinted = Texture::new(Srgb::new(0.5, 1.0, 0.0), "data/earth.jpg", 0.0).unwrap();
+        let (plain, tint) = (texture.get_albedo(0.3, 0.4), tinted.get_albedo(0.3, 0.4));
+        assert_eq!(tint.red, 0.5 * plain.red);
+        assert_eq!(tint.green, plain.green);
+        assert_eq!(tint.blue, 0.0);
     }
 
     #[test]
@@ -284,4 +610,99 @@ mod tests {
         let serialized = serde_json::to_string(&m).unwrap();
         assert_eq!(r#"{"albedo":[0.8,0.8,0.8],"fuzz":2.0}"#, serialized,);
     }
+
+    #[test]
+    fn test_henyey_greenstein() {
+        // the phase function integrates to 1 over the sphere and the mean
+        // cosine of the sampled directions is g
+        let h = HenyeyGreenstein::new(Srgb::new(1.0, 1.0, 1.0), 0.6);
+        let n = 10000;
+        let integral: f64 = (0..n)
+            .map(|i| {
+                let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / n as f64;
+                h.phase(cos_theta) * 2.0 * std::f64::consts::PI * 2.0 / n as f64
+            })
+            .sum();
+        assert!((integral - 1.0).abs() < 1e-3);
+        let mean: f64 = (0..n)
+            .map(|i| h.sample_cos_theta((i as f64 + 0.5) / n as f64))
+            .sum::<f64>()
+            / n as f64;
+        assert!((mean - 0.6).abs() < 1e-3);
+
+        let isotropic = HenyeyGreenstein::new(Srgb::new(1.0, 1.0, 1.0), 0.0);
+        assert!((isotropic.phase(0.3) * 4.0 * std::f64::consts::PI - 1.0).abs() < 1e-12);
+
+        let json = |g: f64| {
+            format!(
+                r#"{{"HenyeyGreenstein":{{"albedo":[1.0,1.0,1.0],"g":{:?}}}}}"#,
+                g
+            )
+        };
+        assert!(serde_json::from_str::<Material>(&json(0.5)).is_ok());
+        assert!(serde_json::from_str::<Material>(&json(1.0)).is_err());
+        assert!(serde_json::from_str::<Material>(&json(-1.5)).is_err());
+        let nan = HenyeyGreensteinParams {
+            albedo: Srgb::new(1.0, 1.0, 1.0),
+            g: f64::NAN,
+        };
+        assert!(HenyeyGreenstein::try_from(nan).is_err());
+    }
+
+    #[test]
+    fn test_emissive() {
+        let json = r#"{"Emissive":{"color":[1.0,0.5,0.25],"intensity":4.0}}"#;
+        let material = serde_json::from_str::<Material>(json).unwrap();
+        assert_eq!(serde_json::to_string(&material).unwrap(), json);
+        assert!(material.is_emissive());
+        let emissive = match &material {
+            Material::Emissive(e) => e,
+            _ => panic!("expected Emissive"),
+        };
+        let mut hit_record = HitRecord {
+            t: 1.0,
+            point: Point3D::new(0.0, 0.0, 0.0),
+            normal: Point3D::new(0.0, 1.0, 0.0),
+            front_face: true,
+            material: &material,
+            u: 0.5,
+            v: 0.5,
+            color: None,
+            emitted: None,
+        };
+        assert_eq!(emissive.emitted(&hit_record), Srgb::new(4.0, 2.0, 1.0));
+        hit_record.front_face = false;
+        assert_eq!(emissive.emitted(&hit_record), Srgb::new(0.0, 0.0, 0.0));
+        let two_sided = Emissive {
+            two_sided: true,
+            ..emissive.clone()
+        };
+        assert_eq!(two_sided.emitted(&hit_record), Srgb::new(4.0, 2.0, 1.0));
+
+        let textured = r#"{"color":[1.0,1.0,1.0],"intensity":1.0,"texture":"data/earth.jpg","two_sided":true}"#;
+        let emissive = serde_json::from_str::<Emissive>(textured).unwrap();
+        assert_eq!(serde_json::to_string(&emissive).unwrap(), textured);
+        assert!(emissive.emitted(&hit_record).red <= 1.0);
+        let mut tiled = HitRecord {
+            u: 1.3,
+            v: -0.6,
+            ..hit_record
+        };
+        let wrapped = emissive.emitted(&tiled);
+        tiled.u = 0.3;
+        tiled.v = 0.4;
+        assert_eq!(wrapped, emissive.emitted(&tiled));
+        let missing = textured.replace("earth", "no_such_image");
+        assert!(serde_json::from_str::<Emissive>(&missing).is_err());
+
+        let negative = r#"{"color":[1.0,1.0,1.0],"intensity":-1.0}"#;
+        assert!(serde_json::from_str::<Emissive>(negative).is_err());
+        let nan = EmissiveParams {
+            color: Srgb::new(1.0, 1.0, 1.0),
+            intensity: f64::NAN,
+            texture: None,
+            two_sided: false,
+        };
+        assert!(Emissive::try_from(nan).is_err());
+    }
 }
@@ -0,0 +1,232 @@
+use std::ops::Mul;
+
+use crate::point3d::Point3D;
+
+#[cfg(test)]
+use assert_approx_eq::assert_approx_eq;
+
+// Row-major 4x4 matrix for affine transforms of points and vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Matrix4 {
+        Matrix4 { m }
+    }
+
+    pub fn identity() -> Matrix4 {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // glTF and most interchange formats store matrices column by column.
+    pub fn from_column_major(c: &[f64; 16]) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (col, chunk) in c.chunks(4).enumerate() {
+            for (row, value) in chunk.iter().enumerate() {
+                m[row][col] = *value;
+            }
+        }
+        Matrix4::new(m)
+    }
+
+    pub fn translation(t: Point3D) -> Matrix4 {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, t.x()],
+            [0.0, 1.0, 0.0, t.y()],
+            [0.0, 0.0, 1.0, t.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scale(s: Point3D) -> Matrix4 {
+        Matrix4::new([
+            [s.x(), 0.0, 0.0, 0.0],
+            [0.0, s.y(), 0.0, 0.0],
+            [0.0, 0.0, s.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Rotation from a unit quaternion given as [x, y, z, w].
+    pub fn rotation_quaternion(q: [f64; 4]) -> Matrix4 {
+        let [x, y, z, w] = q;
+        Matrix4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Rotation by `degrees` around an arbitrary axis (Rodrigues' formula).
+    pub fn rotation_axis(axis: Point3D, degrees: f64) -> Matrix4 {
+        let a = axis.unit_vector();
+        let (s, c) = degrees.to_radians().sin_cos();
+        let t = 1.0 - c;
+        Matrix4::new([
+            [
+                t * a.x() * a.x() + c,
+                t * a.x() * a.y() - s * a.z(),
+                t * a.x() * a.z() + s * a.y(),
+                0.0,
+            ],
+            [
+                t * a.x() * a.y() + s * a.z(),
+                t * a.y() * a.y() + c,
+                t * a.y() * a.z() - s * a.x(),
+                0.0,
+            ],
+            [
+                t * a.x() * a.z() - s * a.y(),
+                t * a.y() * a.z() + s * a.x(),
+                t * a.z() * a.z() + c,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transform_point(&self, p: Point3D) -> Point3D {
+        let m = &self.m;
+        Point3D::new(
+            m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3],
+            m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3],
+            m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3],
+        )
+    }
+
+    // Like transform_point but ignores the translation.
+    pub fn transform_vector(&self, v: Point3D) -> Point3D {
+        let m = &self.m;
+        Point3D::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                *value = self.m[col][row];
+            }
+        }
+        Matrix4::new(m)
+    }
+
+    // Determinant of the upper 3x3 part; negative for mirroring transforms.
+    pub fn determinant3(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    // Inverse by Gauss-Jordan elimination, None for singular matrices.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inv = Matrix4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / a[col][col];
+            for k in 0..4 {
+                a[col][k] *= scale;
+                inv[col][k] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for k in 0..4 {
+                        a[row][k] -= factor * a[col][k];
+                        inv[row][k] -= factor * inv[col][k];
+                    }
+                }
+            }
+        }
+        Some(Matrix4::new(inv))
+    }
+}
+
+impl Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[row][k] * other.m[k][col]).sum();
+            }
+        }
+        Matrix4::new(m)
+    }
+}
+
+#[test]
+fn test_transform_point() {
+    let m = Matrix4::translation(Point3D::new(1.0, 2.0, 3.0))
+        * Matrix4::rotation_axis(Point3D::new(0.0, 0.0, 1.0), 90.0)
+        * Matrix4::scale(Point3D::new(2.0, 2.0, 2.0));
+    let p = m.transform_point(Point3D::new(1.0, 0.0, 0.0));
+    assert_approx_eq!(p.x(), 1.0);
+    assert_approx_eq!(p.y(), 4.0);
+    assert_approx_eq!(p.z(), 3.0);
+    let v = m.transform_vector(Point3D::new(1.0, 0.0, 0.0));
+    assert_approx_eq!(v.x(), 0.0);
+    assert_approx_eq!(v.y(), 2.0);
+}
+
+#[test]
+fn test_quaternion_matches_axis_angle() {
+    let half = (45.0f64).to_radians();
+    let q = Matrix4::rotation_quaternion([0.0, half.sin(), 0.0, half.cos()]);
+    let a = Matrix4::rotation_axis(Point3D::new(0.0, 1.0, 0.0), 90.0);
+    for row in 0..4 {
+        for col in 0..4 {
+            assert_approx_eq!(q.m[row][col], a.m[row][col]);
+        }
+    }
+}
+
+#[test]
+fn test_inverse() {
+    let m = Matrix4::translation(Point3D::new(1.0, -2.0, 3.0))
+        * Matrix4::rotation_axis(Point3D::new(1.0, 1.0, 0.0), 30.0)
+        * Matrix4::scale(Point3D::new(0.5, 2.0, 3.0));
+    let identity = m * m.inverse().unwrap();
+    for row in 0..4 {
+        for col in 0..4 {
+            assert_approx_eq!(identity.m[row][col], if row == col { 1.0 } else { 0.0 });
+        }
+    }
+    assert!(Matrix4::scale(Point3D::new(0.0, 1.0, 1.0))
+        .inverse()
+        .is_none());
+}
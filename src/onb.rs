@@ -0,0 +1,59 @@
+use crate::point3d::Point3D;
+
+#[cfg(test)]
+use assert_approx_eq::assert_approx_eq;
+
+// Orthonormal basis with `w` along a given direction, used to parameterize
+// surfaces around their normal or axis.
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    pub u: Point3D,
+    pub v: Point3D,
+    pub w: Point3D,
+}
+
+impl Onb {
+    // Branchless construction from "Building an Orthonormal Basis, Revisited"
+    // (Duff et al. 2017), `w` does not need to be normalized.
+    pub fn from_w(w: Point3D) -> Onb {
+        let w = w.unit_vector();
+        let sign = 1.0f64.copysign(w.z());
+        let a = -1.0 / (sign + w.z());
+        let b = w.x() * w.y() * a;
+        let u = Point3D::new(1.0 + sign * w.x() * w.x() * a, sign * b, -sign * w.x());
+        let v = Point3D::new(b, sign + w.y() * w.y() * a, -w.y());
+        Onb { u, v, w }
+    }
+
+    // Converts coordinates in this basis to world space.
+    pub fn local(&self, a: Point3D) -> Point3D {
+        self.u * a.x() + self.v * a.y() + self.w * a.z()
+    }
+
+    // Converts world space coordinates to this basis.
+    pub fn to_local(&self, a: Point3D) -> Point3D {
+        Point3D::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
+
+#[test]
+fn test_onb_is_orthonormal() {
+    for w in [
+        Point3D::new(0.0, 0.0, 1.0),
+        Point3D::new(0.0, 0.0, -1.0),
+        Point3D::new(0.0, 3.0, 0.0),
+        Point3D::new(1.0, -2.0, 0.5),
+    ] {
+        let onb = Onb::from_w(w);
+        assert_approx_eq!(onb.u.length(), 1.0);
+        assert_approx_eq!(onb.v.length(), 1.0);
+        assert_approx_eq!(onb.u.dot(onb.v), 0.0);
+        assert_approx_eq!(onb.u.dot(onb.w), 0.0);
+        assert_approx_eq!(onb.v.dot(onb.w), 0.0);
+        // right handed
+        assert_approx_eq!(onb.u.cross(onb.v).dot(onb.w), 1.0);
+        let world = onb.local(Point3D::new(0.0, 0.0, 2.0));
+        assert_approx_eq!(world.dot(w.unit_vector()), 2.0);
+        assert_approx_eq!(onb.to_local(world).z(), 2.0);
+    }
+}
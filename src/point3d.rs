@@ -2,7 +2,7 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::cmp::PartialEq;
 use std::f64;
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::ops::{Add, Div, Index, Mul, Neg, Sub};
 
 #[cfg(test)]
 use assert_approx_eq::assert_approx_eq;
@@ -84,6 +84,35 @@ impl Point3D {
     pub fn near_zero(&self) -> bool {
         self.x.abs() < f64::EPSILON && self.y.abs() < f64::EPSILON && self.z.abs() < f64::EPSILON
     }
+
+    pub fn min(&self, other: Point3D) -> Point3D {
+        Point3D::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    pub fn max(&self, other: Point3D) -> Point3D {
+        Point3D::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+}
+
+impl Index<usize> for Point3D {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("axis out of range: {}", axis),
+        }
+    }
 }
 
 impl Add for Point3D {
@@ -270,3 +299,14 @@ fn test_near_zero() {
     let p = Point3D::new(0.0, 0.0, 0.0);
     assert!(p.near_zero());
 }
+
+#[test]
+fn test_min_max_index() {
+    let p = Point3D::new(0.1, 0.5, -0.3);
+    let q = Point3D::new(0.2, 0.3, -0.4);
+    assert_eq!(p.min(q), Point3D::new(0.1, 0.3, -0.4));
+    assert_eq!(p.max(q), Point3D::new(0.2, 0.5, -0.3));
+    assert_eq!(p[0], 0.1);
+    assert_eq!(p[1], 0.5);
+    assert_eq!(p[2], -0.3);
+}
@@ -1,5 +1,7 @@
+use crate::aabb::Aabb;
 use crate::materials::Material;
 use crate::point3d::Point3D;
+use palette::Srgb;
 
 #[cfg(test)]
 use assert_approx_eq::assert_approx_eq;
@@ -8,11 +10,22 @@ use assert_approx_eq::assert_approx_eq;
 pub struct Ray {
     pub origin: Point3D,
     pub direction: Point3D,
+    // When the ray was sent, somewhere between the camera shutter opening
+    // and closing. Moving bodies are hit where they are at that time.
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point3D, direction: Point3D) -> Ray {
-        Ray { origin, direction }
+        Ray::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Point3D, direction: Point3D, time: f64) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn at(&self, t: f64) -> Point3D {
@@ -28,10 +41,31 @@ pub struct HitRecord<'material> {
     pub material: &'material Material,
     pub u: f64,
     pub v: f64,
+    // Interpolated vertex color at the hit point, for meshes that have them.
+    pub color: Option<Srgb>,
+    // Light given off at the hit point, by glowing volumes.
+    pub emitted: Option<Srgb>,
 }
 
 pub trait Hittable {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    // None for bodies that extend to infinity.
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    // Every surface crossing along the ray, nearest first. For closed
+    // bodies `front_face` tells whether the ray enters or leaves the body
+    // there, which is what CSG needs. By default the nearest hit is asked
+    // for repeatedly, stepping just past the previous one.
+    fn hit_all(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        let step = 1e-7 / ray.direction.length();
+        let mut hits = Vec::new();
+        let mut t = t_min;
+        while let Some(hit) = self.hit(ray, t, t_max) {
+            t = hit.t + step;
+            hits.push(hit);
+        }
+        hits
+    }
 }
 
 #[test]
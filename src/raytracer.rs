@@ -6,27 +6,28 @@ use rayon::prelude::*;
 use std::fs::File;
 use std::time::Instant;
 
-use crate::config::Config;
-use crate::materials::Material;
+use crate::config::{Config, Sky};
+use crate::environment::Environment;
+use crate::lights::LightSource;
 use crate::materials::Scatterable;
 use crate::ray::HitRecord;
 use crate::ray::Hittable;
 use crate::ray::Ray;
 
-use crate::body::{Body, Sphere};
+use crate::body::Body;
+use crate::bvh::Bvh;
+use crate::camera::{Camera, StereoLayout};
 use common::info;
 use image::codecs::png::PngEncoder;
 #[cfg(test)]
+use std::collections::BTreeMap;
+#[cfg(test)]
 use std::fs;
 use std::path::Path;
 
 #[cfg(test)]
 use crate::point3d::Point3D;
 
-#[cfg(test)]
-use crate::camera::Camera;
-#[cfg(test)]
-use crate::config::Sky;
 #[cfg(test)]
 use crate::materials::Lambertian;
 #[cfg(test)]
@@ -40,7 +41,7 @@ fn write_image(filename: &Path, pixels: &[u8], bounds: (usize, usize)) -> Result
 }
 
 fn hit_world<'material>(
-    world: &'material Vec<Body>,
+    world: &'material [Body],
     r: &Ray,
     t_min: f64,
     t_max: f64,
@@ -56,6 +57,92 @@ fn hit_world<'material>(
     hit_record
 }
 
+// The bodies of a scene together with the BVH built over them for the
+// current frame. Without a BVH every ray is tested against every body.
+struct World<'a> {
+    objects: &'a [Body],
+    bvh: Option<Bvh>,
+}
+
+impl<'a> World<'a> {
+    fn new(objects: &'a [Body], brute_force: bool) -> World<'a> {
+        let bvh = if brute_force {
+            None
+        } else {
+            Some(Bvh::build(objects))
+        };
+        World { objects, bvh }
+    }
+
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'a>> {
+        match &self.bvh {
+            Some(bvh) => bvh.hit(self.objects, r, t_min, t_max),
+            None => hit_world(self.objects, r, t_min, t_max),
+        }
+    }
+
+    // Like `hit`, but looks straight through participating media.
+    fn hit_surface(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'a>> {
+        let objects = self.objects;
+        let hit = |index: usize, t_min: f64, t_max: f64| {
+            let body = &objects[index];
+            if body.is_medium() {
+                None
+            } else {
+                body.hit(r, t_min, t_max)
+            }
+        };
+        match &self.bvh {
+            Some(bvh) => bvh.hit_with(r, t_min, t_max, hit),
+            None => {
+                let mut closest_so_far = t_max;
+                let mut hit_record = None;
+                for index in 0..objects.len() {
+                    if let Some(h) = hit(index, t_min, closest_so_far) {
+                        closest_so_far = h.t;
+                        hit_record = Some(h);
+                    }
+                }
+                hit_record
+            }
+        }
+    }
+
+    // Fraction of the light along the ray that gets through the media in
+    // the scene.
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.objects
+            .iter()
+            .filter(|body| body.is_medium())
+            .map(|body| body.transmittance(r, t_min, t_max))
+            .product()
+    }
+}
+
+// Light arriving along a ray aimed at a light: the light given off by the
+// first surface on the way, dimmed by the media in front of it.
+fn light_probe(ray: &Ray, world: &World) -> Srgb {
+    let hit = match world.hit_surface(ray, 0.001, f64::MAX) {
+        Some(hit) => hit,
+        None => return Srgb::new(0.0, 0.0, 0.0),
+    };
+    match hit.material.scatter(ray, &hit) {
+        Some((None, color)) => {
+            let transmittance = world.transmittance(ray, 0.001, hit.t) as f32;
+            Srgb::new(
+                transmittance * color.red,
+                transmittance * color.green,
+                transmittance * color.blue,
+            )
+        }
+        _ => Srgb::new(0.0, 0.0, 0.0),
+    }
+}
+
+fn environment(scene: &Config) -> Option<&Environment> {
+    scene.sky.as_ref().and_then(Sky::environment_map)
+}
+
 fn clamp(value: f32) -> f32 {
     if value < 0.0 {
         0.0
@@ -66,95 +153,224 @@ fn clamp(value: f32) -> f32 {
     }
 }
 
+// Density of aiming at one of the lights, picked at random, along the ray.
+fn lights_pdf(lights: &[&Body], ray: &Ray) -> f64 {
+    if lights.is_empty() {
+        return 0.0;
+    }
+    lights
+        .iter()
+        .map(|light| light.pdf_towards(ray.origin, ray.direction, ray.time))
+        .sum::<f64>()
+        / lights.len() as f64
+}
+
+// Power heuristic weight of a sample drawn with density `pdf`, when another
+// strategy would have drawn it with density `other_pdf`.
+fn mis_weight(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+// Next event estimation: the light arriving at the hit from a random
+// light, weighed against finding it by scattering towards it.
+fn sample_light(
+    ray: &Ray,
+    hit_record: &HitRecord,
+    albedo: Srgb,
+    world: &World,
+    lights: &[&Body],
+) -> Srgb {
+    let mut rng = rand::thread_rng();
+    let light = lights[rng.gen_range(0..lights.len())];
+    let light_ray = Ray::with_time(
+        hit_record.point,
+        light.random_direction_towards(hit_record.point, ray.time),
+        ray.time,
+    );
+    let light_pdf = lights_pdf(lights, &light_ray);
+    let scattering_pdf = hit_record
+        .material
+        .scattering_pdf(ray, hit_record, &light_ray);
+    if light_pdf <= 0.0 || scattering_pdf <= 0.0 {
+        return Srgb::new(0.0, 0.0, 0.0);
+    }
+    let radiance = light_probe(&light_ray, world);
+    let weight = (mis_weight(light_pdf, scattering_pdf) * scattering_pdf / light_pdf) as f32;
+    Srgb::new(
+        weight * albedo.red * radiance.red,
+        weight * albedo.green * radiance.green,
+        weight * albedo.blue * radiance.blue,
+    )
+}
+
+// The light arriving at the hit from one of the scene's light sources,
+// unless something is in the way.
+fn sample_light_source(
+    ray: &Ray,
+    hit_record: &HitRecord,
+    albedo: Srgb,
+    world: &World,
+    light: &LightSource,
+) -> Srgb {
+    let sample = match light.sample(hit_record.point) {
+        Some(sample) => sample,
+        None => return Srgb::new(0.0, 0.0, 0.0),
+    };
+    let shadow_ray = Ray::with_time(hit_record.point, sample.direction, ray.time);
+    let scattering_pdf = hit_record
+        .material
+        .scattering_pdf(ray, hit_record, &shadow_ray);
+    if scattering_pdf <= 0.0
+        || world
+            .hit_surface(&shadow_ray, 0.001, sample.distance)
+            .is_some()
+    {
+        return Srgb::new(0.0, 0.0, 0.0);
+    }
+    let weight = (scattering_pdf * world.transmittance(&shadow_ray, 0.001, sample.distance)) as f32;
+    Srgb::new(
+        weight * albedo.red * sample.light.red,
+        weight * albedo.green * sample.light.green,
+        weight * albedo.blue * sample.light.blue,
+    )
+}
+
+// The light arriving at the hit from a direction picked on the environment
+// map, weighed against finding it by scattering towards it.
+fn sample_environment(
+    ray: &Ray,
+    hit_record: &HitRecord,
+    albedo: Srgb,
+    world: &World,
+    environment: &Environment,
+) -> Srgb {
+    let (direction, environment_pdf) = match environment.sample() {
+        Some(sample) => sample,
+        None => return Srgb::new(0.0, 0.0, 0.0),
+    };
+    let shadow_ray = Ray::with_time(hit_record.point, direction, ray.time);
+    let scattering_pdf = hit_record
+        .material
+        .scattering_pdf(ray, hit_record, &shadow_ray);
+    if scattering_pdf <= 0.0 || world.hit_surface(&shadow_ray, 0.001, f64::MAX).is_some() {
+        return Srgb::new(0.0, 0.0, 0.0);
+    }
+    let radiance = environment.radiance(direction);
+    let weight = (mis_weight(environment_pdf, scattering_pdf) * scattering_pdf / environment_pdf
+        * world.transmittance(&shadow_ray, 0.001, f64::MAX)) as f32;
+    Srgb::new(
+        weight * albedo.red * radiance.red,
+        weight * albedo.green * radiance.green,
+        weight * albedo.blue * radiance.blue,
+    )
+}
+
+// Light arriving along the ray, in linear radiance. `scattering_pdf` is
+// the density the ray was scattered with from a hit that also sampled the
+// lights and the environment, so light it finds is weighed against having
+// aimed at it.
 fn ray_color(
     ray: &Ray,
     scene: &Config,
-    lights: &Vec<Sphere>,
-    max_depth: usize,
+    world: &World,
+    lights: &[&Body],
     depth: usize,
+    scattering_pdf: Option<f64>,
 ) -> Srgb {
-    let mut rng = rand::thread_rng();
-
-    if depth <= 0 {
+    if depth == 0 {
         return Srgb::new(0.0, 0.0, 0.0);
     }
-    let hit = hit_world(&scene.objects, ray, 0.001, f64::MAX);
+    let hit = world.hit(ray, 0.001, f64::MAX);
     match hit {
         Some(hit_record) => {
-            let scattered = hit_record.material.scatter(ray, &hit_record);
-            match scattered {
-                Some((scattered_ray, albedo)) => {
-                    let mut light_red = 0.0;
-                    let mut light_green = 0.0;
-                    let mut light_blue = 0.0;
-                    let mut prob = 0.1;
-                    match hit_record.material {
-                        Material::Glass(_) => {
-                            prob = 0.05;
-                        }
-                        _ => {}
-                    }
-                    if lights.len() > 0
-                        && rng.gen::<f64>() > (1.0 - lights.len() as f64 * prob)
-                        && depth > (max_depth - 2)
-                    {
-                        for light in lights {
-                            let light_ray =
-                                Ray::new(hit_record.point, light.center - hit_record.point);
-                            let target_color = ray_color(&light_ray, scene, lights, 2, 1);
-                            light_red += albedo.red * target_color.red;
-                            light_green += albedo.green * target_color.green;
-                            light_blue += albedo.blue * target_color.blue;
-                        }
-                        light_red /= lights.len() as f32;
-                        light_green /= lights.len() as f32;
-                        light_blue /= lights.len() as f32;
-                    }
-                    match scattered_ray {
-                        Some(sr) => {
-                            let target_color = ray_color(&sr, scene, lights, max_depth, depth - 1);
-                            return Srgb::new(
-                                clamp(light_red + albedo.red * target_color.red),
-                                clamp(light_green + albedo.green * target_color.green),
-                                clamp(light_blue + albedo.blue * target_color.blue),
-                            );
-                        }
-                        None => albedo,
-                    }
-                }
+            let (scattered_ray, albedo) = match hit_record.material.scatter(ray, &hit_record) {
+                Some(scattered) => scattered,
+                // absorbed
+                None => return Srgb::new(0.0, 0.0, 0.0),
+            };
+            let scattered_ray = match scattered_ray {
+                Some(scattered_ray) => scattered_ray,
+                // a light, `albedo` is what it gives off
                 None => {
-                    // don't bother bouncing absorbed rays towards lights
-                    // (they would be absorbed in the opposite direction).
-                    return Srgb::new(0.0, 0.0, 0.0);
+                    let weight = match scattering_pdf {
+                        Some(pdf) => mis_weight(pdf, lights_pdf(lights, ray)) as f32,
+                        None => 1.0,
+                    };
+                    return Srgb::new(
+                        weight * albedo.red,
+                        weight * albedo.green,
+                        weight * albedo.blue,
+                    );
                 }
+            };
+            // glowing volumes add their own light
+            let mut color = hit_record.emitted.unwrap_or(Srgb::new(0.0, 0.0, 0.0));
+            let pdf = hit_record
+                .material
+                .scattering_pdf(ray, &hit_record, &scattered_ray);
+            // mirrors and glass only see lights by scattering towards them
+            if pdf > 0.0 && !lights.is_empty() {
+                let direct = sample_light(ray, &hit_record, albedo, world, lights);
+                color.red += direct.red;
+                color.green += direct.green;
+                color.blue += direct.blue;
             }
-        }
-        None => {
-            let t: f32 = clamp(0.5 * (ray.direction.unit_vector().y() as f32 + 1.0));
-            let u: f32 = clamp(0.5 * (ray.direction.unit_vector().x() as f32 + 1.0));
-            match &scene.sky {
-                None => Srgb::new(0.0, 0.0, 0.0),
-                Some(sky) => match &sky.texture {
-                    None => Srgb::new(
-                        (1.0 - t) * 1.0 + t * 0.5,
-                        (1.0 - t) * 1.0 + t * 0.7,
-                        (1.0 - t) * 1.0 + t * 1.0,
-                    ),
-                    Some((pixels, width, height, _)) => {
-                        let x = (u * (*width - 1) as f32) as usize;
-                        let y = ((1.0 - t) * (*height - 1) as f32) as usize;
-                        let pixel_red = &pixels[(y * *width + x) * 3];
-                        let pixel_green = &pixels[(y * *width + x) * 3 + 1];
-                        let pixel_blue = &pixels[(y * *width + x) * 3 + 2];
-                        Srgb::new(
-                            0.7 * *pixel_red as f32 / 255.0,
-                            0.7 * *pixel_green as f32 / 255.0,
-                            0.7 * *pixel_blue as f32 / 255.0,
-                        )
-                    }
-                },
+            if pdf > 0.0 {
+                for light in &scene.lights {
+                    let direct = sample_light_source(ray, &hit_record, albedo, world, light);
+                    color.red += direct.red;
+                    color.green += direct.green;
+                    color.blue += direct.blue;
+                }
+                if let Some(environment) = environment(scene) {
+                    let direct = sample_environment(ray, &hit_record, albedo, world, environment);
+                    color.red += direct.red;
+                    color.green += direct.green;
+                    color.blue += direct.blue;
+                }
             }
+            let target_color = ray_color(
+                &scattered_ray,
+                scene,
+                world,
+                lights,
+                depth - 1,
+                (pdf > 0.0).then_some(pdf),
+            );
+            Srgb::new(
+                color.red + albedo.red * target_color.red,
+                color.green + albedo.green * target_color.green,
+                color.blue + albedo.blue * target_color.blue,
+            )
         }
+        None => match environment(scene) {
+            Some(environment) => {
+                let weight = match scattering_pdf {
+                    Some(pdf) => mis_weight(pdf, environment.pdf(ray.direction)) as f32,
+                    None => 1.0,
+                };
+                let radiance = environment.radiance(ray.direction);
+                Srgb::new(
+                    weight * radiance.red,
+                    weight * radiance.green,
+                    weight * radiance.blue,
+                )
+            }
+            None if scene.sky.is_some() => {
+                let t: f32 = clamp(0.5 * (ray.direction.unit_vector().y() as f32 + 1.0));
+                Srgb::new(
+                    (1.0 - t) * 1.0 + t * 0.5,
+                    (1.0 - t) * 1.0 + t * 0.7,
+                    (1.0 - t) * 1.0 + t * 1.0,
+                )
+            }
+            None => Srgb::new(0.0, 0.0, 0.0),
+        },
     }
 }
 
@@ -177,12 +393,27 @@ fn test_ray_color() {
             1.333,
         ),
         objects: Vec::new(),
+        lights: Vec::new(),
+        brute_force: false,
+        geometries: BTreeMap::new(),
+        animation: None,
     };
+    let world = World::new(&scene.objects, scene.brute_force);
     let l = Vec::new();
-    assert_eq!(ray_color(&r, &scene, &l, 2, 2), Srgb::new(0.75, 0.85, 1.0));
+    assert_eq!(
+        ray_color(&r, &scene, &world, &l, 2, None),
+        Srgb::new(0.75, 0.85, 1.0)
+    );
 }
 
-fn render_line(pixels: &mut [u8], scene: &Config, lights: &Vec<Sphere>, y: usize) {
+fn render_line(
+    pixels: &mut [u8],
+    scene: &Config,
+    camera: &Camera,
+    world: &World,
+    lights: &[&Body],
+    y: usize,
+) {
     let mut rng = rand::thread_rng();
 
     let bounds = (scene.width, scene.height);
@@ -192,17 +423,17 @@ fn render_line(pixels: &mut [u8], scene: &Config, lights: &Vec<Sphere>, y: usize
         for _s in 0..scene.samples_per_pixel {
             let u = (x as f64 + rng.gen::<f64>()) / (bounds.0 as f64 - 1.0);
             let v = (bounds.1 as f64 - (y as f64 + rng.gen::<f64>())) / (bounds.1 as f64 - 1.0);
-            let r = scene.camera.get_ray(u, v);
-            let c = ray_color(&r, scene, lights, scene.max_depth, scene.max_depth);
+            let r = camera.get_ray(u, v);
+            let c = ray_color(&r, scene, world, lights, scene.max_depth, None);
             pixel_colors[0] += c.red;
             pixel_colors[1] += c.green;
             pixel_colors[2] += c.blue;
         }
         let scale = 1.0 / scene.samples_per_pixel as f32;
         let color = Srgb::new(
-            (scale * pixel_colors[0]).sqrt(),
-            (scale * pixel_colors[1]).sqrt(),
-            (scale * pixel_colors[2]).sqrt(),
+            clamp((scale * pixel_colors[0]).sqrt()),
+            clamp((scale * pixel_colors[1]).sqrt()),
+            clamp((scale * pixel_colors[2]).sqrt()),
         );
         let (r, g, b) = color.into_format().into_components();
         pixels[x * 3] = r;
@@ -211,33 +442,53 @@ fn render_line(pixels: &mut [u8], scene: &Config, lights: &Vec<Sphere>, y: usize
     }
 }
 
-fn find_lights(world: &Vec<Body>) -> Vec<Sphere> {
+// The bodies that give off light and can be aimed at, which leaves out
+// the ones without bounds.
+fn find_lights(world: &[Body]) -> Vec<&Body> {
     world
         .iter()
-        .flat_map(|x| match x {
-            Body::Sphere(s) => Some(s),
-            _ => None,
-        })
-        .filter(|s| match s.material {
-            Material::Light(_) => true,
-            _ => false,
-        })
-        .cloned()
+        .filter(|body| body.is_emissive() && body.bounding_box().is_some())
         .collect()
 }
 
-pub fn render(filename: &Path, scene: Config) -> Result<()> {
-    let image_width = scene.width;
-    let image_height = scene.height;
+pub fn render(filename: &Path, scene: &Config) -> Result<()> {
+    let views = scene
+        .camera
+        .with_image_size(scene.width, scene.height)
+        .views();
+    let side_by_side = scene
+        .camera
+        .stereo
+        .is_some_and(|s| s.layout == StereoLayout::SideBySide);
+    let (image_width, image_height) = if side_by_side {
+        (scene.width * views.len(), scene.height)
+    } else {
+        (scene.width, scene.height * views.len())
+    };
 
     let mut pixels = vec![0; image_width * image_height * 3];
-    let bands: Vec<(usize, &mut [u8])> = pixels.chunks_mut(image_width * 3).enumerate().collect();
+    // one line of one view each
+    let bands: Vec<(usize, usize, &mut [u8])> = pixels
+        .chunks_mut(scene.width * 3)
+        .enumerate()
+        .map(|(i, band)| {
+            if side_by_side {
+                (i % views.len(), i / views.len(), band)
+            } else {
+                (i / scene.height, i % scene.height, band)
+            }
+        })
+        .collect();
 
     let lights = find_lights(&scene.objects);
 
     let start = Instant::now();
-    bands.into_par_iter().for_each(|(i, band)| {
-        render_line(band, &scene, &lights, i);
+    let world = World::new(&scene.objects, scene.brute_force);
+    if !scene.brute_force {
+        info!("BVH build time: {}ms", start.elapsed().as_millis());
+    }
+    bands.into_par_iter().for_each(|(view, i, band)| {
+        render_line(band, scene, &views[view], &world, &lights, i);
     });
     info!("Frame time: {}ms", start.elapsed().as_millis());
 
@@ -246,12 +497,17 @@ pub fn render(filename: &Path, scene: Config) -> Result<()> {
 }
 #[cfg(test)]
 mod tests {
-    use crate::body::Body;
+    use super::*;
+    use crate::body::{
+        Body, Csg, Ellipsoid, Instance, Operation, Placement, Plane, Quad, Sphere, Transform,
+    };
     use crate::config::Config;
-    use crate::materials::Material;
+    use crate::lights::RectLight;
+    use crate::materials::{Emissive, Material};
     use crate::point3d::Point3D;
     use crate::raytracer::{find_lights, render};
     use std::path::Path;
+    use std::sync::Arc;
 
     #[test]
     fn test_find_lights() {
@@ -266,8 +522,232 @@ mod tests {
                 0.5,
                 Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
             )),
+            Body::Transform(
+                Transform::new(
+                    Placement {
+                        translate: Some(Point3D::new(2.0, 0.0, 0.0)),
+                        ..Placement::default()
+                    },
+                    Body::Ellipsoid(Ellipsoid::new(
+                        Point3D::new(0.0, 1.0, 0.0),
+                        Point3D::new(1.0, 0.5, 1.0),
+                        Material::Emissive(Emissive::new(Srgb::new(1.0, 0.5, 0.0), 5.0)),
+                    )),
+                )
+                .unwrap(),
+            ),
+            Body::Instance(
+                Instance::new(
+                    Arc::new(Body::Sphere(Sphere::new(
+                        Point3D::new(0.0, 0.0, 0.0),
+                        0.5,
+                        Material::Emissive(Emissive::new(Srgb::new(1.0, 1.0, 1.0), 2.0)),
+                    ))),
+                    "bulb",
+                    Placement {
+                        translate: Some(Point3D::new(-2.0, 0.0, 0.0)),
+                        ..Placement::default()
+                    },
+                )
+                .unwrap(),
+            ),
+            Body::Csg(Csg::new(
+                Operation::Difference,
+                Body::Sphere(Sphere::new(
+                    Point3D::new(0.0, -3.0, 0.0),
+                    1.0,
+                    Material::Emissive(Emissive::new(Srgb::new(1.0, 1.0, 1.0), 1.0)),
+                )),
+                Body::Sphere(Sphere::new(
+                    Point3D::new(0.0, -2.0, 0.0),
+                    0.5,
+                    Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+                )),
+            )),
+            // glows where it is hit, but can't be aimed at
+            Body::Plane(Plane::new(
+                Point3D::new(0.0, 10.0, 0.0),
+                Point3D::new(0.0, -1.0, 0.0),
+                Material::Emissive(Emissive::new(Srgb::new(1.0, 1.0, 1.0), 1.0)),
+            )),
         ];
-        assert_eq!(find_lights(&world).len(), 1);
+        let lights = find_lights(&world);
+        assert_eq!(lights.len(), 4);
+        // the sphere fills a cone with cos(theta) = sqrt(1 - 0.5^2 / 3^2),
+        // every direction picked in it hits the sphere
+        let origin = Point3D::new(0.0, 0.0, 2.0);
+        let cone = 2.0 * std::f64::consts::PI * (1.0 - (1.0f64 - 0.25 / 9.0).sqrt());
+        for _ in 0..100 {
+            let direction = lights[0].random_direction_towards(origin, 0.0);
+            assert!((lights[0].pdf_towards(origin, direction, 0.0) - 1.0 / cone).abs() < 1e-9);
+            assert!(lights[0]
+                .hit(&Ray::new(origin, direction), 0.0, f64::INFINITY)
+                .is_some());
+            let direction = lights[1].random_direction_towards(origin, 0.0);
+            assert!(lights[1].pdf_towards(origin, direction, 0.0) > 0.0);
+        }
+        assert_eq!(
+            lights[0].pdf_towards(origin, Point3D::new(0.0, 0.0, 1.0), 0.0),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_light_sampling_converges() {
+        // a diffuse floor under a glowing sphere that fills a cone with
+        // sin(theta) = 1/2 seen from the origin, which sends
+        // albedo * radiance * sin(theta)^2 = 0.125 back up
+        let scene = Config {
+            width: 1,
+            height: 1,
+            samples_per_pixel: 1,
+            max_depth: 3,
+            sky: None,
+            camera: Camera::new(
+                Point3D::new(3.0, 1.0, 0.0),
+                Point3D::new(0.0, 0.0, 0.0),
+                Point3D::new(0.0, 1.0, 0.0),
+                20.0,
+                1.0,
+            ),
+            objects: vec![
+                Body::Plane(Plane::new(
+                    Point3D::new(0.0, 0.0, 0.0),
+                    Point3D::new(0.0, 1.0, 0.0),
+                    Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+                )),
+                Body::Sphere(Sphere::new(
+                    Point3D::new(0.0, 2.0, 0.0),
+                    1.0,
+                    Material::Emissive(Emissive::new(Srgb::new(1.0, 1.0, 1.0), 1.0)),
+                )),
+            ],
+            lights: Vec::new(),
+            brute_force: true,
+            geometries: BTreeMap::new(),
+            animation: None,
+        };
+        let world = World::new(&scene.objects, scene.brute_force);
+        let ray = Ray::new(Point3D::new(3.0, 1.0, 0.0), Point3D::new(-3.0, -1.0, 0.0));
+        let estimate = |lights: &[&Body]| {
+            let n = 40000;
+            let samples: Vec<f64> = (0..n)
+                .map(|_| ray_color(&ray, &scene, &world, lights, scene.max_depth, None).red as f64)
+                .collect();
+            let mean = samples.iter().sum::<f64>() / n as f64;
+            let variance = samples.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / n as f64;
+            (mean, variance)
+        };
+        // only scattering, the brute force reference
+        let (reference, reference_variance) = estimate(&[]);
+        assert!((reference - 0.125).abs() < 0.005, "{}", reference);
+        let lights = find_lights(&scene.objects);
+        let (mean, variance) = estimate(&lights);
+        assert!((mean - 0.125).abs() < 0.002, "{}", mean);
+        assert!(variance < reference_variance / 4.0);
+
+        // the light itself is seen at its full brightness
+        let at_light = Ray::new(Point3D::new(0.0, 5.0, 0.0), Point3D::new(0.0, -1.0, 0.0));
+        let color = ray_color(&at_light, &scene, &world, &lights, 3, None);
+        assert_eq!(color.red, 1.0);
+    }
+
+    #[test]
+    fn test_light_sources() {
+        let json = r#"{
+            "width": 1, "height": 1, "samples_per_pixel": 1, "max_depth": 2, "sky": null,
+            "camera": {"look_from": {"x": 3.0, "y": 1.0, "z": 0.0}, "look_at": {"x": 0.0, "y": 0.0, "z": 0.0},
+                       "vup": {"x": 0.0, "y": 1.0, "z": 0.0}, "vfov": 20.0},
+            "objects": [{"type": "Plane", "point": {"x": 0.0, "y": 0.0, "z": 0.0}, "normal": {"x": 0.0, "y": 1.0, "z": 0.0},
+                         "material": {"Lambertian": {"albedo": [0.5, 0.5, 0.5]}}}],
+            "lights": [{"type": "Point", "position": {"x": 0.0, "y": 2.0, "z": 0.0}, "intensity": 4.0}]
+        }"#;
+        let mut scene = serde_json::from_str::<Config>(json).unwrap();
+        let ray = Ray::new(Point3D::new(3.0, 1.0, 0.0), Point3D::new(-3.0, -1.0, 0.0));
+        let color = |scene: &Config| {
+            let world = World::new(&scene.objects, true);
+            let lights = find_lights(&scene.objects);
+            let n = 20000;
+            (0..n)
+                .map(|_| ray_color(&ray, scene, &world, &lights, scene.max_depth, None).red as f64)
+                .sum::<f64>()
+                / n as f64
+        };
+        // a diffuse surface sends albedo / pi of the light it gets back
+        assert!((color(&scene) - 0.5 / std::f64::consts::PI).abs() < 1e-6);
+
+        // a rect light lights the floor like an emissive quad in its place
+        let corner = Point3D::new(-0.5, 2.0, -0.5);
+        let (u, v) = (Point3D::new(1.0, 0.0, 0.0), Point3D::new(0.0, 0.0, 1.0));
+        scene.lights = vec![LightSource::Rect(
+            RectLight::new(corner, u, v, [1.0, 1.0, 1.0], 2.0).unwrap(),
+        )];
+        let from_light = color(&scene);
+        scene.lights.clear();
+        scene.objects.push(Body::Quad(Quad::new(
+            corner,
+            u,
+            v,
+            Material::Emissive(Emissive::new(Srgb::new(1.0, 1.0, 1.0), 2.0)),
+        )));
+        let from_quad = color(&scene);
+        assert!(
+            (from_light - from_quad).abs() < 0.005,
+            "{} {}",
+            from_light,
+            from_quad
+        );
+    }
+
+    #[test]
+    fn test_environment_sampling_converges() {
+        // a diffuse floor under a sky that only glows within 30 degrees of
+        // straight up, which sends albedo * radiance * sin(30)^2 = 0.25 back
+        let (width, height) = (8, 12);
+        let pixels = (0..width * height)
+            .map(|i| if i < 2 * width { [2.0; 3] } else { [0.0; 3] })
+            .collect();
+        let mut environment = Environment::new(pixels, width, height).unwrap();
+        environment.rotation = 10.0;
+        let scene = Config {
+            width: 1,
+            height: 1,
+            samples_per_pixel: 1,
+            max_depth: 2,
+            sky: Some(Sky {
+                texture: None,
+                environment: Some(environment),
+            }),
+            camera: Camera::new(
+                Point3D::new(3.0, 1.0, 0.0),
+                Point3D::new(0.0, 0.0, 0.0),
+                Point3D::new(0.0, 1.0, 0.0),
+                20.0,
+                1.0,
+            ),
+            objects: vec![Body::Plane(Plane::new(
+                Point3D::new(0.0, 0.0, 0.0),
+                Point3D::new(0.0, 1.0, 0.0),
+                Material::Lambertian(Lambertian::new(Srgb::new(0.5, 0.5, 0.5))),
+            ))],
+            lights: Vec::new(),
+            brute_force: true,
+            geometries: BTreeMap::new(),
+            animation: None,
+        };
+        let world = World::new(&scene.objects, scene.brute_force);
+        let ray = Ray::new(Point3D::new(3.0, 1.0, 0.0), Point3D::new(-3.0, -1.0, 0.0));
+        let n = 20000;
+        let mean = (0..n)
+            .map(|_| ray_color(&ray, &scene, &world, &[], scene.max_depth, None).red as f64)
+            .sum::<f64>()
+            / n as f64;
+        assert!((mean - 0.25).abs() < 0.005, "{}", mean);
+
+        // the sky itself is seen as it is
+        let up = Ray::new(Point3D::new(0.0, 1.0, 0.0), Point3D::new(0.0, 1.0, 0.0));
+        let color = ray_color(&up, &scene, &world, &[], 2, None);
+        assert_eq!(color.red, 2.0);
     }
 
     #[test]
@@ -276,7 +756,7 @@ mod tests {
         let mut scene = serde_json::from_slice::<Config>(&json).expect("Unable to parse json");
         scene.width = 80;
         scene.height = 60;
-        render(Path::new("/tmp/test_scene.png"), scene).unwrap();
+        render(Path::new("/tmp/test_scene.png"), &scene).unwrap();
     }
 
     #[test]
@@ -285,6 +765,44 @@ mod tests {
         let mut scene = serde_json::from_slice::<Config>(&json).expect("Unable to parse json");
         scene.width = 40;
         scene.height = 30;
-        render(Path::new("/tmp/cover_scene.png"), scene).unwrap();
+        render(Path::new("/tmp/cover_scene.png"), &scene).unwrap();
+    }
+
+    #[test]
+    fn test_render_stereo() {
+        let json = fs::read("data/test_scene.json").expect("Unable to read file");
+        for (layout, size) in [
+            (StereoLayout::SideBySide, (80, 30)),
+            (StereoLayout::OverUnder, (40, 60)),
+        ] {
+            let mut scene = serde_json::from_slice::<Config>(&json).expect("Unable to parse json");
+            scene.width = 40;
+            scene.height = 30;
+            scene.camera = scene.camera.with_stereo(Some(crate::camera::Stereo {
+                layout,
+                eye_separation: 0.064,
+                convergence: None,
+            }));
+            render(Path::new("/tmp/test_stereo.png"), &scene).unwrap();
+            assert_eq!(
+                image::image_dimensions("/tmp/test_stereo.png").unwrap(),
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn test_world_bvh_matches_brute_force() {
+        let json = fs::read("data/cover_scene.json").expect("Unable to read file");
+        let scene = serde_json::from_slice::<Config>(&json).expect("Unable to parse json");
+        let bvh_world = World::new(&scene.objects, false);
+        let linear_world = World::new(&scene.objects, true);
+        for _ in 0..1000 {
+            let r = scene.camera.get_ray(rand::random(), rand::random());
+            assert_eq!(
+                bvh_world.hit(&r, 0.001, f64::MAX).map(|h| h.t),
+                linear_world.hit(&r, 0.001, f64::MAX).map(|h| h.t)
+            );
+        }
     }
 }